@@ -4,9 +4,15 @@ pub fn all() -> Vec<poise::Command<AppContext, anyhow::Error>> {
     vec![
         crate::discord::debug::debug(),
         crate::games::roll::roll(),
+        crate::games::roll::odds(),
         crate::users::rep::rep(),
+        crate::users::daily::daily(),
+        crate::users::admin::admin(),
+        crate::guilds::command::config(),
+        crate::jobs::command::jobs(),
         crate::games::guess::guess(),
         crate::roulette::command::roulette(),
         crate::sardines::command::sardines(),
+        crate::games::fair::verify(),
     ]
 }
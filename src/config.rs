@@ -1,26 +1,123 @@
+use chrono::NaiveDate;
 use chrono_tz::Tz;
 use std::env;
-use tracing::info;
+use std::fmt;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::firebase::client::EMULATOR_HOST_ENV;
+use crate::games::dice::DiceLimits;
 
 #[derive(Debug, Clone)]
 pub struct Config {
-    #[allow(dead_code)] // used by HTTP gateway (http is not implemented yet)
+    /// Port for the HTTP interactions endpoint.
     pub port: u16,
+    /// Safety-net poll for jobs enqueued by other processes; local jobs wake the queue on time.
     pub job_queue_poll_interval_ms: u64,
     pub min_players_before_rejoin: usize,
     pub sardines_expiry_seconds: u64,
+    pub daily_reward: i64,
     pub random_seed: String,
+    /// First day whose seeded values use v2 derivation; earlier days keep v1.
+    /// Unset once the migration is over, so every day uses the current version.
+    pub seed_v2_from: Option<NaiveDate>,
+    /// Replays an incident: every unseeded draw and new game seed comes from
+    /// this seed instead of the system's randomness.
+    pub replay_seed: Option<u64>,
+    pub dice_limits: DiceLimits,
     pub discord: DiscordConfig,
-    pub firebase: FirebaseConfig,
+    pub storage: StorageConfig,
+}
+
+/// Where slash commands are registered with Discord.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandRegistration {
+    /// Register once for every guild; Discord may take up to an hour to propagate changes.
+    Global,
+    /// Register in each guild as it becomes available; updates are instant.
+    Guild,
+}
+
+impl fmt::Display for CommandRegistration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Global => write!(f, "global"),
+            Self::Guild => write!(f, "guild"),
+        }
+    }
+}
+
+impl FromStr for CommandRegistration {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "global" => Ok(Self::Global),
+            "guild" => Ok(Self::Guild),
+            other => Err(anyhow::anyhow!("Unknown command registration: {other}")),
+        }
+    }
+}
+
+/// How Discord delivers interactions to the bot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionsMode {
+    /// Over the gateway websocket.
+    Gateway,
+    /// As signed webhooks to the interactions endpoint on `port`. The gateway
+    /// stays connected for guild events.
+    Http,
+}
+
+impl fmt::Display for InteractionsMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gateway => write!(f, "gateway"),
+            Self::Http => write!(f, "http"),
+        }
+    }
+}
+
+impl FromStr for InteractionsMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gateway" => Ok(Self::Gateway),
+            "http" => Ok(Self::Http),
+            other => Err(anyhow::anyhow!("Unknown interactions mode: {other}")),
+        }
+    }
+}
+
+/// Which database the bot keeps its state in.
+#[derive(Debug, Clone)]
+pub enum StorageConfig {
+    Firestore(FirebaseConfig),
+    /// A SQLite file at the given path, for self-hosting.
+    Sqlite(String),
+    /// Process memory; everything is lost on exit.
+    Memory,
+}
+
+impl fmt::Display for StorageConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Firestore(_) => write!(f, "firestore"),
+            Self::Sqlite(path) => write!(f, "sqlite ({path})"),
+            Self::Memory => write!(f, "memory"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct DiscordConfig {
     pub timezone: Tz,
     pub bot_token: String,
-    #[allow(dead_code)] // used by HTTP gateway for sig verification (http is not implemented yet)
+    /// Hex-encoded Ed25519 key used to verify HTTP interaction signatures.
     pub public_key: String,
-    pub server_id: String,
+    pub command_registration: CommandRegistration,
+    pub interactions: InteractionsMode,
 }
 
 #[derive(Debug, Clone)]
@@ -38,10 +135,50 @@ impl Config {
 
         let bot_token = required_env("BOT_TOKEN")?;
         let public_key = required_env("DISCORD_PUBLIC_KEY")?;
-        let server_id = required_env("DISCORD_SERVER_ID")?;
-        let firebase_64 = required_env("FIREBASE_64")?;
+        let storage = match env::var("STORAGE_BACKEND")
+            .as_deref()
+            .unwrap_or("firestore")
+        {
+            "firestore" => StorageConfig::Firestore(FirebaseConfig {
+                project_id: "brophylactic-gaming".to_string(),
+                // The emulator needs no credentials
+                cert_base64: if env::var(EMULATOR_HOST_ENV).is_ok() {
+                    env::var("FIREBASE_64").unwrap_or_default()
+                } else {
+                    required_env("FIREBASE_64")?
+                },
+            }),
+            "sqlite" => StorageConfig::Sqlite(
+                env::var("SQLITE_PATH").unwrap_or_else(|_| "discord-bot.sqlite".to_string()),
+            ),
+            "memory" => StorageConfig::Memory,
+            other => anyhow::bail!("ENV VAR STORAGE_BACKEND is invalid: {other}"),
+        };
         let random_seed =
             env::var("RANDOM_SEED").unwrap_or_else(|_| "discord-bot-default-seed".to_string());
+        let seed_v2_from = optional_env("SEED_V2_FROM")?;
+        let replay_seed = optional_env("REPLAY_SEED")?;
+        if replay_seed.is_some() {
+            warn!("REPLAY_SEED is set: game seeds are predictable, never use it in production");
+        }
+
+        let defaults = DiceLimits::default();
+        let dice_limits = DiceLimits {
+            max_dice: optional_env("DICE_MAX_DICE")?.unwrap_or(defaults.max_dice),
+            max_sides: optional_env("DICE_MAX_SIDES")?.unwrap_or(defaults.max_sides),
+            max_terms: optional_env("DICE_MAX_TERMS")?.unwrap_or(defaults.max_terms),
+        };
+
+        let daily_reward = optional_env("DAILY_REWARD")?.unwrap_or(10);
+
+        let command_registration = optional_env("COMMAND_REGISTRATION")?.unwrap_or(if is_dev {
+            CommandRegistration::Guild
+        } else {
+            CommandRegistration::Global
+        });
+
+        let interactions = optional_env("INTERACTIONS_MODE")?.unwrap_or(InteractionsMode::Gateway);
+        let port = optional_env("PORT")?.unwrap_or(8006);
 
         let min_players_before_rejoin = if is_dev { 1 } else { 4 };
 
@@ -52,32 +189,78 @@ impl Config {
         };
 
         info!(
-            "Config loaded: min_players_before_rejoin={}, sardines_expiry_seconds={}",
-            min_players_before_rejoin, sardines_expiry_seconds
+            "Config loaded: min_players_before_rejoin={}, sardines_expiry_seconds={}, command_registration={}, interactions={}, storage={}, seed_v2_from={:?}, dice_limits={:?}",
+            min_players_before_rejoin,
+            sardines_expiry_seconds,
+            command_registration,
+            interactions,
+            storage,
+            seed_v2_from,
+            dice_limits
         );
 
         Ok(Config {
-            port: 8006,
-            job_queue_poll_interval_ms: 5000,
+            port,
+            job_queue_poll_interval_ms: 60_000,
             min_players_before_rejoin,
             sardines_expiry_seconds,
+            daily_reward,
             random_seed,
+            seed_v2_from,
+            replay_seed,
+            dice_limits,
             discord: DiscordConfig {
                 timezone: "America/Los_Angeles"
                     .parse::<Tz>()
                     .map_err(|e| anyhow::anyhow!("Invalid timezone: {e}"))?,
                 bot_token,
                 public_key,
-                server_id,
-            },
-            firebase: FirebaseConfig {
-                project_id: "brophylactic-gaming".to_string(),
-                cert_base64: firebase_64,
+                command_registration,
+                interactions,
             },
+            storage,
         })
     }
 }
 
+#[cfg(test)]
+impl Config {
+    /// Production-like settings with placeholder secrets and in-memory storage.
+    pub fn for_tests() -> Self {
+        Self {
+            port: 8006,
+            job_queue_poll_interval_ms: 5000,
+            min_players_before_rejoin: 4,
+            sardines_expiry_seconds: 86400,
+            daily_reward: 10,
+            random_seed: "test".to_string(),
+            seed_v2_from: None,
+            replay_seed: None,
+            dice_limits: DiceLimits::default(),
+            discord: DiscordConfig {
+                timezone: chrono_tz::America::Los_Angeles,
+                bot_token: "test".to_string(),
+                public_key: "test".to_string(),
+                command_registration: CommandRegistration::Guild,
+                interactions: InteractionsMode::Gateway,
+            },
+            storage: StorageConfig::Memory,
+        }
+    }
+}
+
 fn required_env(name: &str) -> anyhow::Result<String> {
     env::var(name).map_err(|_| anyhow::anyhow!("ENV VAR {name} is required"))
 }
+
+fn optional_env<T>(name: &str) -> anyhow::Result<Option<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    env::var(name)
+        .ok()
+        .map(|v| v.parse::<T>())
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("ENV VAR {name} is invalid: {e}"))
+}
@@ -5,9 +5,12 @@ use poise::serenity_prelude as serenity;
 use tokio::sync::RwLock;
 
 use crate::config::Config;
+use crate::guilds::{GuildSettingsStore, Settings};
 use crate::jobs::JobQueue;
+use crate::storage::Storage;
 use crate::users::UserStoreApi;
-use firestore::FirestoreDb;
+use crate::users::leaderboard::MemberCache;
+use crate::util::random::RandomSource;
 
 /// Per-game lock to serialize concurrent join operations.
 /// Outer Mutex guards the map; inner RwLock guards each game's state.
@@ -17,11 +20,22 @@ pub type GameLocks = Arc<Mutex<HashMap<String, Arc<RwLock<()>>>>>;
 #[derive(Clone)]
 pub struct AppContext {
     pub config: Config,
-    pub db: FirestoreDb,
+    pub storage: Storage,
     pub http: Arc<serenity::Http>,
     pub user_store: Arc<dyn UserStoreApi>,
+    pub guild_settings: Arc<GuildSettingsStore>,
     pub job_queue: Arc<RwLock<JobQueue>>,
     pub game_locks: GameLocks,
+    pub member_cache: MemberCache,
+    pub random: Arc<dyn RandomSource>,
+}
+
+impl AppContext {
+    /// Resolve the effective settings for a guild: its overrides over the config defaults.
+    pub async fn settings(&self, guild_id: serenity::GuildId) -> anyhow::Result<Settings> {
+        let overrides = self.guild_settings.get(guild_id).await?;
+        Ok(overrides.resolve(&self.config))
+    }
 }
 
 /// Get or create a per-game lock for serializing concurrent operations.
@@ -1,7 +1,6 @@
-use crate::context::Context;
+use crate::context::{AppContext, Context};
 use crate::discord::helpers::{encode_custom_id, mention, rep_label};
 use crate::discord::types::InteractionType;
-use crate::util::random::random_inclusive;
 
 use poise::serenity_prelude as serenity;
 use serenity::{
@@ -31,10 +30,11 @@ pub async fn debug(ctx: Context<'_>) -> Result<(), anyhow::Error> {
 pub async fn handle_debug_button(
     ctx: &serenity::Context,
     interaction: &serenity::ComponentInteraction,
+    data: &AppContext,
 ) -> Result<(), anyhow::Error> {
     let custom_id = &interaction.data.custom_id;
     let user_id = interaction.user.id;
-    let value = random_inclusive(50, 100);
+    let value = data.random.range_inclusive(50, 100);
 
     let label = rep_label(value, true);
     let user_mention = mention(user_id);
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use poise::serenity_prelude as serenity;
+use ring::signature::{ED25519, UnparsedPublicKey};
+use tracing::{error, info, warn};
+
+use crate::context::AppContext;
+
+const SIGNATURE_HEADER: &str = "X-Signature-Ed25519";
+const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+/// Discord's interaction type for the endpoint health check.
+const PING: u64 = 1;
+
+/// Checks that a request body was signed by Discord with the application's key.
+pub struct Verifier {
+    public_key: Vec<u8>,
+}
+
+impl Verifier {
+    /// Build from the hex-encoded public key shown in the Discord developer portal.
+    pub fn new(public_key_hex: &str) -> anyhow::Result<Self> {
+        let public_key = hex::decode(public_key_hex.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid DISCORD_PUBLIC_KEY: {e}"))?;
+        Ok(Self { public_key })
+    }
+
+    /// Discord signs `timestamp || body`; the signature arrives hex-encoded.
+    pub fn verify(&self, signature_hex: &str, timestamp: &str, body: &[u8]) -> bool {
+        let Ok(signature) = hex::decode(signature_hex) else {
+            return false;
+        };
+        let message = [timestamp.as_bytes(), body].concat();
+        UnparsedPublicKey::new(&ED25519, &self.public_key)
+            .verify(&message, &signature)
+            .is_ok()
+    }
+}
+
+/// Everything needed to run poise's dispatch outside the gateway event loop.
+pub struct InteractionServer {
+    pub verifier: Verifier,
+    pub serenity_ctx: serenity::Context,
+    pub options: poise::FrameworkOptions<AppContext, anyhow::Error>,
+    pub data: AppContext,
+    pub shard_manager: Arc<serenity::ShardManager>,
+    pub bot_id: serenity::UserId,
+}
+
+/// Serve the interactions endpoint on the given port until the process exits.
+pub async fn serve(server: Arc<InteractionServer>, port: u16) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/interactions", post(handle_interaction))
+        .with_state(server);
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+    info!(port, "Interactions endpoint listening");
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn handle_interaction(
+    State(server): State<Arc<InteractionServer>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let (Some(signature), Some(timestamp)) = (header(SIGNATURE_HEADER), header(TIMESTAMP_HEADER))
+    else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    if !server.verifier.verify(signature, timestamp, &body) {
+        warn!("Rejected interaction with invalid signature");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let raw: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(v) => v,
+        Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    if raw.get("type").and_then(|t| t.as_u64()) == Some(PING) {
+        return axum::Json(serde_json::json!({ "type": PING })).into_response();
+    }
+
+    let interaction: serenity::Interaction = match serde_json::from_value(raw) {
+        Ok(i) => i,
+        Err(e) => {
+            error!(error = %e, "Failed to parse interaction");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    // Handlers respond through the interaction callback endpoint, exactly as
+    // they do for gateway interactions, so the webhook itself is only acknowledged.
+    tokio::spawn(async move {
+        let framework = poise::FrameworkContext {
+            bot_id: server.bot_id,
+            options: &server.options,
+            user_data: &server.data,
+            shard_manager: &server.shard_manager,
+        };
+        poise::dispatch_event(
+            framework,
+            &server.serenity_ctx,
+            serenity::FullEvent::InteractionCreate { interaction },
+        )
+        .await;
+    });
+
+    StatusCode::ACCEPTED.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn keypair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_signature() {
+        let key = keypair();
+        let verifier = Verifier::new(&hex::encode(key.public_key().as_ref())).unwrap();
+        let body = br#"{"type":1}"#;
+        let signature = key.sign(&[b"1700000000".as_slice(), body].concat());
+
+        assert!(verifier.verify(&hex::encode(signature.as_ref()), "1700000000", body));
+    }
+
+    #[test]
+    fn rejects_tampered_body_and_timestamp() {
+        let key = keypair();
+        let verifier = Verifier::new(&hex::encode(key.public_key().as_ref())).unwrap();
+        let body = br#"{"type":1}"#;
+        let signature = hex::encode(key.sign(&[b"1700000000".as_slice(), body].concat()));
+
+        assert!(!verifier.verify(&signature, "1700000000", br#"{"type":2}"#));
+        assert!(!verifier.verify(&signature, "1700000001", body));
+        assert!(!verifier.verify("not-hex", "1700000000", body));
+    }
+
+    #[test]
+    fn rejects_malformed_public_key() {
+        assert!(Verifier::new("zz").is_err());
+    }
+}
@@ -9,6 +9,8 @@ use tracing::info;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InteractionType {
     Debug,
+    RepHistory,
+    RepLeaderboard,
     Roulette,
     Sardines,
 }
@@ -17,6 +19,8 @@ impl fmt::Display for InteractionType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Debug => write!(f, "DEBUG"),
+            Self::RepHistory => write!(f, "REP_HISTORY"),
+            Self::RepLeaderboard => write!(f, "REP_LEADERBOARD"),
             Self::Roulette => write!(f, "ROULETTE"),
             Self::Sardines => write!(f, "SARDINES"),
         }
@@ -29,6 +33,8 @@ impl FromStr for InteractionType {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "DEBUG" => Ok(Self::Debug),
+            "REP_HISTORY" => Ok(Self::RepHistory),
+            "REP_LEADERBOARD" => Ok(Self::RepLeaderboard),
             "ROULETTE" => Ok(Self::Roulette),
             "SARDINES" => Ok(Self::Sardines),
             other => Err(anyhow::anyhow!("Unknown interaction type: {other}")),
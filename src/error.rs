@@ -0,0 +1,77 @@
+use thiserror::Error;
+
+use crate::storage::StorageError;
+
+/// Errors raised by the economy and game layer. Every variant except
+/// `StorageUnavailable` is caused by the user and is safe to show them as-is.
+#[derive(Debug, Error)]
+pub enum EconomyError {
+    #[error("You only have \u{211e}{balance} and need \u{211e}{amount}")]
+    InsufficientFunds { balance: i64, amount: i64 },
+    #[error("Bets must be a positive amount of \u{211e}")]
+    InvalidBet,
+    #[error("That game has already ended")]
+    GameNotFound,
+    #[error("That game is closed to new players")]
+    GameClosed,
+    #[error("You are already in this game")]
+    AlreadyJoined,
+    #[error(
+        "You are already in this game and cannot rejoin until the minimum player count of {min_players} is met"
+    )]
+    RejoinTooSoon { min_players: usize },
+    #[error("Your \u{211e} has been frozen by an admin")]
+    AccountFrozen,
+    #[error("That would take a balance past the most \u{211e} that can be stored")]
+    BalanceOverflow,
+    #[error("You already {activity} today")]
+    DailyLimitReached { activity: &'static str },
+    #[error("Storage is unavailable, please try again later")]
+    StorageUnavailable(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+pub type EconomyResult<T> = Result<T, EconomyError>;
+
+impl EconomyError {
+    /// True when the error was caused by the user rather than a bug or outage.
+    pub fn is_user_error(&self) -> bool {
+        !matches!(self, Self::StorageUnavailable(_))
+    }
+}
+
+impl From<StorageError> for EconomyError {
+    fn from(e: StorageError) -> Self {
+        Self::StorageUnavailable(Box::new(e))
+    }
+}
+
+/// Store helpers return `anyhow::Error`; unwrap an `EconomyError` if one is
+/// inside, otherwise treat it as a storage failure.
+impl From<anyhow::Error> for EconomyError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<EconomyError>() {
+            Ok(economy) => economy,
+            Err(other) => Self::StorageUnavailable(other.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_errors_are_not_user_errors() {
+        let err = EconomyError::from(anyhow::anyhow!("connection reset"));
+        assert!(matches!(err, EconomyError::StorageUnavailable(_)));
+        assert!(!err.is_user_error());
+    }
+
+    #[test]
+    fn wrapped_economy_errors_survive_anyhow() {
+        let wrapped: anyhow::Error = EconomyError::InvalidBet.into();
+        let err = EconomyError::from(wrapped);
+        assert!(matches!(err, EconomyError::InvalidBet));
+        assert!(err.is_user_error());
+    }
+}
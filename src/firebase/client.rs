@@ -1,16 +1,23 @@
 use base64::Engine;
 use firestore::*;
-use gcloud_sdk::TokenSourceType;
+use gcloud_sdk::{Source, Token, TokenSourceType};
 use tracing::info;
 
+/// Set to `host:port` of a running Firestore emulator to use it instead of Google Cloud.
+pub const EMULATOR_HOST_ENV: &str = "FIRESTORE_EMULATOR_HOST";
+
 /// Initialize a FirestoreDb from a base64-encoded service account JSON string.
 ///
 /// The `cert_base64` parameter is the FIREBASE_64 env var: a base64url-encoded
-/// JSON service account key.
+/// JSON service account key. It is ignored when `FIRESTORE_EMULATOR_HOST` is set.
 pub async fn create_firestore_db(
     project_id: &str,
     cert_base64: &str,
 ) -> anyhow::Result<FirestoreDb> {
+    if std::env::var(EMULATOR_HOST_ENV).is_ok() {
+        return create_emulator_db(project_id).await;
+    }
+
     let json_bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
         .decode(cert_base64)
         .or_else(|_| base64::engine::general_purpose::STANDARD.decode(cert_base64))?;
@@ -26,3 +33,31 @@ pub async fn create_firestore_db(
     info!(project_id, "Firestore client initialized");
     Ok(db)
 }
+
+/// Connect to the emulator at `FIRESTORE_EMULATOR_HOST`. Each project id gets
+/// its own isolated set of collections.
+pub async fn create_emulator_db(project_id: &str) -> anyhow::Result<FirestoreDb> {
+    let db = FirestoreDb::with_options_token_source(
+        FirestoreDbOptions::new(project_id.to_string()),
+        Vec::new(),
+        TokenSourceType::ExternalSource(Box::new(EmulatorToken)),
+    )
+    .await?;
+
+    info!(project_id, "Firestore emulator client initialized");
+    Ok(db)
+}
+
+/// The emulator accepts this fixed token in place of real credentials.
+struct EmulatorToken;
+
+#[async_trait::async_trait]
+impl Source for EmulatorToken {
+    async fn token(&self) -> gcloud_sdk::error::Result<Token> {
+        Ok(Token::new(
+            "Bearer".to_string(),
+            "owner".to_string().into(),
+            chrono::Utc::now() + chrono::Duration::hours(1),
+        ))
+    }
+}
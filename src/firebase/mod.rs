@@ -1,4 +1 @@
 pub mod client;
-pub mod store;
-
-pub use store::FirestoreStore;
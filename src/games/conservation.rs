@@ -0,0 +1,324 @@
+//! Conservation checks for the ℞ economy.
+//!
+//! Plays randomized lottery games against in-memory storage and checks that
+//! the guild's total ℞ moves by exactly the documented amount: nothing for a
+//! roulette game (the winner takes the pot) or a refunded game, and
+//! `payout - pot` for a sardines game (the winner takes the pot times the
+//! payout multiplier).
+//!
+//! Storage faults are injected one at a time into write batches, and failed
+//! finishes are retried the way the job queue retries them.
+
+use std::sync::{Arc, Mutex};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::discord::types::GuildMember;
+use crate::games::fair;
+use crate::games::lottery::Lottery;
+use crate::jobs::JobQueue;
+use crate::roulette::game::Roulette;
+use crate::sardines::game::Sardines;
+use crate::sardines::store::{SardinesStore, SardinesStoreApi};
+use crate::storage::{Backend, MemoryBackend, Storage, StorageResult, Write};
+use crate::users::{UserStore, UserStoreApi};
+use crate::util::random::{RandomSource, SeededRandom};
+
+const GUILD: &str = "guild";
+const SCENARIOS: u64 = 200;
+/// Faults are one at a time, so a finish should succeed on its first retry.
+const FINISH_ATTEMPTS: usize = 2;
+
+/// Arms a single failure for the n-th write batch from now.
+#[derive(Clone, Default)]
+struct Faults {
+    countdown: Arc<Mutex<Option<usize>>>,
+}
+
+impl Faults {
+    fn fail_nth_write(&self, n: usize) {
+        *self.countdown.lock().unwrap() = Some(n);
+    }
+
+    fn clear(&self) {
+        *self.countdown.lock().unwrap() = None;
+    }
+
+    fn should_fail(&self) -> bool {
+        let mut countdown = self.countdown.lock().unwrap();
+        match *countdown {
+            Some(0) => {
+                *countdown = None;
+                true
+            }
+            Some(n) => {
+                *countdown = Some(n - 1);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+struct FaultyBackend {
+    inner: MemoryBackend,
+    faults: Faults,
+}
+
+impl Backend for FaultyBackend {
+    fn get(&self, collection: &str, id: &str) -> StorageResult<Option<Value>> {
+        self.inner.get(collection, id)
+    }
+
+    fn list(&self, collection: &str) -> StorageResult<Vec<(String, Value)>> {
+        self.inner.list(collection)
+    }
+
+    fn apply(&mut self, writes: Vec<Write>) -> StorageResult<()> {
+        if self.faults.should_fail() {
+            let busy = rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY);
+            return Err(rusqlite::Error::SqliteFailure(busy, None).into());
+        }
+        self.inner.apply(writes)
+    }
+}
+
+/// One simulated guild: shared storage with fault injection and a seeded
+/// source of player decisions.
+struct Sim {
+    storage: Storage,
+    faults: Faults,
+    user_store: Arc<UserStore>,
+    random: Arc<dyn RandomSource>,
+    rng: StdRng,
+}
+
+impl Sim {
+    fn new(seed: u64) -> Self {
+        let faults = Faults::default();
+        let storage = Storage::local(FaultyBackend {
+            inner: MemoryBackend::default(),
+            faults: faults.clone(),
+        });
+        Self {
+            user_store: Arc::new(UserStore::new(storage.clone())),
+            storage,
+            faults,
+            random: Arc::new(SeededRandom::new(seed)),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Sometimes arm a fault for one of the next few write batches.
+    fn maybe_fault(&mut self) {
+        self.faults.clear();
+        if self.rng.gen_bool(0.3) {
+            let n = self.rng.gen_range(0..3);
+            self.faults.fail_nth_write(n);
+        }
+    }
+
+    async fn total_rep(&self) -> i64 {
+        self.user_store
+            .get_guild_reputation_offsets(GUILD)
+            .await
+            .unwrap()
+            .values()
+            .sum()
+    }
+}
+
+fn member(n: usize) -> GuildMember {
+    GuildMember {
+        id: format!("player{n}"),
+        guild_id: GUILD.to_string(),
+        username: format!("player{n}"),
+        joined_at: None,
+    }
+}
+
+/// Returns the roulette game id if it was started.
+async fn play_roulette(sim: &mut Sim) -> Option<String> {
+    let job_queue = JobQueue::new(sim.storage.clone());
+    let bet = sim.rng.gen_range(1..=100);
+    let mut game =
+        Roulette::init(sim.storage.clone(), &member(0), bet, sim.random.as_ref()).unwrap();
+
+    sim.maybe_fault();
+    if game
+        .start("token", &job_queue, 30, sim.user_store.as_ref())
+        .await
+        .is_err()
+    {
+        return None;
+    }
+
+    // Roulette turns away players who already joined, so each joins once
+    for n in 1..=sim.rng.gen_range(0..6) {
+        sim.maybe_fault();
+        let _ = game.add_player(&member(n), sim.user_store.as_ref()).await;
+    }
+
+    if sim.rng.gen_bool(0.5) {
+        sim.maybe_fault();
+        let _ = game.close().await;
+    }
+
+    sim.maybe_fault();
+    for _ in 0..FINISH_ATTEMPTS {
+        let game = Roulette::load(sim.storage.clone(), game.id())
+            .await
+            .unwrap();
+        if game.finish(sim.random.as_ref()).await.is_ok() {
+            return Some(game.id().to_string());
+        }
+    }
+    panic!("roulette game {} never finished", game.id());
+}
+
+/// Returns the sardines game id and the expected change in total ℞.
+async fn play_sardines(sim: &mut Sim, config: &Config) -> Option<(String, i64)> {
+    let store: Arc<dyn SardinesStoreApi> = Arc::new(SardinesStore::new(sim.storage.clone()));
+    let user_store: Arc<dyn UserStoreApi> = sim.user_store.clone();
+    let bet = sim.rng.gen_range(1..=100);
+    let mut game = Sardines::init(
+        store.clone(),
+        config,
+        user_store.clone(),
+        sim.random.clone(),
+        &member(0),
+        bet,
+    )
+    .unwrap();
+
+    sim.maybe_fault();
+    game.save().await.ok()?;
+
+    // Players may rejoin, and any join may end the game
+    let mut ended_by = None;
+    for _ in 0..sim.rng.gen_range(0..8) {
+        let joiner = member(sim.rng.gen_range(0..5));
+        sim.maybe_fault();
+        if game.add_player(&joiner).await.is_ok() && sim.rng.gen_bool(0.2) {
+            ended_by = Some(joiner.username);
+            break;
+        }
+    }
+
+    sim.maybe_fault();
+    for _ in 0..FINISH_ATTEMPTS {
+        let game = Sardines::load(
+            store.clone(),
+            config,
+            user_store.clone(),
+            sim.random.clone(),
+            game.id(),
+        )
+        .await
+        .unwrap();
+        let expected = if game.lottery.can_finish() {
+            game.get_payout() - game.lottery.pot_size()
+        } else {
+            0
+        };
+        if game.finish(ended_by.as_deref()).await.is_ok() {
+            return Some((game.id().to_string(), expected));
+        }
+    }
+    panic!("sardines game {} never finished", game.id());
+}
+
+#[test]
+fn lottery_pot_is_every_bet_and_the_winner_is_a_player() {
+    let mut rng = StdRng::seed_from_u64(0);
+    let random = SeededRandom::new(0);
+    for _ in 0..SCENARIOS {
+        let bet = rng.gen_range(1..=1000);
+        let mut lottery = Lottery::new(0, bet, &random).unwrap();
+        let players = rng.gen_range(1..10);
+        for n in 0..players {
+            lottery.add_player(n);
+        }
+
+        assert_eq!(lottery.pot_size(), bet * players as i64);
+        assert!(lottery.players.contains(&lottery.finish(&random).winner));
+    }
+}
+
+#[tokio::test]
+async fn roulette_neither_creates_nor_destroys_rep() {
+    for seed in 0..SCENARIOS {
+        let mut sim = Sim::new(seed);
+        let id = play_roulette(&mut sim).await;
+        sim.faults.clear();
+
+        assert_eq!(sim.total_rep().await, 0, "seed {seed}");
+        if let Some(id) = id {
+            let left = Roulette::load(sim.storage.clone(), &id).await;
+            assert!(left.is_err(), "seed {seed}: game was not removed");
+        }
+    }
+}
+
+#[tokio::test]
+async fn a_retried_finish_pays_once() {
+    // Fail each of the finish's writes in turn, including the delete that
+    // follows the payout, then finish once more from a copy loaded earlier
+    for n in 0..3 {
+        let sim = Sim::new(n as u64);
+        let job_queue = JobQueue::new(sim.storage.clone());
+        let mut game =
+            Roulette::init(sim.storage.clone(), &member(0), 50, sim.random.as_ref()).unwrap();
+        game.start("token", &job_queue, 30, sim.user_store.as_ref())
+            .await
+            .unwrap();
+        for p in 1..3 {
+            game.add_player(&member(p), sim.user_store.as_ref())
+                .await
+                .unwrap();
+        }
+        let stale = Roulette::load(sim.storage.clone(), game.id())
+            .await
+            .unwrap();
+
+        sim.faults.fail_nth_write(n);
+        let mut finished = false;
+        for _ in 0..FINISH_ATTEMPTS {
+            let game = Roulette::load(sim.storage.clone(), game.id())
+                .await
+                .unwrap();
+            if game.finish(sim.random.as_ref()).await.is_ok() {
+                finished = true;
+                break;
+            }
+        }
+        assert!(finished, "fault {n}: game never finished");
+        assert!(
+            stale.finish(sim.random.as_ref()).await.is_err(),
+            "fault {n}"
+        );
+        assert_eq!(sim.total_rep().await, 0, "fault {n}");
+        let reveal = fair::get_reveal(&sim.storage, game.id()).await.unwrap();
+        assert!(reveal.is_some_and(|r| r.is_consistent()), "fault {n}");
+    }
+}
+
+#[tokio::test]
+async fn sardines_only_mints_the_payout_bonus() {
+    let config = Config::for_tests();
+    for seed in 0..SCENARIOS {
+        let mut sim = Sim::new(seed);
+        let outcome = play_sardines(&mut sim, &config).await;
+        sim.faults.clear();
+
+        let expected = outcome.as_ref().map_or(0, |(_, delta)| *delta);
+        assert_eq!(sim.total_rep().await, expected, "seed {seed}");
+        if let Some((id, _)) = outcome {
+            let store = SardinesStore::new(sim.storage.clone());
+            assert!(store.get(&id).await.unwrap().is_none(), "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,645 @@
+//! Dice expressions for `/roll`.
+//!
+//! An expression adds and subtracts dice terms and constants, e.g.
+//! `1d8+1d6-2`. A dice term is `[count]d<size>`, where the count defaults to
+//! one, `d%` is a d100 and `dF` is a fudge die (-1, 0 or +1), followed by
+//! any of these modifiers:
+//!
+//! - `!` explodes: each die showing its highest face adds another die
+//! - `rN` rerolls a die until it stops showing `N`
+//! - `khN`/`klN` keep the highest/lowest `N` dice (`kN` is `khN`)
+//! - `dhN`/`dlN` drop the highest/lowest `N` dice (`dN` is `dlN`)
+
+mod parse;
+pub mod stats;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::util::random::RandomSource;
+
+pub use parse::parse;
+
+/// A single die can only explode this many times, so an unlucky streak
+/// cannot keep a roll going forever.
+const MAX_EXPLOSIONS: u32 = 100;
+/// Terms with more dice than this are summed as they are rolled instead of
+/// keeping every die for the breakdown.
+const LISTED_DICE: u32 = 50;
+
+#[derive(Debug, Error)]
+pub enum DiceError {
+    #[error("Missing dice parameter.")]
+    MissingInput,
+    #[error("Unexpected `{0}`. Try something like 2d6+3, 4d6kh3 or d20")]
+    Unexpected(String),
+    #[error("The roll ends too early. Try something like 2d6+3, 4d6kh3 or d20")]
+    UnexpectedEnd,
+    #[error("{0} is too large")]
+    NumberTooLarge(String),
+    #[error("Die size cannot be 0")]
+    ZeroSize,
+    #[error("You can roll at most {0} dice at once")]
+    TooManyDice(u64),
+    #[error("Dice can have at most {0} sides")]
+    TooManySides(u32),
+    #[error("A roll can add up at most {0} dice and numbers")]
+    TooManyTerms(usize),
+    #[error("That roll is too complex to work out the odds exactly")]
+    TooComplex,
+    #[error("A die with one face cannot explode, it would never stop")]
+    EndlessExplosion,
+    #[error("That reroll covers every face of the die, it would never stop")]
+    EndlessReroll,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sign {
+    Plus,
+    Minus,
+}
+
+impl Sign {
+    fn apply(self, value: i64) -> i64 {
+        match self {
+            Sign::Plus => value,
+            Sign::Minus => -value,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Sign::Plus => "+",
+            Sign::Minus => "-",
+        }
+    }
+}
+
+/// A parsed expression: each term with the sign in front of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub terms: Vec<(Sign, Term)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Dice(DiceTerm),
+    Constant(i64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiceTerm {
+    pub count: u32,
+    pub die: Die,
+    pub keep: Option<Keep>,
+    pub explode: bool,
+    /// Face that is rerolled whenever it comes up.
+    pub reroll: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Die {
+    Sided(u32),
+    Fudge,
+}
+
+impl Die {
+    pub fn min(self) -> i64 {
+        match self {
+            Die::Sided(_) => 1,
+            Die::Fudge => -1,
+        }
+    }
+
+    pub fn max(self) -> i64 {
+        match self {
+            Die::Sided(size) => i64::from(size),
+            Die::Fudge => 1,
+        }
+    }
+
+    fn roll(self, random: &dyn RandomSource) -> i64 {
+        random.range_inclusive(self.min(), self.max())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Highest(u32),
+    Lowest(u32),
+    DropHighest(u32),
+    DropLowest(u32),
+}
+
+impl Keep {
+    /// The ranks kept out of `len` dice sorted lowest first.
+    fn kept_ranks(self, len: u64) -> Range<u64> {
+        let n = u64::from(match self {
+            Keep::Highest(n) | Keep::Lowest(n) | Keep::DropHighest(n) | Keep::DropLowest(n) => n,
+        });
+        match self {
+            Keep::Highest(_) => len.saturating_sub(n)..len,
+            Keep::Lowest(_) => 0..n.min(len),
+            Keep::DropHighest(_) => 0..len.saturating_sub(n),
+            Keep::DropLowest(_) => n.min(len)..len,
+        }
+    }
+
+    /// Which of `values` are kept, in the same order.
+    fn select(self, values: &[i64]) -> Vec<bool> {
+        let mut ascending: Vec<usize> = (0..values.len()).collect();
+        ascending.sort_by_key(|&i| values[i]);
+
+        let ranks = self.kept_ranks(values.len() as u64);
+        let mut selected = vec![false; values.len()];
+        for &i in &ascending[ranks.start as usize..ranks.end as usize] {
+            selected[i] = true;
+        }
+        selected
+    }
+
+    /// The sum of the kept dice, given how many times each face came up.
+    fn sum_counted(self, faces: &BTreeMap<i64, u64>, len: u64) -> i64 {
+        let ranks = self.kept_ranks(len);
+        let mut sum = 0i64;
+        let mut rank = 0u64;
+        for (&face, &count) in faces {
+            let kept = (rank + count)
+                .min(ranks.end)
+                .saturating_sub(rank.max(ranks.start));
+            sum += face * kept as i64;
+            rank += count;
+        }
+        sum
+    }
+}
+
+/// One die in a rolled term.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DieRoll {
+    pub value: i64,
+    pub kept: bool,
+    /// It showed the highest face and added another die.
+    pub exploded: bool,
+    /// Faces it showed before a reroll, in order.
+    pub rerolled: Vec<i64>,
+}
+
+/// A rolled term and the dice behind it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermRoll {
+    pub sign: Sign,
+    pub term: Term,
+    /// Every die rolled, or `None` for a term with too many dice to list.
+    /// Empty for a constant.
+    pub dice: Option<Vec<DieRoll>>,
+    /// How many dice were rolled, counting explosions.
+    pub rolled: u64,
+    /// The term's value before its sign is applied.
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollResult {
+    pub terms: Vec<TermRoll>,
+    pub total: i64,
+}
+
+/// Caps on what a single expression may ask for, so one command cannot tie
+/// up the bot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiceLimits {
+    /// Dice across the whole expression, not counting explosions.
+    pub max_dice: u64,
+    pub max_sides: u32,
+    /// Dice terms and constants in the expression.
+    pub max_terms: usize,
+}
+
+impl Default for DiceLimits {
+    fn default() -> Self {
+        Self {
+            max_dice: 100_000,
+            max_sides: 1_000_000,
+            max_terms: 20,
+        }
+    }
+}
+
+impl DiceLimits {
+    pub fn check(&self, expr: &Expr) -> Result<(), DiceError> {
+        if expr.terms.len() > self.max_terms {
+            return Err(DiceError::TooManyTerms(self.max_terms));
+        }
+        let mut dice = 0u64;
+        for (_, term) in &expr.terms {
+            if let Term::Dice(term) = term {
+                if let Die::Sided(size) = term.die
+                    && size > self.max_sides
+                {
+                    return Err(DiceError::TooManySides(self.max_sides));
+                }
+                dice += u64::from(term.count);
+            }
+        }
+        if dice > self.max_dice {
+            return Err(DiceError::TooManyDice(self.max_dice));
+        }
+        Ok(())
+    }
+}
+
+/// Parse and roll an expression like `2d6+3`.
+pub fn roll(
+    input: &str,
+    limits: &DiceLimits,
+    random: &dyn RandomSource,
+) -> Result<RollResult, DiceError> {
+    let expr = parse(input)?;
+    limits.check(&expr)?;
+    Ok(expr.roll(random))
+}
+
+impl Expr {
+    /// Roll every term. Call [`DiceLimits::check`] first for untrusted input.
+    pub fn roll(&self, random: &dyn RandomSource) -> RollResult {
+        let terms: Vec<TermRoll> = self
+            .terms
+            .iter()
+            .map(|(sign, term)| match term {
+                Term::Dice(dice) => dice.roll(*sign, random),
+                Term::Constant(n) => TermRoll {
+                    sign: *sign,
+                    term: term.clone(),
+                    dice: Some(Vec::new()),
+                    rolled: 0,
+                    value: *n,
+                },
+            })
+            .collect();
+        let total = terms
+            .iter()
+            .fold(0i64, |total, t| total.saturating_add(t.sign.apply(t.value)));
+        RollResult { terms, total }
+    }
+}
+
+impl DiceTerm {
+    fn roll(&self, sign: Sign, random: &dyn RandomSource) -> TermRoll {
+        let (dice, rolled, value) = if self.count <= LISTED_DICE {
+            let dice = self.roll_listed(random);
+            let value = dice.iter().filter(|d| d.kept).map(|d| d.value).sum();
+            let rolled = dice.len() as u64;
+            (Some(dice), rolled, value)
+        } else {
+            let (rolled, value) = self.roll_summed(random);
+            (None, rolled, value)
+        };
+        TermRoll {
+            sign,
+            term: Term::Dice(self.clone()),
+            dice,
+            rolled,
+            value,
+        }
+    }
+
+    fn roll_listed(&self, random: &dyn RandomSource) -> Vec<DieRoll> {
+        let mut dice = Vec::with_capacity(self.count as usize);
+        self.each_die(random, |die| dice.push(die));
+
+        if let Some(keep) = self.keep {
+            let values: Vec<i64> = dice.iter().map(|d| d.value).collect();
+            for (die, kept) in dice.iter_mut().zip(keep.select(&values)) {
+                die.kept = kept;
+            }
+        }
+        dice
+    }
+
+    /// Sum the dice as they are rolled. A keep or drop only needs to know how
+    /// often each face came up, not the order, so memory grows with the
+    /// number of distinct faces rather than the number of dice.
+    fn roll_summed(&self, random: &dyn RandomSource) -> (u64, i64) {
+        let mut rolled = 0u64;
+        let mut sum = 0i64;
+        let mut faces: BTreeMap<i64, u64> = BTreeMap::new();
+        self.each_die(random, |die| {
+            rolled += 1;
+            match self.keep {
+                Some(_) => *faces.entry(die.value).or_default() += 1,
+                None => sum += die.value,
+            }
+        });
+        if let Some(keep) = self.keep {
+            sum = keep.sum_counted(&faces, rolled);
+        }
+        (rolled, sum)
+    }
+
+    /// Roll each die in turn, followed by any dice its explosions add.
+    fn each_die(&self, random: &dyn RandomSource, mut f: impl FnMut(DieRoll)) {
+        for _ in 0..self.count {
+            let mut explosions = 0;
+            loop {
+                let die = self.roll_one(random);
+                let explodes =
+                    self.explode && die.value == self.die.max() && explosions < MAX_EXPLOSIONS;
+                f(DieRoll {
+                    exploded: explodes,
+                    ..die
+                });
+                if !explodes {
+                    break;
+                }
+                explosions += 1;
+            }
+        }
+    }
+
+    fn roll_one(&self, random: &dyn RandomSource) -> DieRoll {
+        let mut rerolled = Vec::new();
+        let mut value = self.die.roll(random);
+        while Some(value) == self.reroll {
+            rerolled.push(value);
+            value = self.die.roll(random);
+        }
+        DieRoll {
+            value,
+            kept: true,
+            exploded: false,
+            rerolled,
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (sign, term)) in self.terms.iter().enumerate() {
+            if i > 0 || *sign == Sign::Minus {
+                write!(f, "{}", sign.symbol())?;
+            }
+            write!(f, "{term}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Dice(dice) => write!(f, "{dice}"),
+            Term::Constant(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+impl fmt::Display for DiceTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d", self.count)?;
+        match self.die {
+            Die::Sided(size) => write!(f, "{size}")?,
+            Die::Fudge => write!(f, "F")?,
+        }
+        if self.explode {
+            write!(f, "!")?;
+        }
+        if let Some(face) = self.reroll {
+            write!(f, "r{face}")?;
+        }
+        match self.keep {
+            Some(Keep::Highest(n)) => write!(f, "kh{n}"),
+            Some(Keep::Lowest(n)) => write!(f, "kl{n}"),
+            Some(Keep::DropHighest(n)) => write!(f, "dh{n}"),
+            Some(Keep::DropLowest(n)) => write!(f, "dl{n}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl RollResult {
+    /// Every term with its dice, e.g. `4d6kh3 [6, 5, 3, ~~1~~] + 3`. Dropped
+    /// dice are struck through, exploded dice end in `!` and rerolled faces
+    /// are shown before the face that stayed.
+    pub fn breakdown(&self) -> String {
+        let mut out = String::new();
+        for (i, term) in self.terms.iter().enumerate() {
+            if i > 0 {
+                out.push_str(&format!(" {} ", term.sign.symbol()));
+            } else if term.sign == Sign::Minus {
+                out.push('-');
+            }
+            match &term.term {
+                Term::Dice(dice) => match &term.dice {
+                    Some(rolls) => {
+                        let faces: Vec<String> =
+                            rolls.iter().map(|d| format_die(d, dice.die)).collect();
+                        out.push_str(&format!("{dice} [{}]", faces.join(", ")));
+                    }
+                    None => out.push_str(&format!("{dice} [{} dice: {}]", term.rolled, term.value)),
+                },
+                Term::Constant(n) => out.push_str(&n.to_string()),
+            }
+        }
+        out
+    }
+}
+
+fn format_die(roll: &DieRoll, die: Die) -> String {
+    let face = |value: i64| match die {
+        Die::Fudge if value > 0 => "+".to_string(),
+        Die::Fudge if value < 0 => "-".to_string(),
+        _ => value.to_string(),
+    };
+    let mut out = String::new();
+    for value in &roll.rerolled {
+        out.push_str(&format!("{}→", face(*value)));
+    }
+    out.push_str(&face(roll.value));
+    if roll.exploded {
+        out.push('!');
+    }
+    if roll.kept { out } else { format!("~~{out}~~") }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::random::{ScriptedRandom, SeededRandom};
+
+    /// The draw that lands on `face` of a die with `sides` faces.
+    fn draw(sides: i64, face: i64) -> f64 {
+        (face as f64 - 0.5) / sides as f64
+    }
+
+    fn script(sides: i64, faces: &[i64]) -> ScriptedRandom {
+        faces.iter().fold(ScriptedRandom::new(), |random, face| {
+            random.then(draw(sides, *face))
+        })
+    }
+
+    #[test]
+    fn roll_produces_correct_count() {
+        let result = roll("4d6", &DiceLimits::default(), &SeededRandom::new(0)).unwrap();
+        let dice = result.terms[0].dice.as_ref().unwrap();
+        assert_eq!(dice.len(), 4);
+        for d in dice {
+            assert!(d.value >= 1 && d.value <= 6);
+        }
+        assert_eq!(result.total, dice.iter().map(|d| d.value).sum::<i64>());
+    }
+
+    #[test]
+    fn roll_empty_input() {
+        assert!(matches!(
+            roll("", &DiceLimits::default(), &SeededRandom::new(0)),
+            Err(DiceError::MissingInput)
+        ));
+    }
+
+    #[test]
+    fn adds_and_subtracts_terms() {
+        let result = roll(
+            "1d8+1d6-2",
+            &DiceLimits::default(),
+            &ScriptedRandom::new().then(draw(8, 5)).then(draw(6, 3)),
+        )
+        .unwrap();
+        assert_eq!(result.total, 5 + 3 - 2);
+        assert_eq!(result.breakdown(), "1d8 [5] + 1d6 [3] - 2");
+    }
+
+    #[test]
+    fn keeps_the_highest_dice() {
+        let result = roll("4d6kh3", &DiceLimits::default(), &script(6, &[3, 6, 1, 5])).unwrap();
+        assert_eq!(result.total, 14);
+        assert_eq!(result.breakdown(), "4d6kh3 [3, 6, ~~1~~, 5]");
+
+        let result = roll("2d20kl1", &DiceLimits::default(), &script(20, &[12, 4])).unwrap();
+        assert_eq!(result.total, 4);
+
+        let result = roll("3d6dh1", &DiceLimits::default(), &script(6, &[2, 2, 2])).unwrap();
+        assert_eq!(result.total, 4);
+        assert_eq!(
+            result.terms[0]
+                .dice
+                .as_ref()
+                .unwrap()
+                .iter()
+                .filter(|d| d.kept)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn keeping_more_dice_than_rolled_keeps_them_all() {
+        let result = roll("2d6kh5", &DiceLimits::default(), &script(6, &[3, 4])).unwrap();
+        assert_eq!(result.total, 7);
+        let result = roll("2d6dl5", &DiceLimits::default(), &script(6, &[3, 4])).unwrap();
+        assert_eq!(result.total, 0);
+    }
+
+    #[test]
+    fn exploding_dice_add_a_die_on_the_highest_face() {
+        let result = roll("2d6!", &DiceLimits::default(), &script(6, &[6, 6, 2, 4])).unwrap();
+        assert_eq!(result.total, 18);
+        assert_eq!(result.breakdown(), "2d6! [6!, 6!, 2, 4]");
+    }
+
+    #[test]
+    fn rerolls_until_the_face_changes() {
+        let result = roll("2d6r1", &DiceLimits::default(), &script(6, &[1, 1, 4, 2])).unwrap();
+        assert_eq!(result.total, 6);
+        assert_eq!(result.breakdown(), "2d6r1 [1→1→4, 2]");
+    }
+
+    #[test]
+    fn fudge_and_percentile_dice() {
+        let random = ScriptedRandom::new().then(0.0).then(0.5).then(0.99);
+        let result = roll("3dF", &DiceLimits::default(), &random).unwrap();
+        assert_eq!(result.total, 0);
+        assert_eq!(result.breakdown(), "3dF [-, 0, +]");
+
+        let result = roll(
+            "d%",
+            &DiceLimits::default(),
+            &ScriptedRandom::new().then(0.999),
+        )
+        .unwrap();
+        assert_eq!(result.total, 100);
+    }
+
+    #[test]
+    fn limits_reject_oversized_rolls() {
+        let limits = DiceLimits {
+            max_dice: 10,
+            max_sides: 100,
+            max_terms: 3,
+        };
+        let random = SeededRandom::new(0);
+        assert!(roll("6d6+4d6", &limits, &random).is_ok());
+        assert!(matches!(
+            roll("6d6+5d6", &limits, &random),
+            Err(DiceError::TooManyDice(10))
+        ));
+        assert!(matches!(
+            roll("d101", &limits, &random),
+            Err(DiceError::TooManySides(100))
+        ));
+        assert!(roll("d%", &limits, &random).is_ok());
+        assert!(matches!(
+            roll("1+2+3+4", &limits, &random),
+            Err(DiceError::TooManyTerms(3))
+        ));
+        assert!(matches!(
+            roll("4000000000d6", &DiceLimits::default(), &random),
+            Err(DiceError::TooManyDice(_))
+        ));
+    }
+
+    #[test]
+    fn large_rolls_are_summed_without_listing_dice() {
+        let result = roll("100000d6", &DiceLimits::default(), &SeededRandom::new(0)).unwrap();
+        let term = &result.terms[0];
+        assert_eq!(term.dice, None);
+        assert_eq!(term.rolled, 100_000);
+        assert!((100_000..=600_000).contains(&result.total));
+        assert_eq!(
+            result.breakdown(),
+            format!("100000d6 [100000 dice: {}]", result.total)
+        );
+    }
+
+    #[test]
+    fn counted_keeps_match_listed_keeps() {
+        let values = [3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5];
+        let mut faces = BTreeMap::new();
+        for v in values {
+            *faces.entry(v).or_default() += 1;
+        }
+        for n in 0..=12 {
+            for keep in [
+                Keep::Highest(n),
+                Keep::Lowest(n),
+                Keep::DropHighest(n),
+                Keep::DropLowest(n),
+            ] {
+                let listed: i64 = values
+                    .iter()
+                    .zip(keep.select(&values))
+                    .filter(|(_, kept)| *kept)
+                    .map(|(v, _)| v)
+                    .sum();
+                assert_eq!(
+                    keep.sum_counted(&faces, values.len() as u64),
+                    listed,
+                    "{keep:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use super::{DiceError, DiceTerm, Die, Expr, Keep, Sign, Term};
+
+/// Parse a dice expression like `2d6+3` or `4d6kh3`. Whitespace is ignored
+/// and letters are case-insensitive.
+pub fn parse(input: &str) -> Result<Expr, DiceError> {
+    let source: Vec<char> = input
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if source.is_empty() {
+        return Err(DiceError::MissingInput);
+    }
+
+    let mut parser = Parser { source, pos: 0 };
+    let mut sign = match parser.peek() {
+        Some('-') => {
+            parser.pos += 1;
+            Sign::Minus
+        }
+        Some('+') => {
+            parser.pos += 1;
+            Sign::Plus
+        }
+        _ => Sign::Plus,
+    };
+
+    let mut terms = Vec::new();
+    loop {
+        terms.push((sign, parser.term()?));
+        sign = match parser.next() {
+            Some('+') => Sign::Plus,
+            Some('-') => Sign::Minus,
+            None => break,
+            Some(c) => return Err(parser.unexpected(c)),
+        };
+    }
+    Ok(Expr { terms })
+}
+
+struct Parser {
+    source: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.source.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&self, c: char) -> DiceError {
+        DiceError::Unexpected(c.to_string())
+    }
+
+    fn end_or_unexpected(&self) -> DiceError {
+        match self.peek() {
+            Some(c) => self.unexpected(c),
+            None => DiceError::UnexpectedEnd,
+        }
+    }
+
+    /// Digits at the current position, if any.
+    fn number(&mut self) -> Result<Option<u64>, DiceError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+        let digits: String = self.source[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|_| DiceError::NumberTooLarge(digits))
+    }
+
+    fn required_number(&mut self) -> Result<u32, DiceError> {
+        let n = self.number()?.ok_or_else(|| self.end_or_unexpected())?;
+        u32::try_from(n).map_err(|_| DiceError::NumberTooLarge(n.to_string()))
+    }
+
+    fn term(&mut self) -> Result<Term, DiceError> {
+        let number = self.number()?;
+        if !self.eat('d') {
+            return match number {
+                Some(n) => i64::try_from(n)
+                    .map(Term::Constant)
+                    .map_err(|_| DiceError::NumberTooLarge(n.to_string())),
+                None => Err(self.end_or_unexpected()),
+            };
+        }
+
+        let count = match number {
+            Some(n) => u32::try_from(n).map_err(|_| DiceError::NumberTooLarge(n.to_string()))?,
+            None => 1,
+        };
+        let die = if self.eat('%') {
+            Die::Sided(100)
+        } else if self.eat('f') {
+            Die::Fudge
+        } else {
+            match self.required_number()? {
+                0 => return Err(DiceError::ZeroSize),
+                size => Die::Sided(size),
+            }
+        };
+
+        let mut dice = DiceTerm {
+            count,
+            die,
+            keep: None,
+            explode: false,
+            reroll: None,
+        };
+        self.modifiers(&mut dice)?;
+        Ok(Term::Dice(dice))
+    }
+
+    fn modifiers(&mut self, dice: &mut DiceTerm) -> Result<(), DiceError> {
+        loop {
+            match self.peek() {
+                Some('!') if !dice.explode => {
+                    self.pos += 1;
+                    if dice.die.min() == dice.die.max() {
+                        return Err(DiceError::EndlessExplosion);
+                    }
+                    dice.explode = true;
+                }
+                Some('k' | 'd') if dice.keep.is_none() => {
+                    let keep = self.next() == Some('k');
+                    let highest = if self.eat('l') {
+                        false
+                    } else {
+                        // `k3` keeps the highest, `d3` drops the lowest
+                        self.eat('h') || keep
+                    };
+                    let n = self.required_number()?;
+                    dice.keep = Some(match (keep, highest) {
+                        (true, true) => Keep::Highest(n),
+                        (true, false) => Keep::Lowest(n),
+                        (false, true) => Keep::DropHighest(n),
+                        (false, false) => Keep::DropLowest(n),
+                    });
+                }
+                Some('r') if dice.reroll.is_none() => {
+                    self.pos += 1;
+                    let sign = if self.eat('-') { -1 } else { 1 };
+                    let face = sign * i64::from(self.required_number()?);
+                    if dice.die.min() == dice.die.max() && face == dice.die.min() {
+                        return Err(DiceError::EndlessReroll);
+                    }
+                    dice.reroll = Some(face);
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dice(input: &str) -> DiceTerm {
+        match parse(input).unwrap().terms.remove(0) {
+            (Sign::Plus, Term::Dice(dice)) => dice,
+            other => panic!("expected dice, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_arithmetic_between_terms() {
+        let expr = parse("1d8 + 1d6 - 2").unwrap();
+        assert_eq!(expr.terms.len(), 3);
+        assert_eq!(expr.terms[2], (Sign::Minus, Term::Constant(2)));
+        assert_eq!(expr.to_string(), "1d8+1d6-2");
+        assert_eq!(parse("-d4+10").unwrap().to_string(), "-1d4+10");
+    }
+
+    #[test]
+    fn parses_shorthand_dice() {
+        assert_eq!(dice("d20").count, 1);
+        assert_eq!(dice("d%").die, Die::Sided(100));
+        assert_eq!(dice("4dF").die, Die::Fudge);
+        assert_eq!(dice("4DF").count, 4);
+    }
+
+    #[test]
+    fn parses_modifiers_in_any_order() {
+        let d = dice("4d6kh3");
+        assert_eq!(d.keep, Some(Keep::Highest(3)));
+        assert_eq!(dice("2d20kl1").keep, Some(Keep::Lowest(1)));
+        assert_eq!(dice("4d6k3").keep, Some(Keep::Highest(3)));
+        assert_eq!(dice("4d6d1").keep, Some(Keep::DropLowest(1)));
+        assert_eq!(dice("4d6dh1").keep, Some(Keep::DropHighest(1)));
+
+        let d = dice("3d6r1!kh2");
+        assert!(d.explode);
+        assert_eq!(d.reroll, Some(1));
+        assert_eq!(d.keep, Some(Keep::Highest(2)));
+        assert_eq!(d.to_string(), "3d6!r1kh2");
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert!(matches!(parse(""), Err(DiceError::MissingInput)));
+        assert!(matches!(parse("  "), Err(DiceError::MissingInput)));
+        assert!(matches!(parse("abc"), Err(DiceError::Unexpected(_))));
+        assert!(matches!(parse("3d"), Err(DiceError::UnexpectedEnd)));
+        assert!(matches!(parse("2d6+"), Err(DiceError::UnexpectedEnd)));
+        assert!(matches!(parse("2d6*2"), Err(DiceError::Unexpected(_))));
+        assert!(matches!(parse("2d6!!"), Err(DiceError::Unexpected(_))));
+        assert!(matches!(parse("1d0"), Err(DiceError::ZeroSize)));
+        assert!(matches!(
+            parse("99999999999d6"),
+            Err(DiceError::NumberTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_dice_that_never_settle() {
+        assert!(matches!(parse("1d1!"), Err(DiceError::EndlessExplosion)));
+        assert!(matches!(parse("2d1r1"), Err(DiceError::EndlessReroll)));
+        assert!(parse("4dFr-1").is_ok());
+    }
+}
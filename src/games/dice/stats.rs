@@ -0,0 +1,473 @@
+//! Exact outcome distributions for dice expressions, built by convolving the
+//! distribution of each die. Exploding dice have no real maximum, so chains of
+//! explosions less likely than [`NEGLIGIBLE`] are cut off.
+
+use super::{DiceError, DiceTerm, Expr, Keep, MAX_EXPLOSIONS, Sign, Term};
+
+/// Distributions with more possible totals than this are not worked out.
+const MAX_OUTCOMES: usize = 10_000;
+/// Budget for working out keep/drop terms, in multiply-adds.
+const MAX_KEEP_WORK: usize = 50_000_000;
+/// Keep/drop terms with more dice than this are not worked out.
+const MAX_KEEP_DICE: usize = 1000;
+/// Explosion chains rarer than this are treated as stopping.
+const NEGLIGIBLE: f64 = 1e-15;
+/// The rarest totals at each end of the histogram, up to this much
+/// probability, are left out so long tails do not flatten the bars.
+const HISTOGRAM_TAIL: f64 = 0.001;
+
+/// The probability of every total from `min` upwards. Every total, up to
+/// `min + probs.len() - 1`, fits in an i64.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    pub min: i64,
+    pub probs: Vec<f64>,
+    /// Some die explodes, so larger totals than `highest()` are possible.
+    pub open_ended: bool,
+}
+
+impl Distribution {
+    fn constant(value: i64) -> Self {
+        Self {
+            min: value,
+            probs: vec![1.0],
+            open_ended: false,
+        }
+    }
+
+    /// Every total from `min` to `max` equally likely.
+    fn uniform(min: i64, max: i64) -> Self {
+        let len = (max - min + 1) as usize;
+        Self {
+            min,
+            probs: vec![1.0 / len as f64; len],
+            open_ended: false,
+        }
+    }
+
+    fn outcomes(&self) -> impl Iterator<Item = (i64, f64)> + '_ {
+        self.probs
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (self.min + i as i64, *p))
+    }
+
+    /// The lowest possible total.
+    pub fn lowest(&self) -> i64 {
+        self.outcomes()
+            .find(|(_, p)| *p > 0.0)
+            .map_or(self.min, |(v, _)| v)
+    }
+
+    /// The highest total worked out.
+    pub fn highest(&self) -> i64 {
+        self.outcomes()
+            .filter(|(_, p)| *p > 0.0)
+            .last()
+            .map_or(self.min, |(v, _)| v)
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.outcomes().map(|(v, p)| v as f64 * p).sum()
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        let mean = self.mean();
+        self.outcomes()
+            .map(|(v, p)| (v as f64 - mean).powi(2) * p)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// P(total ≥ `target`).
+    pub fn at_least(&self, target: i64) -> f64 {
+        self.outcomes()
+            .filter(|(v, _)| *v >= target)
+            .map(|(_, p)| p)
+            .sum::<f64>()
+            .min(1.0)
+    }
+
+    fn negate(&self) -> Result<Self, DiceError> {
+        let max = self.min + (self.probs.len() as i64 - 1);
+        // -i64::MIN does not fit, so neither end may be i64::MIN
+        if self.min.checked_neg().is_none() || max.checked_neg().is_none() {
+            return Err(total_too_large());
+        }
+        Ok(Self {
+            min: -max,
+            probs: self.probs.iter().rev().copied().collect(),
+            open_ended: self.open_ended,
+        })
+    }
+
+    fn convolve(&self, other: &Self) -> Result<Self, DiceError> {
+        let len = self.probs.len() + other.probs.len() - 1;
+        if len > MAX_OUTCOMES {
+            return Err(DiceError::TooComplex);
+        }
+        let mut probs = vec![0.0; len];
+        for (i, a) in self.probs.iter().enumerate() {
+            if *a == 0.0 {
+                continue;
+            }
+            for (j, b) in other.probs.iter().enumerate() {
+                probs[i + j] += a * b;
+            }
+        }
+        let min = self
+            .min
+            .checked_add(other.min)
+            .filter(|min| min.checked_add(len as i64 - 1).is_some())
+            .ok_or_else(total_too_large)?;
+        Ok(Self {
+            min,
+            probs,
+            open_ended: self.open_ended || other.open_ended,
+        })
+    }
+
+    /// A text histogram with one row per total, or per range of totals when
+    /// there are more than `rows` of them.
+    pub fn histogram(&self, rows: usize, width: usize) -> String {
+        let mut cumulative = 0.0;
+        let first = self
+            .probs
+            .iter()
+            .position(|p| {
+                cumulative += p;
+                cumulative > HISTOGRAM_TAIL
+            })
+            .unwrap_or(0);
+        cumulative = 0.0;
+        let last = self.probs.len()
+            - 1
+            - self
+                .probs
+                .iter()
+                .rev()
+                .position(|p| {
+                    cumulative += p;
+                    cumulative > HISTOGRAM_TAIL
+                })
+                .unwrap_or(0);
+
+        let shown = &self.probs[first..=last];
+        let bucket = shown.len().div_ceil(rows.max(1));
+        let buckets: Vec<(i64, i64, f64)> = shown
+            .chunks(bucket)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let lo = self.min + (first + i * bucket) as i64;
+                (lo, lo + chunk.len() as i64 - 1, chunk.iter().sum())
+            })
+            .collect();
+
+        let tallest = buckets.iter().map(|b| b.2).fold(0.0, f64::max);
+        let labels: Vec<String> = buckets
+            .iter()
+            .map(|(lo, hi, _)| {
+                if lo == hi {
+                    lo.to_string()
+                } else {
+                    format!("{lo}–{hi}")
+                }
+            })
+            .collect();
+        let label_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+        buckets
+            .iter()
+            .zip(&labels)
+            .map(|((_, _, p), label)| {
+                let bar = if tallest > 0.0 {
+                    (p / tallest * width as f64).round() as usize
+                } else {
+                    0
+                };
+                format!(
+                    "{label:>label_width$} {:>6.2}% {}",
+                    p * 100.0,
+                    "█".repeat(bar)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn total_too_large() -> DiceError {
+    DiceError::NumberTooLarge("The total".to_string())
+}
+
+/// The exact distribution of an expression's total. Call
+/// [`super::DiceLimits::check`] first for untrusted input.
+pub fn distribution(expr: &Expr) -> Result<Distribution, DiceError> {
+    let mut total = Distribution::constant(0);
+    for (sign, term) in &expr.terms {
+        let dist = match term {
+            Term::Dice(dice) => dice_distribution(dice)?,
+            Term::Constant(n) => Distribution::constant(*n),
+        };
+        let dist = match sign {
+            Sign::Plus => dist,
+            Sign::Minus => dist.negate()?,
+        };
+        total = total.convolve(&dist)?;
+    }
+    Ok(total)
+}
+
+fn dice_distribution(term: &DiceTerm) -> Result<Distribution, DiceError> {
+    let die = die_distribution(term)?;
+    match term.keep {
+        None => {
+            let mut total = Distribution::constant(0);
+            for _ in 0..term.count {
+                total = total.convolve(&die)?;
+            }
+            Ok(total)
+        }
+        // Explosions add dice that are kept or dropped on their own
+        Some(_) if term.explode => Err(DiceError::TooComplex),
+        Some(keep) => {
+            let count = term.count;
+            match keep {
+                Keep::Highest(n) => keep_highest(&die, count, n),
+                Keep::DropLowest(n) => keep_highest(&die, count, count.saturating_sub(n)),
+                Keep::Lowest(n) => keep_highest(&die.negate()?, count, n)?.negate(),
+                Keep::DropHighest(n) => {
+                    keep_highest(&die.negate()?, count, count.saturating_sub(n))?.negate()
+                }
+            }
+        }
+    }
+}
+
+/// One die, including its rerolls and explosions.
+fn die_distribution(term: &DiceTerm) -> Result<Distribution, DiceError> {
+    if term.die.max() - term.die.min() >= MAX_OUTCOMES as i64 {
+        return Err(DiceError::TooComplex);
+    }
+    let mut face = Distribution::uniform(term.die.min(), term.die.max());
+    if let Some(reroll) = term.reroll
+        && (term.die.min()..=term.die.max()).contains(&reroll)
+    {
+        // Rerolling until the face changes spreads its chance over the rest
+        let index = (reroll - face.min) as usize;
+        face.probs[index] = 0.0;
+        let rest = face.probs.len() as f64 - 1.0;
+        for p in face.probs.iter_mut().filter(|p| **p > 0.0) {
+            *p = 1.0 / rest;
+        }
+    }
+    if !term.explode {
+        return Ok(face);
+    }
+
+    let max = term.die.max();
+    let p_max = *face.probs.last().expect("a die has faces");
+    let mut depth = 0;
+    while depth < MAX_EXPLOSIONS && p_max.powi(depth as i32 + 1) >= NEGLIGIBLE {
+        depth += 1;
+    }
+
+    // After k explosions the die shows k * max plus the face it stopped on
+    let len = depth as usize * max as usize + face.probs.len();
+    if len > MAX_OUTCOMES {
+        return Err(DiceError::TooComplex);
+    }
+    let mut probs = vec![0.0; len];
+    for k in 0..=depth {
+        let reached = p_max.powi(k as i32);
+        for (i, p) in face.probs.iter().enumerate() {
+            let stops = i + 1 < face.probs.len() || k == depth;
+            if stops {
+                probs[k as usize * max as usize + i] += reached * p;
+            }
+        }
+    }
+    Ok(Distribution {
+        min: face.min,
+        probs,
+        open_ended: p_max > 0.0,
+    })
+}
+
+/// The total of the highest `keep` of `count` dice. Faces are handed out
+/// from the highest down, so the first `keep` dice assigned are the ones
+/// kept; `states[n]` is the chance of each kept total once `n` dice have a
+/// face.
+fn keep_highest(die: &Distribution, count: u32, keep: u32) -> Result<Distribution, DiceError> {
+    let count = count as usize;
+    let keep = (keep as usize).min(count);
+    let faces = die.probs.len();
+    let len = keep * (faces - 1) + 1;
+    if count > MAX_KEEP_DICE
+        || len > MAX_OUTCOMES
+        || faces * (count + 1) * (count + 1) * len > MAX_KEEP_WORK
+    {
+        return Err(DiceError::TooComplex);
+    }
+
+    let mut binomial = vec![vec![1.0f64; 1]; count + 1];
+    for m in 1..=count {
+        let mut row = vec![1.0; m + 1];
+        for c in 1..m {
+            row[c] = binomial[m - 1][c - 1] + binomial[m - 1][c];
+        }
+        binomial[m] = row;
+    }
+
+    let mut states = vec![vec![0.0; len]; count + 1];
+    states[0][0] = 1.0;
+    for (face, p) in die.probs.iter().enumerate().rev() {
+        let mut next = vec![vec![0.0; len]; count + 1];
+        for (assigned, totals) in states.iter().enumerate() {
+            if totals.iter().all(|t| *t == 0.0) {
+                continue;
+            }
+            let left = count - assigned;
+            for showing in 0..=left {
+                let weight = binomial[left][showing] * p.powi(showing as i32);
+                if weight == 0.0 {
+                    continue;
+                }
+                let kept = showing.min(keep.saturating_sub(assigned));
+                let shift = kept * face;
+                for (total, chance) in totals.iter().enumerate() {
+                    if *chance > 0.0 {
+                        next[assigned + showing][total + shift] += chance * weight;
+                    }
+                }
+            }
+        }
+        states = next;
+    }
+
+    Ok(Distribution {
+        min: keep as i64 * die.min,
+        probs: states.swap_remove(count),
+        open_ended: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::dice::parse;
+
+    fn dist(input: &str) -> Distribution {
+        distribution(&parse(input).unwrap()).unwrap()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn assert_same(a: &Distribution, b: &Distribution) {
+        assert_eq!(a.min, b.min);
+        assert_eq!(a.probs.len(), b.probs.len());
+        for (x, y) in a.probs.iter().zip(&b.probs) {
+            assert_close(*x, *y);
+        }
+    }
+
+    #[test]
+    fn three_d6_matches_the_known_odds() {
+        let d = dist("3d6");
+        assert_eq!((d.lowest(), d.highest()), (3, 18));
+        assert_close(d.mean(), 10.5);
+        assert_close(d.std_dev(), 8.75f64.sqrt());
+        // 20 of the 216 outcomes are 15 or more
+        assert_close(d.at_least(15), 20.0 / 216.0);
+        assert_close(d.at_least(3), 1.0);
+        assert_close(d.probs.iter().sum(), 1.0);
+    }
+
+    #[test]
+    fn arithmetic_shifts_and_negates() {
+        let d = dist("1d4-1d4+10");
+        assert_eq!((d.lowest(), d.highest()), (7, 13));
+        assert_close(d.mean(), 10.0);
+
+        let d = dist("d%");
+        assert_eq!((d.lowest(), d.highest()), (1, 100));
+        let d = dist("4dF");
+        assert_eq!((d.lowest(), d.highest()), (-4, 4));
+        assert_close(d.mean(), 0.0);
+    }
+
+    #[test]
+    fn keep_and_drop_match_known_odds() {
+        // Advantage: 1 - (19/20)^2 chance of at least one 20
+        let d = dist("2d20kh1");
+        assert_close(d.at_least(20), 1.0 - (19.0f64 / 20.0).powi(2));
+        assert_close(dist("2d20kl1").at_least(20), 1.0 / 400.0);
+
+        let d = dist("4d6kh3");
+        assert_eq!((d.lowest(), d.highest()), (3, 18));
+        assert_close(d.mean(), 15869.0 / 1296.0);
+        assert_same(&dist("4d6dl1"), &d);
+        assert_same(&dist("4d6dh1"), &dist("4d6kl3"));
+        assert_same(&dist("3d6kh5"), &dist("3d6"));
+    }
+
+    #[test]
+    fn rerolls_and_explosions() {
+        let d = dist("1d6r1");
+        assert_eq!((d.lowest(), d.highest()), (2, 6));
+        assert_close(d.mean(), 4.0);
+
+        // An exploding d6 averages 3.5 * 6/5
+        let d = dist("1d6!");
+        assert!(d.open_ended);
+        assert_close(d.mean(), 4.2);
+        assert_close(d.at_least(7), 1.0 / 6.0);
+        assert_close(d.probs.iter().sum(), 1.0);
+    }
+
+    #[test]
+    fn refuses_what_it_cannot_work_out() {
+        let expr = parse("4d6!kh3").unwrap();
+        assert!(matches!(distribution(&expr), Err(DiceError::TooComplex)));
+        let expr = parse("1000d100").unwrap();
+        assert!(matches!(distribution(&expr), Err(DiceError::TooComplex)));
+        let expr = parse("d1000000!").unwrap();
+        assert!(matches!(distribution(&expr), Err(DiceError::TooComplex)));
+    }
+
+    #[test]
+    fn totals_past_an_i64_are_too_large() {
+        for input in [
+            "9223372036854775807+1",
+            "9223372036854775807+1d6",
+            "1d6-9223372036854775807-9",
+            "0-9223372036854775807-1d6",
+        ] {
+            let expr = parse(input).unwrap();
+            assert!(
+                matches!(distribution(&expr), Err(DiceError::NumberTooLarge(_))),
+                "{input}"
+            );
+        }
+        let d = dist("9223372036854775806+1");
+        assert_eq!(d.highest(), i64::MAX);
+    }
+
+    #[test]
+    fn histogram_has_a_row_per_total_or_range() {
+        let histogram = dist("2d6").histogram(20, 10);
+        let rows: Vec<&str> = histogram.lines().collect();
+        assert_eq!(rows.len(), 11);
+        assert_eq!(rows[0], " 2   2.78% ██");
+        assert_eq!(rows[5], " 7  16.67% ██████████");
+
+        let histogram = dist("10d10").histogram(20, 10);
+        assert!(histogram.lines().count() <= 20);
+        assert!(histogram.contains('–'));
+    }
+}
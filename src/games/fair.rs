@@ -0,0 +1,337 @@
+//! Commit–reveal randomness for lottery games.
+//!
+//! Each game draws a secret server seed when it is created and shows only the
+//! seed's hash while it runs. Every random outcome is a roll derived from the
+//! seed and public inputs, and the seed is revealed when the game ends so
+//! anyone can recompute the rolls and check the seed against the hash:
+//!
+//! - hash = hex(SHA-256(seed))
+//! - roll = HMAC-SHA256(key = seed, message = "<game id>:<label>"), top 53 bits
+//!   of the first 8 bytes (big-endian) divided by 2^53
+//! - the winner is player `floor(roll * players)` for label `winner:<players>`
+//! - a sardines join with `n` players already in rolls label `join:<n>` and
+//!   ends the game when the roll is below the join failure chance
+
+use chrono::{DateTime, Utc};
+use ring::digest::{SHA256, digest};
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use crate::context::Context;
+use crate::games::lottery::DbPlayer;
+use crate::roulette::store::RouletteStore;
+use crate::sardines::game::{join_failure_chance, join_roll_ends_game};
+use crate::sardines::store::SardinesStore;
+use crate::storage::{Collection, Storage};
+
+pub(crate) const REVEAL_COLLECTION: &str = "reveals";
+const SEED_BYTES: usize = 32;
+
+/// Draw a fresh secret seed, hex encoded.
+pub fn new_server_seed() -> String {
+    let mut bytes = [0u8; SEED_BYTES];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system randomness is unavailable");
+    hex::encode(bytes)
+}
+
+/// The commitment shown to players before the seed is revealed.
+pub fn seed_hash(seed: &str) -> String {
+    hex::encode(digest(&SHA256, seed.as_bytes()))
+}
+
+/// A uniform roll in `[0, 1)` derived from the seed and public inputs.
+pub fn roll(seed: &str, game_id: &str, label: &str) -> f64 {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, seed.as_bytes());
+    let tag = hmac::sign(&key, format!("{game_id}:{label}").as_bytes());
+    let mut head = [0u8; 8];
+    head.copy_from_slice(&tag.as_ref()[..8]);
+    (u64::from_be_bytes(head) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+pub fn winner_label(players: usize) -> String {
+    format!("winner:{players}")
+}
+
+pub fn join_label(players_before: usize) -> String {
+    format!("join:{players_before}")
+}
+
+/// Map a roll onto one of `count` choices.
+pub fn pick(roll: f64, count: usize) -> usize {
+    ((roll * count as f64) as usize).min(count - 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GameKind {
+    Roulette,
+    Sardines,
+}
+
+/// Everything needed to recompute a finished game's rolls. Written when the
+/// game finishes, since the game document itself is deleted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reveal {
+    pub game_id: String,
+    pub kind: GameKind,
+    pub guild_id: String,
+    pub server_seed: String,
+    pub players: Vec<DbPlayer>,
+    /// The player who was paid, or `None` if every bet was refunded.
+    pub winner_id: Option<String>,
+    #[serde(with = "firestore::serialize_as_timestamp")]
+    pub revealed_at: DateTime<Utc>,
+}
+
+/// One sardines join, recomputed from the revealed seed.
+pub struct JoinRoll {
+    pub player: DbPlayer,
+    pub players_before: usize,
+    pub roll: f64,
+}
+
+impl JoinRoll {
+    pub fn ends_game(&self) -> bool {
+        join_roll_ends_game(self.roll, self.players_before)
+    }
+}
+
+impl Reveal {
+    /// The winner the seed picks, or `None` if too few players joined.
+    pub fn expected_winner(&self) -> Option<&DbPlayer> {
+        if self.players.len() < 2 {
+            return None;
+        }
+        let count = self.players.len();
+        let roll = roll(&self.server_seed, &self.game_id, &winner_label(count));
+        Some(&self.players[pick(roll, count)])
+    }
+
+    /// Every join after the creator's, in order.
+    pub fn join_rolls(&self) -> Vec<JoinRoll> {
+        if self.kind != GameKind::Sardines {
+            return Vec::new();
+        }
+        self.players
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(players_before, player)| JoinRoll {
+                player: player.clone(),
+                players_before,
+                roll: roll(
+                    &self.server_seed,
+                    &self.game_id,
+                    &join_label(players_before),
+                ),
+            })
+            .collect()
+    }
+
+    /// Whether the recorded outcome is the one the seed produces.
+    pub fn is_consistent(&self) -> bool {
+        let winner_matches = self.expected_winner().map(|p| &p.id) == self.winner_id.as_ref();
+        // Only the last join can have ended the game
+        let rolls = self.join_rolls();
+        let joins_match = rolls.iter().rev().skip(1).all(|join| !join.ends_game());
+        winner_matches && joins_match
+    }
+}
+
+pub async fn get_reveal(storage: &Storage, game_id: &str) -> anyhow::Result<Option<Reveal>> {
+    Collection::new(storage.clone(), REVEAL_COLLECTION)
+        .get(game_id)
+        .await
+}
+
+/// Recompute a game's random outcomes from its revealed seed
+#[poise::command(slash_command, guild_only)]
+pub async fn verify(
+    ctx: Context<'_>,
+    #[description = "Id of the roulette or sardines game"] id: String,
+) -> Result<(), anyhow::Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Must be in a guild"))?
+        .to_string();
+    let storage = &ctx.data().storage;
+
+    let content = match get_reveal(storage, &id).await? {
+        Some(reveal) if reveal.guild_id == guild_id => format_reveal(&reveal),
+        _ => match running_game_seed(storage, &id, &guild_id).await? {
+            Some(seed) => format!(
+                "Game `{id}` is still running. Its seed hash is `{}`; the seed is revealed when the game ends.",
+                seed_hash(&seed)
+            ),
+            None => format!("No game with id `{id}`"),
+        },
+    };
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn running_game_seed(
+    storage: &Storage,
+    id: &str,
+    guild_id: &str,
+) -> anyhow::Result<Option<String>> {
+    let lottery = match RouletteStore::new(storage.clone()).get(id).await? {
+        Some(lottery) => Some(lottery),
+        None => SardinesStore::new(storage.clone()).get(id).await?,
+    };
+    Ok(lottery
+        .filter(|l| l.creator.guild_id == guild_id && !l.server_seed.is_empty())
+        .map(|l| l.server_seed))
+}
+
+fn format_reveal(reveal: &Reveal) -> String {
+    let kind = match reveal.kind {
+        GameKind::Roulette => "Roulette",
+        GameKind::Sardines => "Sardines",
+    };
+    if reveal.server_seed.is_empty() {
+        return format!(
+            "{kind} game `{}` started before seeds were committed, so it cannot be verified.",
+            reveal.game_id
+        );
+    }
+
+    let mut lines = vec![
+        format!("## {kind} game `{}`", reveal.game_id),
+        format!("Seed: `{}`", reveal.server_seed),
+        format!(
+            "Seed hash: `{}` (compare with the hash shown when the game started)",
+            seed_hash(&reveal.server_seed)
+        ),
+    ];
+
+    let joins = reveal.join_rolls();
+    if !joins.is_empty() {
+        lines.push("**Joins**".to_string());
+        for join in &joins {
+            let chance = join_failure_chance(join.players_before) * 100.0;
+            let verdict = if join.ends_game() {
+                "ended the game"
+            } else {
+                "continued"
+            };
+            lines.push(format!(
+                "{}: rolled {:.4} against a {chance:.2}% end chance, {verdict}",
+                join.player.username, join.roll
+            ));
+        }
+    }
+
+    match reveal.expected_winner() {
+        Some(winner) => {
+            let count = reveal.players.len();
+            let roll = roll(&reveal.server_seed, &reveal.game_id, &winner_label(count));
+            lines.push(format!(
+                "**Winner**: rolled {roll:.4} across {count} players, picking **{}**",
+                winner.username
+            ));
+        }
+        None => lines.push("Not enough players joined, so every bet was refunded.".to_string()),
+    }
+
+    lines.push(if reveal.is_consistent() {
+        "✅ The recorded outcome matches the seed.".to_string()
+    } else {
+        "❌ The recorded outcome does not match the seed.".to_string()
+    });
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::games::lottery::Lottery;
+    use crate::util::random::ThreadRandom;
+
+    fn player(id: &str) -> DbPlayer {
+        DbPlayer {
+            id: id.to_string(),
+            guild_id: "guild".to_string(),
+            username: id.to_string(),
+            joined_at: None,
+        }
+    }
+
+    fn reveal(players: &[&str], kind: GameKind) -> Reveal {
+        Reveal {
+            game_id: "game".to_string(),
+            kind,
+            guild_id: "guild".to_string(),
+            server_seed: "seed".to_string(),
+            players: players.iter().map(|id| player(id)).collect(),
+            winner_id: None,
+            revealed_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn rolls_are_stable_and_depend_on_every_input() {
+        // Published derivation: these must never change
+        assert_eq!(
+            seed_hash("seed"),
+            "19b25856e1c150ca834cffc8b59b23adbd0ec0389e58eb22b3b64768098d002b"
+        );
+        let r = roll("seed", "game", "winner:2");
+        assert!((0.0..1.0).contains(&r));
+        assert_eq!(r, roll("seed", "game", "winner:2"));
+        assert_ne!(r, roll("other", "game", "winner:2"));
+        assert_ne!(r, roll("seed", "other", "winner:2"));
+        assert_ne!(r, roll("seed", "game", "winner:3"));
+    }
+
+    #[test]
+    fn pick_covers_every_choice_and_stays_in_range() {
+        assert_eq!(pick(0.0, 3), 0);
+        assert_eq!(pick(0.5, 3), 1);
+        assert_eq!(pick(0.9999, 3), 2);
+    }
+
+    #[test]
+    fn finished_lotteries_verify_against_their_reveal() {
+        let mut lottery = Lottery::new(player("a"), 10, &ThreadRandom).unwrap();
+        for id in ["a", "b", "c", "d"] {
+            lottery.add_player(player(id));
+        }
+        let winner = lottery.finish(&ThreadRandom).winner;
+
+        let mut game = reveal(&["a", "b", "c", "d"], GameKind::Roulette);
+        game.game_id = lottery.id.clone();
+        game.server_seed = lottery.server_seed.clone();
+        assert_eq!(game.expected_winner(), Some(&winner));
+        assert_eq!(lottery.seed_hash(), seed_hash(&game.server_seed));
+    }
+
+    #[test]
+    fn reveal_checks_the_recorded_outcome() {
+        let mut game = reveal(&["a", "b", "c"], GameKind::Roulette);
+        let winner = game.expected_winner().unwrap().id.clone();
+        game.winner_id = Some(winner.clone());
+        assert!(game.is_consistent());
+
+        let other = ["a", "b", "c"]
+            .into_iter()
+            .find(|id| *id != winner)
+            .unwrap();
+        game.winner_id = Some(other.to_string());
+        assert!(!game.is_consistent());
+
+        let refunded = reveal(&["a"], GameKind::Roulette);
+        assert!(refunded.expected_winner().is_none());
+        assert!(refunded.is_consistent());
+    }
+}
@@ -3,35 +3,37 @@ use chrono::Utc;
 use crate::context::Context;
 use crate::discord::helpers::rep_label;
 use crate::discord::types::GuildMember;
+use crate::error::EconomyError;
+use crate::guilds::Settings;
+use crate::users::admin::ensure_not_frozen;
+use crate::users::{RepChange, RepReason};
 use crate::util::dates::{format_distance_to_now, get_day_string, is_today};
-use crate::util::random::seeded_random_inclusive;
+use crate::util::random::{SeedVersion, seeded_random_inclusive};
 
-const MAGIC_NUMBER_REWARD: i64 = 1000;
+pub const MAGIC_NUMBER_REWARD: i64 = 1000;
 const MAGIC_NUMBER_RANGE: i64 = 3;
-const RANGE_REWARD: i64 = 30;
-const LAST_DIGIT_REWARD: i64 = 10;
-const PAIRWISE_REWARD: i64 = 250;
+pub const RANGE_REWARD: i64 = 30;
+pub const LAST_DIGIT_REWARD: i64 = 10;
+pub const PAIRWISE_REWARD: i64 = 250;
 
 struct Rule {
     predicate: fn(i64, i64) -> bool,
-    reward: i64,
-    message: fn(i64, i64) -> String,
+    reward: fn(&Settings) -> i64,
+    message: fn(i64, i64, &str) -> String,
 }
 
 const RULES: &[Rule] = &[
     Rule {
         predicate: |answer, guess| answer == guess,
-        reward: MAGIC_NUMBER_REWARD,
-        message: |answer, _guess| {
-            let reward = rep_label(MAGIC_NUMBER_REWARD, false);
+        reward: |settings| settings.guess_magic_number_reward,
+        message: |answer, _guess, reward| {
             format!("# Winner 🚀\n\n**{answer}** is the right number! You won {reward}",)
         },
     },
     Rule {
         predicate: |answer, guess| is_magic_pair(answer, guess),
-        reward: PAIRWISE_REWARD,
-        message: |answer, guess| {
-            let reward = rep_label(PAIRWISE_REWARD, false);
+        reward: |settings| settings.guess_pairwise_reward,
+        message: |answer, guess, reward| {
             format!(
                 "## Magic Number Match 🪄\n\nYour guess of **{guess}** magically pairs with the correct answer **{answer}**. You won {reward}"
             )
@@ -39,9 +41,8 @@ const RULES: &[Rule] = &[
     },
     Rule {
         predicate: |answer, guess| is_within(guess, answer, MAGIC_NUMBER_RANGE),
-        reward: RANGE_REWARD,
-        message: |answer, guess| {
-            let reward = rep_label(RANGE_REWARD, false);
+        reward: |settings| settings.guess_range_reward,
+        message: |answer, guess, reward| {
             format!(
                 "### Near Correct \n\nYour guess of **{guess}** is within {MAGIC_NUMBER_RANGE} of the correct answer **{answer}**. You won {reward}"
             )
@@ -49,9 +50,8 @@ const RULES: &[Rule] = &[
     },
     Rule {
         predicate: |answer, guess| last_digit(answer) == last_digit(guess),
-        reward: LAST_DIGIT_REWARD,
-        message: |answer, guess| {
-            let reward = rep_label(LAST_DIGIT_REWARD, false);
+        reward: |settings| settings.guess_last_digit_reward,
+        message: |answer, guess, reward| {
             format!(
                 "### Last Digit\n\nYour guess of **{guess}** matches the last digit of the correct answer **{answer}**. You won {reward}"
             )
@@ -69,10 +69,11 @@ pub async fn guess(
     number: i64,
 ) -> Result<(), anyhow::Error> {
     let data = ctx.data();
-    let timezone = data.config.discord.timezone;
     let guild_id = ctx
         .guild_id()
         .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+    let settings = data.settings(guild_id).await?;
+    let timezone = settings.timezone;
 
     let member_data = ctx
         .author_member()
@@ -86,6 +87,7 @@ pub async fn guess(
     );
     let member_name = &member.username;
 
+    ensure_not_frozen(data.user_store.as_ref(), &member).await?;
     let last_guess = data.user_store.get_user_last_guess(&member).await?;
 
     if !(1..=100).contains(&number) {
@@ -93,7 +95,7 @@ pub async fn guess(
             Some(dt) => format_distance_to_now(dt),
             None => "never".to_string(),
         };
-        let reward_label = rep_label(MAGIC_NUMBER_REWARD, false);
+        let reward_label = rep_label(settings.guess_magic_number_reward, false);
         ctx.send(
             poise::CreateReply::default().content(format!(
                 "Guess a number between 1-100 to win {reward_label}. Only guess allowed per day.\n{member_name} made their last Guess {last_guess_str}"
@@ -106,13 +108,10 @@ pub async fn guess(
     if let Some(last) = last_guess
         && is_today(timezone, last)
     {
-        ctx.send(
-            poise::CreateReply::default()
-                .content("You already guessed today")
-                .ephemeral(true),
-        )
-        .await?;
-        return Ok(());
+        return Err(EconomyError::DailyLimitReached {
+            activity: "guessed",
+        }
+        .into());
     }
 
     let now = Utc::now();
@@ -120,16 +119,21 @@ pub async fn guess(
 
     let day = get_day_string(timezone, now);
     let seed = format!("{member_name}:{day}");
-    let magic_number = seeded_random_inclusive(1, 100, &seed, &data.config.random_seed);
+    let version = SeedVersion::for_day(
+        data.config.seed_v2_from,
+        now.with_timezone(&timezone).date_naive(),
+    );
+    let magic_number = seeded_random_inclusive(1, 100, &seed, &data.config.random_seed, version);
 
     let matched_rule = RULES.iter().find(|r| (r.predicate)(magic_number, number));
 
     match matched_rule {
         Some(rule) => {
+            let reward = (rule.reward)(&settings);
             data.user_store
-                .increment_user_rep(&member, rule.reward)
+                .increment_user_rep(&member, reward, &RepChange::new(RepReason::GuessReward))
                 .await?;
-            let msg = (rule.message)(magic_number, number);
+            let msg = (rule.message)(magic_number, number, &rep_label(reward, false));
             ctx.send(poise::CreateReply::default().content(msg)).await?;
         }
         None => {
@@ -1,8 +1,15 @@
+use std::sync::Arc;
+
 use nanoid::nanoid;
 use serde::{Deserialize, Serialize};
 
 use crate::discord::types::GuildMember;
-use crate::util::random::random_inclusive;
+use crate::error::{EconomyError, EconomyResult};
+use crate::games::fair;
+use crate::storage::Storage;
+use crate::users::RepChange;
+use crate::users::store::increment_in_transaction;
+use crate::util::random::{RandomSource, SeedVersion};
 
 /// Serializable player type for lottery persistence in Firestore.
 /// Uses String for joined_at (not DateTime) to match existing Firestore data format.
@@ -50,8 +57,20 @@ pub struct Lottery<Player: Clone + PartialEq> {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub start_time: Option<String>,
+    /// When the join window closes, for games with a fixed duration.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<String>,
     #[serde(default)]
     pub closed: bool,
+    /// Secret seed behind every roll in this game, revealed when it ends.
+    /// Empty for games started before seeds were committed.
+    #[serde(default)]
+    pub server_seed: String,
+    /// Derivation for values seeded by the game id, fixed when the game is
+    /// created so an upgrade mid-game cannot change them.
+    #[serde(default = "SeedVersion::legacy")]
+    pub seed_version: SeedVersion,
 }
 
 pub struct LotteryResult<Player> {
@@ -59,9 +78,9 @@ pub struct LotteryResult<Player> {
 }
 
 impl<Player: Clone + PartialEq> Lottery<Player> {
-    pub fn new(creator: Player, bet: i64) -> anyhow::Result<Self> {
+    pub fn new(creator: Player, bet: i64, random: &dyn RandomSource) -> EconomyResult<Self> {
         if bet <= 0 {
-            anyhow::bail!("bet cannot be less than or equal to 0");
+            return Err(EconomyError::InvalidBet);
         }
 
         Ok(Self {
@@ -70,7 +89,10 @@ impl<Player: Clone + PartialEq> Lottery<Player> {
             creator,
             players: Vec::new(),
             start_time: None,
+            end_time: None,
             closed: false,
+            server_seed: random.server_seed(),
+            seed_version: SeedVersion::CURRENT,
         })
     }
 
@@ -106,10 +128,71 @@ impl<Player: Clone + PartialEq> Lottery<Player> {
         self.closed
     }
 
-    /// Finish the lottery: pick a random winner.
-    pub fn finish(&self) -> LotteryResult<Player> {
-        let idx = random_inclusive(0, self.players.len() as i64 - 1) as usize;
+    /// The commitment to `server_seed` shown to players while the game runs.
+    pub fn seed_hash(&self) -> String {
+        fair::seed_hash(&self.server_seed)
+    }
+
+    /// A roll in `[0, 1)` for the outcome named by `label`, derived from the
+    /// committed seed.
+    pub fn roll(&self, random: &dyn RandomSource, label: &str) -> f64 {
+        if self.server_seed.is_empty() {
+            return random.next_f64();
+        }
+        random.game_roll(&self.server_seed, &self.id, label)
+    }
+
+    /// Finish the lottery: pick the winner the committed seed selects.
+    pub fn finish(&self, random: &dyn RandomSource) -> LotteryResult<Player> {
+        let count = self.players.len();
+        let idx = fair::pick(self.roll(random, &fair::winner_label(count)), count);
         let winner = self.players[idx].clone();
         LotteryResult { winner }
     }
 }
+
+/// What a finished game pays out, the winnings or every bet refunded, and
+/// the seed reveal that explains it.
+pub struct Settlement {
+    pub payouts: Vec<(GuildMember, i64)>,
+    pub change: RepChange,
+    pub reveal: fair::Reveal,
+}
+
+/// Pay out a finished game, reveal its seed and delete it in one
+/// transaction, so a finish that is retried after a failure can never pay
+/// twice and a game is never left joinable with its seed public. Returns
+/// false, and writes nothing, if the game was already settled; fails, also
+/// writing nothing, if a payout would overflow a balance.
+pub async fn settle(
+    storage: &Storage,
+    collection: &'static str,
+    id: &str,
+    settlement: Settlement,
+) -> anyhow::Result<bool> {
+    let id = id.to_string();
+    let settlement = Arc::new(settlement);
+    let settled = storage
+        .run_transaction(move |tx| {
+            let id = id.clone();
+            let settlement = settlement.clone();
+            Box::pin(async move {
+                let game: Option<Lottery<DbPlayer>> = tx.get(collection, &id).await?;
+                if game.is_none() {
+                    return Ok(Ok(false));
+                }
+                let paid =
+                    increment_in_transaction(tx, &settlement.payouts, &settlement.change).await?;
+                if let Err(e) = paid {
+                    // Keep the game so it can be settled once the overflow is fixed
+                    return Ok(Err(e));
+                }
+                let reveal = &settlement.reveal;
+                tx.update(fair::REVEAL_COLLECTION, &reveal.game_id, reveal)?;
+                tx.delete(collection, &id)?;
+                Ok(Ok(true))
+            })
+        })
+        .await?;
+    Ok(settled?)
+}
@@ -1,5 +1,9 @@
 pub mod cards;
+#[cfg(test)]
+mod conservation;
 pub mod dice;
+pub mod fair;
 pub mod guess;
 pub mod lottery;
 pub mod roll;
+pub mod sweep;
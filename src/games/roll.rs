@@ -1,12 +1,17 @@
 use crate::context::Context;
-use crate::games::dice;
+use crate::games::dice::{self, stats};
+
+/// Discord rejects messages longer than this.
+const MAX_MESSAGE_LENGTH: usize = 2000;
+const HISTOGRAM_ROWS: usize = 20;
+const HISTOGRAM_WIDTH: usize = 30;
 
 /// Roll dice
 #[poise::command(slash_command, guild_only)]
 pub async fn roll(
     ctx: Context<'_>,
-    #[description = "dice to roll e.g. 1d6, d20, 3d6"] dice: Option<String>,
-    #[description = "See all dice rolls individually"] verbose: Option<bool>,
+    #[description = "dice to roll e.g. d20, 2d6+3, 4d6kh3, 3d6!, 4dF"] dice: Option<String>,
+    #[description = "See every die, including dropped and rerolled ones"] verbose: Option<bool>,
     #[description = "See response as a private message (default: false)"] private: Option<bool>,
 ) -> Result<(), anyhow::Error> {
     let dice_input = dice.as_deref().unwrap_or("1d6");
@@ -14,16 +19,19 @@ pub async fn roll(
     let ephemeral = private.unwrap_or(false);
     let username = &ctx.author().name;
 
-    match dice::roll(dice_input) {
-        Ok(results) => {
-            let total: u32 = results.iter().sum();
-            let msg = if verbose {
-                let rolls: Vec<String> = results.iter().map(|r| r.to_string()).collect();
-                let rolls = rolls.join(", ");
-                format!("{username} rolled {dice_input} and got {total} with {rolls}")
-            } else {
-                format!("{username} rolled {dice_input} and got {total}")
-            };
+    let data = ctx.data();
+    match dice::roll(dice_input, &data.config.dice_limits, data.random.as_ref()) {
+        Ok(result) => {
+            let total = result.total;
+            let mut msg = format!("{username} rolled {dice_input} and got {total}");
+            if verbose {
+                let detailed = format!("{msg} with {}", result.breakdown());
+                msg = if detailed.chars().count() <= MAX_MESSAGE_LENGTH {
+                    detailed
+                } else {
+                    format!("{msg} (too many dice to list)")
+                };
+            }
             ctx.send(
                 poise::CreateReply::default()
                     .content(msg)
@@ -43,3 +51,90 @@ pub async fn roll(
 
     Ok(())
 }
+
+/// See the odds of a roll without rolling it
+#[poise::command(slash_command, guild_only)]
+pub async fn odds(
+    ctx: Context<'_>,
+    #[description = "dice to look at e.g. 3d6, 4d6kh3, 2d20kl1+5"] dice: String,
+    #[description = "Show the chance of rolling at least this"] target: Option<i64>,
+    #[description = "See response as a private message (default: false)"] private: Option<bool>,
+) -> Result<(), anyhow::Error> {
+    let ephemeral = private.unwrap_or(false);
+
+    let content = match dice_stats(&dice, target, &ctx.data().config.dice_limits) {
+        Ok(content) => content,
+        Err(e) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(format!("Error working out the odds: {e}"))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(ephemeral),
+    )
+    .await?;
+    Ok(())
+}
+
+fn dice_stats(
+    input: &str,
+    target: Option<i64>,
+    limits: &dice::DiceLimits,
+) -> Result<String, dice::DiceError> {
+    let expr = dice::parse(input)?;
+    limits.check(&expr)?;
+    let dist = stats::distribution(&expr)?;
+
+    let highest = if dist.open_ended {
+        "no limit".to_string()
+    } else {
+        dist.highest().to_string()
+    };
+    let mut lines = vec![format!(
+        "**{expr}**: min {} · max {highest} · mean {:.2} · std dev {:.2}",
+        dist.lowest(),
+        dist.mean(),
+        dist.std_dev()
+    )];
+    if let Some(target) = target {
+        lines.push(format!(
+            "Chance of {target} or more: **{:.2}%**",
+            dist.at_least(target) * 100.0
+        ));
+    }
+    lines.push(format!(
+        "```\n{}\n```",
+        dist.histogram(HISTOGRAM_ROWS, HISTOGRAM_WIDTH)
+    ));
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_summarize_the_odds() {
+        let content = dice_stats("3d6", Some(15), &dice::DiceLimits::default()).unwrap();
+        let mut lines = content.lines();
+        assert_eq!(
+            lines.next(),
+            Some("**3d6**: min 3 · max 18 · mean 10.50 · std dev 2.96")
+        );
+        assert_eq!(lines.next(), Some("Chance of 15 or more: **9.26%**"));
+        assert_eq!(lines.next(), Some("```"));
+        assert_eq!(content.lines().filter(|l| l.contains('%')).count(), 17);
+        assert!(content.chars().count() <= MAX_MESSAGE_LENGTH);
+
+        let content = dice_stats("2d6!", None, &dice::DiceLimits::default()).unwrap();
+        assert!(content.contains("max no limit"));
+    }
+}
@@ -0,0 +1,21 @@
+//! Recovery for games whose scheduled jobs were lost or dead-lettered.
+
+use crate::context::AppContext;
+use crate::roulette::command::recover_roulettes;
+use crate::sardines::command::recover_sardines;
+
+/// Games younger than this are never treated as orphaned.
+pub const ORPHAN_GRACE_SECONDS: i64 = 300;
+
+/// Job handler for games:sweep. Finishes roulette and sardines games with no
+/// active job, refunding or paying out players instead of stranding their bets.
+pub async fn sweep_stale_games(ctx: AppContext, _payload: ()) -> anyhow::Result<()> {
+    recover_orphaned_games(&ctx).await;
+    Ok(())
+}
+
+/// Finish every orphaned game. Runs on startup and from the periodic sweep.
+pub async fn recover_orphaned_games(ctx: &AppContext) {
+    recover_roulettes(ctx).await;
+    recover_sardines(ctx).await;
+}
@@ -0,0 +1,119 @@
+use poise::ChoiceParameter;
+
+use crate::context::Context;
+use crate::guilds::SettingKey;
+use crate::users::admin::is_admin;
+
+/// Tune this server's games
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("get", "set", "reset"),
+    check = "is_admin"
+)]
+pub async fn config(_ctx: Context<'_>) -> Result<(), anyhow::Error> {
+    Ok(())
+}
+
+/// Show one setting, or all of them
+#[poise::command(slash_command, guild_only)]
+async fn get(
+    ctx: Context<'_>,
+    #[description = "Setting to show (default: all)"] key: Option<SettingKey>,
+) -> Result<(), anyhow::Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+    let overrides = ctx.data().guild_settings.get(guild_id).await?;
+    let settings = overrides.resolve(&ctx.data().config);
+
+    let keys = match key {
+        Some(key) => vec![key],
+        None => SettingKey::ALL.to_vec(),
+    };
+    let lines: Vec<String> = keys
+        .into_iter()
+        .map(|key| {
+            let source = if overrides.is_overridden(key) {
+                ""
+            } else {
+                " (default)"
+            };
+            format!("`{}` = **{}**{source}", key.name(), settings.display(key))
+        })
+        .collect();
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(lines.join("\n"))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Override a setting for this server
+#[poise::command(slash_command, guild_only)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Setting to change"] key: SettingKey,
+    #[description = "New value"] value: String,
+) -> Result<(), anyhow::Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+    let data = ctx.data();
+    let mut overrides = data.guild_settings.get(guild_id).await?;
+
+    if let Err(e) = overrides.set(key, &value) {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(e.to_string())
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    data.guild_settings.put(guild_id, &overrides).await?;
+
+    let settings = overrides.resolve(&data.config);
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "`{}` is now **{}**",
+                key.name(),
+                settings.display(key)
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Restore a setting to its default
+#[poise::command(slash_command, guild_only)]
+async fn reset(
+    ctx: Context<'_>,
+    #[description = "Setting to restore"] key: SettingKey,
+) -> Result<(), anyhow::Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+    let data = ctx.data();
+    let mut overrides = data.guild_settings.get(guild_id).await?;
+    overrides.reset(key);
+    data.guild_settings.put(guild_id, &overrides).await?;
+
+    let settings = overrides.resolve(&data.config);
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "`{}` reset to the default **{}**",
+                key.name(),
+                settings.display(key)
+            ))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,6 @@
+pub mod command;
+pub mod settings;
+pub mod store;
+
+pub use settings::{SettingKey, Settings};
+pub use store::GuildSettingsStore;
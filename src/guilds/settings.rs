@@ -0,0 +1,390 @@
+use chrono_tz::Tz;
+use poise::ChoiceParameter;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::Config;
+use crate::games::guess::{LAST_DIGIT_REWARD, MAGIC_NUMBER_REWARD, PAIRWISE_REWARD, RANGE_REWARD};
+use crate::roulette::game::ROULETTE_TIME_SECONDS;
+
+/// Upper bounds for `/config` values, generous enough for any real guild but
+/// small enough that rewards and deadlines derived from them cannot overflow.
+const MAX_PLAYERS: usize = 100;
+const MAX_SARDINES_EXPIRY_SECONDS: u64 = 7 * 24 * 60 * 60;
+const MAX_ROULETTE_TIME_SECONDS: u64 = 24 * 60 * 60;
+const MAX_REWARD: i64 = 1_000_000;
+
+/// A tunable per-guild setting, as exposed by `/config`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum SettingKey {
+    #[name = "timezone"]
+    Timezone,
+    #[name = "min_players_before_rejoin"]
+    MinPlayersBeforeRejoin,
+    #[name = "sardines_expiry_seconds"]
+    SardinesExpirySeconds,
+    #[name = "roulette_time_seconds"]
+    RouletteTimeSeconds,
+    #[name = "daily_reward"]
+    DailyReward,
+    #[name = "guess_magic_number_reward"]
+    GuessMagicNumberReward,
+    #[name = "guess_pairwise_reward"]
+    GuessPairwiseReward,
+    #[name = "guess_range_reward"]
+    GuessRangeReward,
+    #[name = "guess_last_digit_reward"]
+    GuessLastDigitReward,
+    #[name = "admin_role"]
+    AdminRole,
+    #[name = "admin_log_channel"]
+    AdminLogChannel,
+}
+
+impl SettingKey {
+    pub const ALL: [SettingKey; 11] = [
+        Self::Timezone,
+        Self::MinPlayersBeforeRejoin,
+        Self::SardinesExpirySeconds,
+        Self::RouletteTimeSeconds,
+        Self::DailyReward,
+        Self::GuessMagicNumberReward,
+        Self::GuessPairwiseReward,
+        Self::GuessRangeReward,
+        Self::GuessLastDigitReward,
+        Self::AdminRole,
+        Self::AdminLogChannel,
+    ];
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SettingError {
+    #[error("{key} must be {expected}, got \"{value}\"")]
+    Invalid {
+        key: &'static str,
+        expected: String,
+        value: String,
+    },
+}
+
+/// Per-guild overrides stored in Firestore. Unset fields fall back to the
+/// stage-derived defaults in `Config`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuildSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_players_before_rejoin: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sardines_expiry_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roulette_time_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_reward: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guess_magic_number_reward: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guess_pairwise_reward: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guess_range_reward: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guess_last_digit_reward: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_role_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin_log_channel_id: Option<u64>,
+}
+
+/// Effective settings for a guild: overrides merged over defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub timezone: Tz,
+    pub min_players_before_rejoin: usize,
+    pub sardines_expiry_seconds: u64,
+    pub roulette_time_seconds: u64,
+    pub daily_reward: i64,
+    pub guess_magic_number_reward: i64,
+    pub guess_pairwise_reward: i64,
+    pub guess_range_reward: i64,
+    pub guess_last_digit_reward: i64,
+    /// Members with this role may use admin commands, in addition to server managers.
+    pub admin_role_id: Option<u64>,
+    /// Channel that receives an audit message for every admin action.
+    pub admin_log_channel_id: Option<u64>,
+}
+
+impl Settings {
+    /// The defaults every guild starts with.
+    pub fn defaults(config: &Config) -> Self {
+        Self {
+            timezone: config.discord.timezone,
+            min_players_before_rejoin: config.min_players_before_rejoin,
+            sardines_expiry_seconds: config.sardines_expiry_seconds,
+            roulette_time_seconds: ROULETTE_TIME_SECONDS,
+            daily_reward: config.daily_reward,
+            guess_magic_number_reward: MAGIC_NUMBER_REWARD,
+            guess_pairwise_reward: PAIRWISE_REWARD,
+            guess_range_reward: RANGE_REWARD,
+            guess_last_digit_reward: LAST_DIGIT_REWARD,
+            admin_role_id: None,
+            admin_log_channel_id: None,
+        }
+    }
+
+    /// Render a single setting for display.
+    pub fn display(&self, key: SettingKey) -> String {
+        match key {
+            SettingKey::Timezone => self.timezone.to_string(),
+            SettingKey::MinPlayersBeforeRejoin => self.min_players_before_rejoin.to_string(),
+            SettingKey::SardinesExpirySeconds => self.sardines_expiry_seconds.to_string(),
+            SettingKey::RouletteTimeSeconds => self.roulette_time_seconds.to_string(),
+            SettingKey::DailyReward => self.daily_reward.to_string(),
+            SettingKey::GuessMagicNumberReward => self.guess_magic_number_reward.to_string(),
+            SettingKey::GuessPairwiseReward => self.guess_pairwise_reward.to_string(),
+            SettingKey::GuessRangeReward => self.guess_range_reward.to_string(),
+            SettingKey::GuessLastDigitReward => self.guess_last_digit_reward.to_string(),
+            SettingKey::AdminRole => self
+                .admin_role_id
+                .map_or("none".to_string(), |id| format!("<@&{id}>")),
+            SettingKey::AdminLogChannel => self
+                .admin_log_channel_id
+                .map_or("none".to_string(), |id| format!("<#{id}>")),
+        }
+    }
+}
+
+impl GuildSettings {
+    /// Merge the overrides over the defaults. A stored timezone that no longer
+    /// parses falls back to the default rather than failing every command.
+    pub fn resolve(&self, config: &Config) -> Settings {
+        let defaults = Settings::defaults(config);
+        Settings {
+            timezone: self
+                .timezone
+                .as_deref()
+                .and_then(|tz| tz.parse().ok())
+                .unwrap_or(defaults.timezone),
+            min_players_before_rejoin: self
+                .min_players_before_rejoin
+                .unwrap_or(defaults.min_players_before_rejoin),
+            sardines_expiry_seconds: self
+                .sardines_expiry_seconds
+                .unwrap_or(defaults.sardines_expiry_seconds),
+            roulette_time_seconds: self
+                .roulette_time_seconds
+                .unwrap_or(defaults.roulette_time_seconds),
+            daily_reward: self.daily_reward.unwrap_or(defaults.daily_reward),
+            guess_magic_number_reward: self
+                .guess_magic_number_reward
+                .unwrap_or(defaults.guess_magic_number_reward),
+            guess_pairwise_reward: self
+                .guess_pairwise_reward
+                .unwrap_or(defaults.guess_pairwise_reward),
+            guess_range_reward: self
+                .guess_range_reward
+                .unwrap_or(defaults.guess_range_reward),
+            guess_last_digit_reward: self
+                .guess_last_digit_reward
+                .unwrap_or(defaults.guess_last_digit_reward),
+            admin_role_id: self.admin_role_id.or(defaults.admin_role_id),
+            admin_log_channel_id: self.admin_log_channel_id.or(defaults.admin_log_channel_id),
+        }
+    }
+
+    pub fn is_overridden(&self, key: SettingKey) -> bool {
+        match key {
+            SettingKey::Timezone => self.timezone.is_some(),
+            SettingKey::MinPlayersBeforeRejoin => self.min_players_before_rejoin.is_some(),
+            SettingKey::SardinesExpirySeconds => self.sardines_expiry_seconds.is_some(),
+            SettingKey::RouletteTimeSeconds => self.roulette_time_seconds.is_some(),
+            SettingKey::DailyReward => self.daily_reward.is_some(),
+            SettingKey::GuessMagicNumberReward => self.guess_magic_number_reward.is_some(),
+            SettingKey::GuessPairwiseReward => self.guess_pairwise_reward.is_some(),
+            SettingKey::GuessRangeReward => self.guess_range_reward.is_some(),
+            SettingKey::GuessLastDigitReward => self.guess_last_digit_reward.is_some(),
+            SettingKey::AdminRole => self.admin_role_id.is_some(),
+            SettingKey::AdminLogChannel => self.admin_log_channel_id.is_some(),
+        }
+    }
+
+    /// Parse and store an override.
+    pub fn set(&mut self, key: SettingKey, value: &str) -> Result<(), SettingError> {
+        let value = value.trim();
+        match key {
+            SettingKey::Timezone => {
+                let tz: Tz = value
+                    .parse()
+                    .map_err(|_| invalid(key, "an IANA timezone", value))?;
+                self.timezone = Some(tz.to_string());
+            }
+            SettingKey::MinPlayersBeforeRejoin => {
+                self.min_players_before_rejoin = Some(parse_range(key, value, 1, MAX_PLAYERS)?);
+            }
+            SettingKey::SardinesExpirySeconds => {
+                self.sardines_expiry_seconds =
+                    Some(parse_range(key, value, 60, MAX_SARDINES_EXPIRY_SECONDS)?);
+            }
+            SettingKey::RouletteTimeSeconds => {
+                self.roulette_time_seconds =
+                    Some(parse_range(key, value, 10, MAX_ROULETTE_TIME_SECONDS)?);
+            }
+            SettingKey::DailyReward => {
+                self.daily_reward = Some(parse_range(key, value, 0, MAX_REWARD)?);
+            }
+            SettingKey::GuessMagicNumberReward => {
+                self.guess_magic_number_reward = Some(parse_range(key, value, 0, MAX_REWARD)?);
+            }
+            SettingKey::GuessPairwiseReward => {
+                self.guess_pairwise_reward = Some(parse_range(key, value, 0, MAX_REWARD)?);
+            }
+            SettingKey::GuessRangeReward => {
+                self.guess_range_reward = Some(parse_range(key, value, 0, MAX_REWARD)?);
+            }
+            SettingKey::GuessLastDigitReward => {
+                self.guess_last_digit_reward = Some(parse_range(key, value, 0, MAX_REWARD)?);
+            }
+            SettingKey::AdminRole => {
+                self.admin_role_id = Some(parse_id(key, value, "<@&", "a role id or mention")?);
+            }
+            SettingKey::AdminLogChannel => {
+                self.admin_log_channel_id =
+                    Some(parse_id(key, value, "<#", "a channel id or mention")?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove an override so the default applies again.
+    pub fn reset(&mut self, key: SettingKey) {
+        match key {
+            SettingKey::Timezone => self.timezone = None,
+            SettingKey::MinPlayersBeforeRejoin => self.min_players_before_rejoin = None,
+            SettingKey::SardinesExpirySeconds => self.sardines_expiry_seconds = None,
+            SettingKey::RouletteTimeSeconds => self.roulette_time_seconds = None,
+            SettingKey::DailyReward => self.daily_reward = None,
+            SettingKey::GuessMagicNumberReward => self.guess_magic_number_reward = None,
+            SettingKey::GuessPairwiseReward => self.guess_pairwise_reward = None,
+            SettingKey::GuessRangeReward => self.guess_range_reward = None,
+            SettingKey::GuessLastDigitReward => self.guess_last_digit_reward = None,
+            SettingKey::AdminRole => self.admin_role_id = None,
+            SettingKey::AdminLogChannel => self.admin_log_channel_id = None,
+        }
+    }
+}
+
+fn invalid(key: SettingKey, expected: impl Into<String>, value: &str) -> SettingError {
+    SettingError::Invalid {
+        key: key.name(),
+        expected: expected.into(),
+        value: value.to_string(),
+    }
+}
+
+/// Parse an integer setting that must be within `min..=max`.
+fn parse_range<T>(key: SettingKey, value: &str, min: T, max: T) -> Result<T, SettingError>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    match value.parse::<T>() {
+        Ok(v) if v >= min && v <= max => Ok(v),
+        _ => Err(invalid(
+            key,
+            format!("a whole number from {min} to {max}"),
+            value,
+        )),
+    }
+}
+
+/// Parse a Discord id, given bare or as a mention like `<@&123>`.
+fn parse_id(
+    key: SettingKey,
+    value: &str,
+    mention_prefix: &str,
+    expected: &'static str,
+) -> Result<u64, SettingError> {
+    let id = value
+        .strip_prefix(mention_prefix)
+        .and_then(|rest| rest.strip_suffix('>'))
+        .unwrap_or(value);
+    match id.parse::<u64>() {
+        Ok(id) if id > 0 => Ok(id),
+        _ => Err(invalid(key, expected, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_overrides_resolve_to_defaults() {
+        let config = Config::for_tests();
+        assert_eq!(
+            GuildSettings::default().resolve(&config),
+            Settings::defaults(&config)
+        );
+    }
+
+    #[test]
+    fn set_and_reset_override() {
+        let config = Config::for_tests();
+        let mut overrides = GuildSettings::default();
+        overrides
+            .set(SettingKey::RouletteTimeSeconds, "45")
+            .unwrap();
+        overrides
+            .set(SettingKey::Timezone, "Europe/London")
+            .unwrap();
+        let settings = overrides.resolve(&config);
+        assert_eq!(settings.roulette_time_seconds, 45);
+        assert_eq!(settings.timezone, chrono_tz::Europe::London);
+        assert!(overrides.is_overridden(SettingKey::RouletteTimeSeconds));
+
+        overrides.reset(SettingKey::RouletteTimeSeconds);
+        assert_eq!(
+            overrides.resolve(&config).roulette_time_seconds,
+            ROULETTE_TIME_SECONDS
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let mut overrides = GuildSettings::default();
+        assert!(overrides.set(SettingKey::Timezone, "Mars/Base").is_err());
+        assert!(
+            overrides
+                .set(SettingKey::MinPlayersBeforeRejoin, "0")
+                .is_err()
+        );
+        assert!(overrides.set(SettingKey::DailyReward, "lots").is_err());
+        assert!(
+            overrides
+                .set(SettingKey::DailyReward, &i64::MAX.to_string())
+                .is_err()
+        );
+        assert!(
+            overrides
+                .set(SettingKey::RouletteTimeSeconds, &u64::MAX.to_string())
+                .is_err()
+        );
+        assert!(overrides.set(SettingKey::AdminRole, "<#123>").is_err());
+        assert_eq!(overrides, GuildSettings::default());
+    }
+
+    #[test]
+    fn admin_role_and_log_channel_accept_ids_or_mentions() {
+        let config = Config::for_tests();
+        let mut overrides = GuildSettings::default();
+        overrides.set(SettingKey::AdminRole, "<@&42>").unwrap();
+        overrides.set(SettingKey::AdminLogChannel, "7").unwrap();
+        let settings = overrides.resolve(&config);
+        assert_eq!(settings.admin_role_id, Some(42));
+        assert_eq!(settings.admin_log_channel_id, Some(7));
+        assert_eq!(settings.display(SettingKey::AdminLogChannel), "<#7>");
+        assert_eq!(
+            Settings::defaults(&config).display(SettingKey::AdminRole),
+            "none"
+        );
+    }
+}
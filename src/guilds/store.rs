@@ -0,0 +1,32 @@
+use crate::guilds::settings::GuildSettings;
+use crate::storage::{Collection, Storage};
+use poise::serenity_prelude as serenity;
+
+const COLLECTION: &str = "guild_settings";
+
+/// Per-guild setting overrides, one document per guild keyed by guild id.
+pub struct GuildSettingsStore {
+    store: Collection,
+}
+
+impl GuildSettingsStore {
+    pub fn new(storage: Storage) -> Self {
+        Self {
+            store: Collection::new(storage, COLLECTION),
+        }
+    }
+
+    /// Fetch a guild's overrides; guilds that never ran `/config` have none.
+    pub async fn get(&self, guild_id: serenity::GuildId) -> anyhow::Result<GuildSettings> {
+        let settings: Option<GuildSettings> = self.store.get(&guild_id.to_string()).await?;
+        Ok(settings.unwrap_or_default())
+    }
+
+    pub async fn put(
+        &self,
+        guild_id: serenity::GuildId,
+        settings: &GuildSettings,
+    ) -> anyhow::Result<()> {
+        self.store.update(&guild_id.to_string(), settings).await
+    }
+}
@@ -0,0 +1,236 @@
+use chrono::Utc;
+
+use crate::context::Context;
+use crate::jobs::queue::Job;
+use crate::users::admin::is_admin;
+use crate::util::dates::format_distance_to_now;
+
+/// Keep listings within Discord's message length limit.
+const MAX_LISTED: usize = 15;
+
+/// Inspect and manage this server's queued jobs
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("list", "show", "run_now", "cancel", "dead", "retry"),
+    check = "is_admin"
+)]
+pub async fn jobs(_ctx: Context<'_>) -> Result<(), anyhow::Error> {
+    Ok(())
+}
+
+/// List queued and running jobs, soonest first
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), anyhow::Error> {
+    let guild_id = guild_id(ctx)?;
+    let mut jobs = ctx.data().job_queue.read().await.get_active_jobs().await?;
+    jobs.retain(|j| in_guild(j, &guild_id));
+    jobs.sort_by_key(|j| j.execute_at);
+    let content = if jobs.is_empty() {
+        "No queued jobs".to_string()
+    } else {
+        format_listing(&jobs, format_job)
+    };
+    reply(ctx, content).await
+}
+
+/// Show everything about one job
+#[poise::command(slash_command, guild_only)]
+async fn show(
+    ctx: Context<'_>,
+    #[description = "Id of the job"] id: String,
+) -> Result<(), anyhow::Error> {
+    let guild_id = guild_id(ctx)?;
+    let job = ctx.data().job_queue.read().await.get_job(&id).await?;
+    let content = match job.filter(|j| in_guild(j, &guild_id)) {
+        Some(job) => format_job_details(&job),
+        None => format!("No queued job with id `{id}`"),
+    };
+    reply(ctx, content).await
+}
+
+/// Run a pending job now instead of waiting for its due time
+#[poise::command(slash_command, guild_only, rename = "run-now")]
+async fn run_now(
+    ctx: Context<'_>,
+    #[description = "Id of the pending job"] id: String,
+) -> Result<(), anyhow::Error> {
+    let guild_id = guild_id(ctx)?;
+    let job_queue = ctx.data().job_queue.read().await;
+    let job = job_queue.get_job(&id).await?;
+    let moved = match job.filter(|j| in_guild(j, &guild_id)) {
+        Some(_) => job_queue.reschedule_job(&id, Utc::now()).await?,
+        None => false,
+    };
+    let content = if moved {
+        format!("`{id}` will run momentarily")
+    } else {
+        format!("No pending job with id `{id}`")
+    };
+    reply(ctx, content).await
+}
+
+/// Drop a pending job without running it
+#[poise::command(slash_command, guild_only)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Id of the pending job"] id: String,
+) -> Result<(), anyhow::Error> {
+    let guild_id = guild_id(ctx)?;
+    let job_queue = ctx.data().job_queue.read().await;
+    let job = job_queue.get_job(&id).await?;
+    let cancelled = match job.filter(|j| in_guild(j, &guild_id)) {
+        Some(_) => job_queue.cancel_job(&id).await?,
+        None => false,
+    };
+    let content = if cancelled {
+        format!("Cancelled `{id}`")
+    } else {
+        format!("No pending job with id `{id}`")
+    };
+    reply(ctx, content).await
+}
+
+/// List jobs that failed every retry
+#[poise::command(slash_command, guild_only)]
+async fn dead(ctx: Context<'_>) -> Result<(), anyhow::Error> {
+    let guild_id = guild_id(ctx)?;
+    let mut jobs = ctx.data().job_queue.read().await.get_dead_jobs().await?;
+    jobs.retain(|j| in_guild(j, &guild_id));
+    let content = if jobs.is_empty() {
+        "No dead jobs".to_string()
+    } else {
+        format_listing(&jobs, format_dead_job)
+    };
+    reply(ctx, content).await
+}
+
+/// Put a dead job back on the queue
+#[poise::command(slash_command, guild_only)]
+async fn retry(
+    ctx: Context<'_>,
+    #[description = "Id of the dead job"] id: String,
+) -> Result<(), anyhow::Error> {
+    let guild_id = guild_id(ctx)?;
+    let job_queue = ctx.data().job_queue.read().await;
+    let job = job_queue.get_dead_job(&id).await?;
+    let replayed = match job.filter(|j| in_guild(j, &guild_id)) {
+        Some(_) => job_queue.replay_dead_job(&id).await?,
+        None => false,
+    };
+    let content = if replayed {
+        format!("Requeued `{id}`")
+    } else {
+        format!("No dead job with id `{id}`")
+    };
+    reply(ctx, content).await
+}
+
+fn guild_id(ctx: Context<'_>) -> Result<String, anyhow::Error> {
+    Ok(ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Must be in a guild"))?
+        .to_string())
+}
+
+/// Admins only see and manage jobs for their own guild. Jobs that span
+/// every guild, like the stale game sweep, are left to the operator.
+fn in_guild(job: &Job, guild_id: &str) -> bool {
+    job.guild_id.as_deref() == Some(guild_id)
+}
+
+async fn reply(ctx: Context<'_>, content: String) -> Result<(), anyhow::Error> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+fn format_listing(jobs: &[Job], format: fn(&Job) -> String) -> String {
+    let mut lines: Vec<String> = jobs.iter().take(MAX_LISTED).map(format).collect();
+    if jobs.len() > MAX_LISTED {
+        lines.push(format!("…and {} more", jobs.len() - MAX_LISTED));
+    }
+    lines.join("\n")
+}
+
+fn format_job(job: &Job) -> String {
+    let target = job.target_id().unwrap_or("-");
+    format!(
+        "`{}` {} for `{target}`, {}, due {}",
+        job.id,
+        job.job_type,
+        job.status,
+        format_distance_to_now(job.execute_at)
+    )
+}
+
+fn format_job_details(job: &Job) -> String {
+    let mut lines = vec![
+        format!("**{}** `{}`", job.job_type, job.id),
+        format!("Status: {}", job.status),
+        format!(
+            "Due: {} ({})",
+            job.execute_at.to_rfc3339(),
+            format_distance_to_now(job.execute_at)
+        ),
+        format!("Target: `{}`", job.target_id().unwrap_or("-")),
+        format!("Attempts: {}", job.attempts),
+    ];
+    if let Some(error) = &job.last_error {
+        lines.push(format!("Last error: {error}"));
+    }
+    if let Some(owner) = &job.lease_owner {
+        lines.push(format!("Leased by: {owner}"));
+    }
+    lines.join("\n")
+}
+
+fn format_dead_job(job: &Job) -> String {
+    let error = job.last_error.as_deref().unwrap_or("unknown error");
+    format!(
+        "`{}` {} after {} attempts, due {}: {error}",
+        job.id,
+        job.job_type,
+        job.attempts,
+        format_distance_to_now(job.execute_at)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobType;
+
+    fn job(id: &str) -> Job {
+        Job::new(
+            id.to_string(),
+            JobType::RouletteFinish,
+            serde_json::json!({ "id": "game1", "interactionToken": "secret" }),
+            Utc::now(),
+        )
+    }
+
+    #[test]
+    fn listing_shows_target_and_truncates() {
+        let jobs: Vec<Job> = (0..MAX_LISTED + 2).map(|i| job(&format!("j{i}"))).collect();
+        let listing = format_listing(&jobs, format_job);
+        assert!(listing.starts_with("`j0` roulette:finish for `game1`, pending"));
+        assert!(listing.ends_with("…and 2 more"));
+        assert_eq!(listing.lines().count(), MAX_LISTED + 1);
+    }
+
+    #[test]
+    fn only_jobs_for_the_guild_are_in_scope() {
+        let scoped = Job {
+            guild_id: Some("guild1".to_string()),
+            ..job("j1")
+        };
+        assert!(in_guild(&scoped, "guild1"));
+        assert!(!in_guild(&scoped, "guild2"));
+        assert!(!in_guild(&job("sweep"), "guild1"));
+    }
+}
@@ -1,3 +1,6 @@
+pub mod command;
 pub mod queue;
+pub mod schedule;
+pub mod timer;
 
 pub use queue::{JobQueue, JobType};
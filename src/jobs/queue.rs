@@ -5,15 +5,24 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
-use firestore::*;
+use chrono_tz::Tz;
+use firestore::path;
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::context::AppContext;
+use crate::jobs::schedule::{self, Schedule};
+use crate::jobs::timer::Timer;
+use crate::storage::{Query, Storage, StorageResult, Transaction};
+use crate::util::dates::seconds_from_now;
 
-const COLLECTION: &str = "jobs";
+pub(crate) const COLLECTION: &str = "jobs";
+const DEAD_COLLECTION: &str = "jobs_dead";
+/// How long a worker may hold a job before another worker may reclaim it.
+/// Renewed while the handler runs, so it only lapses if the worker stops.
+const LEASE_SECONDS: i64 = 120;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum JobType {
@@ -23,6 +32,8 @@ pub enum JobType {
     RouletteFinish,
     #[serde(rename = "sardines:finish")]
     SardinesFinish,
+    #[serde(rename = "games:sweep")]
+    StaleGameSweep,
 }
 
 impl fmt::Display for JobType {
@@ -31,6 +42,50 @@ impl fmt::Display for JobType {
             Self::RouletteClose => write!(f, "roulette:close"),
             Self::RouletteFinish => write!(f, "roulette:finish"),
             Self::SardinesFinish => write!(f, "sardines:finish"),
+            Self::StaleGameSweep => write!(f, "games:sweep"),
+        }
+    }
+}
+
+/// How often and how patiently a failed job is retried before it is dead-lettered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total runs allowed, including the first.
+    pub max_attempts: u32,
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64,
+}
+
+impl RetryPolicy {
+    /// Delay before the next run after `attempts` failed runs: doubles each time, capped.
+    pub fn backoff_seconds(&self, attempts: u32) -> u64 {
+        let exponent = attempts.saturating_sub(1).min(32);
+        self.base_delay_seconds
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay_seconds)
+    }
+}
+
+impl JobType {
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match self {
+            // Finishing moves the pot, so keep trying for a long while
+            Self::RouletteFinish | Self::SardinesFinish => RetryPolicy {
+                max_attempts: 8,
+                base_delay_seconds: 5,
+                max_delay_seconds: 600,
+            },
+            // A few quick retries; the next scheduled sweep covers anything left over
+            Self::StaleGameSweep => RetryPolicy {
+                max_attempts: 3,
+                base_delay_seconds: 30,
+                max_delay_seconds: 300,
+            },
+            Self::RouletteClose => RetryPolicy {
+                max_attempts: 5,
+                base_delay_seconds: 2,
+                max_delay_seconds: 60,
+            },
         }
     }
 }
@@ -42,6 +97,15 @@ pub enum JobStatus {
     Running,
 }
 
+impl fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Running => write!(f, "running"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Job {
@@ -52,6 +116,83 @@ pub struct Job {
     #[serde(with = "firestore::serialize_as_timestamp")]
     pub execute_at: DateTime<Utc>,
     pub status: JobStatus,
+    /// Caller-supplied dedupe key, such as the game the job acts on.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Guild the job acts in; `None` for jobs that span every guild, such as
+    /// the stale game sweep.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guild_id: Option<String>,
+    /// Failed runs so far.
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Worker currently running the job.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_owner: Option<String>,
+    #[serde(default)]
+    #[serde(with = "firestore::serialize_as_optional_timestamp")]
+    pub lease_expires_at: Option<DateTime<Utc>>,
+}
+
+impl Job {
+    pub fn new(
+        id: String,
+        job_type: JobType,
+        payload: serde_json::Value,
+        execute_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id,
+            job_type,
+            payload,
+            execute_at,
+            status: JobStatus::Pending,
+            key: None,
+            guild_id: None,
+            attempts: 0,
+            last_error: None,
+            lease_owner: None,
+            lease_expires_at: None,
+        }
+    }
+
+    /// Due pending jobs may be claimed, as may running jobs whose worker
+    /// let the lease lapse (e.g. it crashed mid-run).
+    pub fn is_claimable(&self, now: DateTime<Utc>) -> bool {
+        self.claimable_at().is_none_or(|at| at <= now)
+    }
+
+    /// The game or other entity the job acts on, if its payload names one.
+    pub fn target_id(&self) -> Option<&str> {
+        self.payload.get("id").and_then(|id| id.as_str())
+    }
+
+    /// Whether `worker_id` is running the job and its lease has not lapsed.
+    fn is_leased_by(&self, worker_id: &str, now: DateTime<Utc>) -> bool {
+        self.status == JobStatus::Running
+            && self.lease_owner.as_deref() == Some(worker_id)
+            && self.lease_expires_at.is_some_and(|at| at > now)
+    }
+
+    /// When the job next becomes claimable; `None` means it already is.
+    fn claimable_at(&self) -> Option<DateTime<Utc>> {
+        match self.status {
+            JobStatus::Pending => Some(self.execute_at),
+            JobStatus::Running => self.lease_expires_at,
+        }
+    }
+}
+
+/// Identifies an enqueued job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobHandle {
+    pub id: String,
 }
 
 /// Async handler function type for processing jobs.
@@ -62,15 +203,21 @@ pub type JobHandler = Arc<
 >;
 
 pub struct JobQueue {
-    db: FirestoreDb,
+    storage: Storage,
+    /// Identifies this process in job leases and logs.
+    worker_id: String,
+    timer: Arc<Timer>,
     handlers: Arc<RwLock<HashMap<JobType, JobHandler>>>,
     poll_handle: Option<JoinHandle<()>>,
 }
 
 impl JobQueue {
-    pub fn new(db: FirestoreDb) -> Self {
+    pub fn new(storage: Storage) -> Self {
+        let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "worker".to_string());
         Self {
-            db,
+            storage,
+            worker_id: format!("{host}-{}", nanoid::nanoid!(6)),
+            timer: Arc::new(Timer::default()),
             handlers: Arc::new(RwLock::new(HashMap::new())),
             poll_handle: None,
         }
@@ -87,51 +234,220 @@ impl JobQueue {
 
     /// Enqueue a job to run after `delay_seconds`. The payload is automatically
     /// serialized to JSON.
+    ///
+    /// With a `key` (e.g. a game id) the job id is derived from the type and key,
+    /// so enqueueing the same job twice is a no-op that returns the existing
+    /// job, and the job can later be cancelled or rescheduled by that key.
+    ///
+    /// `guild_id` scopes the job to one guild, so only that guild's admins
+    /// can see or manage it.
     pub async fn enqueue<T: Serialize>(
         &self,
         job_type: JobType,
         payload: &T,
         delay_seconds: u64,
-    ) -> anyhow::Result<()> {
-        let execute_at = Utc::now() + chrono::Duration::seconds(delay_seconds as i64);
-        let id = format!("{}-{}", job_type, Utc::now().timestamp_millis());
-
-        info!(%job_type, %id, %execute_at, "Job enqueued");
+        key: Option<&str>,
+        guild_id: Option<&str>,
+    ) -> anyhow::Result<JobHandle> {
+        let execute_at = seconds_from_now(delay_seconds)
+            .ok_or_else(|| anyhow::anyhow!("Job delay of {delay_seconds}s is out of range"))?;
+        let id = job_id(&job_type, key);
 
         let job = Job {
-            id,
-            job_type,
-            payload: serde_json::to_value(payload)?,
-            execute_at,
-            status: JobStatus::Pending,
+            key: key.map(str::to_string),
+            guild_id: guild_id.map(str::to_string),
+            ..Job::new(id, job_type, serde_json::to_value(payload)?, execute_at)
         };
 
-        self.db
-            .fluent()
-            .insert()
-            .into(COLLECTION)
-            .document_id(&job.id)
-            .object(&job)
-            .execute::<()>()
+        let inserted = self
+            .storage
+            .run_transaction({
+                let job = job.clone();
+                move |tx| {
+                    let job = job.clone();
+                    Box::pin(async move {
+                        let existing: Option<Job> = tx.get(COLLECTION, &job.id).await?;
+                        if existing.is_some() {
+                            return Ok(false);
+                        }
+                        tx.update(COLLECTION, &job.id, &job)?;
+                        Ok(true)
+                    })
+                }
+            })
             .await?;
-        Ok(())
+
+        if inserted {
+            self.timer.schedule(execute_at);
+            info!(job_type = %job.job_type, id = job.id, %execute_at, "Job enqueued");
+        } else {
+            info!(job_type = %job.job_type, id = job.id, "Job already enqueued");
+        }
+        Ok(JobHandle { id: job.id })
+    }
+
+    /// Drop every pending job enqueued with `key`. Jobs already running finish
+    /// normally. Returns how many jobs were cancelled.
+    pub async fn cancel(&self, key: &str) -> anyhow::Result<usize> {
+        let mut cancelled = 0;
+        for job in self.get_keyed_jobs(key).await? {
+            if self.cancel_job(&job.id).await? {
+                cancelled += 1;
+            }
+        }
+        Ok(cancelled)
+    }
+
+    /// Drop a single job if it is still pending. Returns whether it was dropped.
+    pub async fn cancel_job(&self, id: &str) -> anyhow::Result<bool> {
+        let removed: Option<JobType> = self
+            .storage
+            .run_transaction({
+                let id = id.to_string();
+                move |tx| {
+                    let id = id.clone();
+                    Box::pin(async move {
+                        let current: Option<Job> = tx.get(COLLECTION, &id).await?;
+                        let Some(current) = current.filter(|j| j.status == JobStatus::Pending)
+                        else {
+                            return Ok(None);
+                        };
+                        tx.delete(COLLECTION, &id)?;
+                        Ok(Some(current.job_type))
+                    })
+                }
+            })
+            .await?;
+        if let Some(job_type) = &removed {
+            info!(%job_type, id, "Job cancelled");
+        }
+        Ok(removed.is_some())
+    }
+
+    /// Move a single pending job to `execute_at`. Returns whether it was moved.
+    pub async fn reschedule_job(
+        &self,
+        id: &str,
+        execute_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let moved: Option<JobType> = self
+            .storage
+            .run_transaction({
+                let id = id.to_string();
+                move |tx| {
+                    let id = id.clone();
+                    Box::pin(async move {
+                        let current: Option<Job> = tx.get(COLLECTION, &id).await?;
+                        let Some(current) = current.filter(|j| j.status == JobStatus::Pending)
+                        else {
+                            return Ok(None);
+                        };
+                        let moved = Job {
+                            execute_at,
+                            ..current
+                        };
+                        tx.update(COLLECTION, &id, &moved)?;
+                        Ok(Some(moved.job_type))
+                    })
+                }
+            })
+            .await?;
+        if let Some(job_type) = &moved {
+            self.timer.schedule(execute_at);
+            info!(%job_type, id, %execute_at, "Job rescheduled");
+        }
+        Ok(moved.is_some())
+    }
+
+    pub async fn get_job(&self, id: &str) -> anyhow::Result<Option<Job>> {
+        Ok(self.storage.get(COLLECTION, id).await?)
+    }
+
+    async fn get_keyed_jobs(&self, key: &str) -> anyhow::Result<Vec<Job>> {
+        let query = Query::new(COLLECTION).eq(path!(Job::key), key);
+        Ok(self.storage.query(&query).await?)
     }
 
     /// Get all pending jobs.
     pub async fn get_pending_jobs(&self) -> anyhow::Result<Vec<Job>> {
-        let jobs: Vec<Job> = self
-            .db
-            .fluent()
-            .select()
-            .from(COLLECTION)
-            .filter(|q| q.for_all([q.field(path!(Job::status)).eq("pending")]))
-            .obj()
-            .query()
+        let query = Query::new(COLLECTION).eq(path!(Job::status), "pending");
+        Ok(self.storage.query(&query).await?)
+    }
+
+    /// Get all jobs that have not completed, including ones a worker is running.
+    pub async fn get_active_jobs(&self) -> anyhow::Result<Vec<Job>> {
+        get_active_jobs(&self.storage).await
+    }
+
+    /// Run `job_type` on a recurring cron schedule evaluated in `tz`. Schedules
+    /// are stored under `id`, so registering the same one on every startup is safe.
+    pub async fn schedule<T: Serialize>(
+        &self,
+        id: &str,
+        job_type: JobType,
+        payload: &T,
+        cron: &str,
+        tz: Tz,
+    ) -> anyhow::Result<()> {
+        let schedule: Schedule = cron.parse()?;
+        schedule::upsert(
+            &self.storage,
+            id,
+            job_type,
+            serde_json::to_value(payload)?,
+            &schedule,
+            tz,
+        )
+        .await
+    }
+
+    /// Get jobs that exhausted their retries.
+    pub async fn get_dead_jobs(&self) -> anyhow::Result<Vec<Job>> {
+        let jobs = self.storage.list(DEAD_COLLECTION).await?;
+        Ok(jobs.into_iter().map(|(_, job)| job).collect())
+    }
+
+    pub async fn get_dead_job(&self, id: &str) -> anyhow::Result<Option<Job>> {
+        Ok(self.storage.get(DEAD_COLLECTION, id).await?)
+    }
+
+    /// Move a dead job back onto the queue with a fresh set of attempts, due now.
+    /// Returns false if no dead job has that id.
+    pub async fn replay_dead_job(&self, id: &str) -> anyhow::Result<bool> {
+        let replayed: Option<Job> = self
+            .storage
+            .run_transaction({
+                let id = id.to_string();
+                move |tx| {
+                    let id = id.clone();
+                    Box::pin(async move {
+                        let dead: Option<Job> = tx.get(DEAD_COLLECTION, &id).await?;
+                        let Some(dead) = dead else {
+                            return Ok(None);
+                        };
+                        let job = Job {
+                            execute_at: Utc::now(),
+                            status: JobStatus::Pending,
+                            attempts: 0,
+                            ..dead
+                        };
+                        tx.update(COLLECTION, &job.id, &job)?;
+                        tx.delete(DEAD_COLLECTION, &job.id)?;
+                        Ok(Some(job))
+                    })
+                }
+            })
             .await?;
-        Ok(jobs)
+        let Some(job) = replayed else {
+            return Ok(false);
+        };
+        self.timer.schedule(job.execute_at);
+
+        info!(job_type = %job.job_type, id = job.id, "Dead job replayed");
+        Ok(true)
     }
 
-    /// Stop the polling loop.
+    /// Stop the worker loop.
     pub fn stop(&mut self) {
         if let Some(handle) = self.poll_handle.take() {
             handle.abort();
@@ -139,27 +455,34 @@ impl JobQueue {
         }
     }
 
-    /// Start the polling loop. Processes immediately on startup for recovery,
-    /// then polls at the given interval.
+    /// Start the worker loop. It processes immediately on startup for recovery,
+    /// then sleeps until the next job is due, waking early when this process
+    /// enqueues a sooner one. Jobs enqueued by other processes are picked up by
+    /// a slow safety poll every `poll_interval_ms`.
     pub fn start(&mut self, poll_interval_ms: u64) {
-        info!(poll_interval_ms, "Job queue started");
+        info!(
+            poll_interval_ms,
+            worker = self.worker_id,
+            "Job queue started"
+        );
 
-        let db = self.db.clone();
+        let storage = self.storage.clone();
+        let worker_id = self.worker_id.clone();
         let handlers = self.handlers.clone();
+        let timer = self.timer.clone();
+        let poll_interval = chrono::Duration::milliseconds(poll_interval_ms as i64);
 
         let handle = tokio::spawn(async move {
-            // Process immediately on startup for recovery
-            if let Err(e) = process_due_jobs(&db, &handlers).await {
-                error!(error = %e, "Error processing jobs on startup");
-            }
-
-            let mut interval =
-                tokio::time::interval(tokio::time::Duration::from_millis(poll_interval_ms));
             loop {
-                interval.tick().await;
-                if let Err(e) = process_due_jobs(&db, &handlers).await {
+                // This pass sees everything due so far and plans the next one
+                timer.clear();
+                if let Err(e) = schedule::enqueue_due(&storage, &worker_id, &timer).await {
+                    error!(error = %e, "Error enqueueing scheduled jobs");
+                }
+                if let Err(e) = process_due_jobs(&storage, &worker_id, &handlers, &timer).await {
                     error!(error = %e, "Error processing jobs");
                 }
+                timer.wait(Utc::now() + poll_interval).await;
             }
         });
 
@@ -167,35 +490,170 @@ impl JobQueue {
     }
 }
 
-/// Poll for due jobs and execute them.
+/// Keyed jobs get a stable id so they dedupe; the rest get a unique one.
+fn job_id(job_type: &JobType, key: Option<&str>) -> String {
+    match key {
+        Some(key) => format!("{job_type}:{key}"),
+        None => format!("{job_type}-{}", nanoid::nanoid!()),
+    }
+}
+
+async fn get_active_jobs(storage: &Storage) -> anyhow::Result<Vec<Job>> {
+    let query = Query::new(COLLECTION).any_of(path!(Job::status), ["pending", "running"]);
+    Ok(storage.query(&query).await?)
+}
+
+/// Claim and execute every due job, and plan a pass for when the next one comes due.
 async fn process_due_jobs(
-    db: &FirestoreDb,
+    storage: &Storage,
+    worker_id: &str,
     handlers: &Arc<RwLock<HashMap<JobType, JobHandler>>>,
+    timer: &Timer,
 ) -> anyhow::Result<()> {
-    let jobs: Vec<Job> = db
-        .fluent()
-        .select()
-        .from(COLLECTION)
-        .filter(|q| q.for_all([q.field(path!(Job::status)).eq("pending")]))
-        .obj()
-        .query()
-        .await?;
+    let jobs = get_active_jobs(storage).await?;
 
     let now = Utc::now();
 
     for job in jobs {
-        if job.execute_at <= now {
-            execute_job(db, handlers, &job).await;
+        if !job.is_claimable(now) {
+            if let Some(at) = job.claimable_at() {
+                timer.schedule(at);
+            }
+            continue;
+        }
+        match claim_job(storage, &job.id, worker_id).await {
+            Ok(Some(Claim::Claimed(claimed))) => {
+                execute_job(storage, worker_id, handlers, timer, &claimed).await
+            }
+            Ok(Some(Claim::DeadLettered(dead))) => {
+                error!(job_type = %dead.job_type, id = dead.id, worker = worker_id, attempts = dead.attempts, "Job lease lapsed with no attempts left, dead-lettering")
+            }
+            // Another worker got there first
+            Ok(None) => {}
+            Err(e) => error!(error = %e, id = job.id, worker = worker_id, "Failed to claim job"),
         }
     }
 
     Ok(())
 }
 
-/// Execute a single job: mark running, call handler, delete on success or mark failed.
+/// What claiming a job did.
+enum Claim {
+    /// This worker holds the lease and should run the job.
+    Claimed(Job),
+    /// The job's last lease lapsed with no attempts left, so it was
+    /// dead-lettered instead of run again.
+    DeadLettered(Job),
+}
+
+/// Take a lease on a job inside a transaction so only one worker runs it.
+/// A reclaimed job whose previous lease lapsed counts as a failed attempt.
+async fn claim_job(storage: &Storage, id: &str, worker_id: &str) -> anyhow::Result<Option<Claim>> {
+    let id = id.to_string();
+    let worker_id = worker_id.to_string();
+    let claim = storage
+        .run_transaction(move |tx| {
+            let id = id.clone();
+            let worker_id = worker_id.clone();
+            Box::pin(async move {
+                let job: Option<Job> = tx.get(COLLECTION, &id).await?;
+                let now = Utc::now();
+                let Some(job) = job.filter(|j| j.is_claimable(now)) else {
+                    return Ok(None);
+                };
+
+                let (attempts, last_error) = match (&job.status, &job.lease_owner) {
+                    (JobStatus::Running, owner) => (
+                        job.attempts + 1,
+                        Some(format!(
+                            "lease held by {} expired",
+                            owner.as_deref().unwrap_or("unknown worker")
+                        )),
+                    ),
+                    (JobStatus::Pending, _) => (job.attempts, job.last_error.clone()),
+                };
+                if attempts >= job.job_type.retry_policy().max_attempts {
+                    let dead = Job {
+                        attempts,
+                        last_error,
+                        lease_owner: None,
+                        lease_expires_at: None,
+                        ..job
+                    };
+                    tx.update(DEAD_COLLECTION, &id, &dead)?;
+                    tx.delete(COLLECTION, &id)?;
+                    return Ok(Some(Claim::DeadLettered(dead)));
+                }
+
+                let claimed = Job {
+                    status: JobStatus::Running,
+                    attempts,
+                    last_error,
+                    lease_owner: Some(worker_id),
+                    lease_expires_at: Some(now + chrono::Duration::seconds(LEASE_SECONDS)),
+                    ..job
+                };
+                tx.update(COLLECTION, &id, &claimed)?;
+                Ok(Some(Claim::Claimed(claimed)))
+            })
+        })
+        .await?;
+    Ok(claim)
+}
+
+/// Apply `write` to the job inside a transaction, but only while `worker_id`
+/// still holds an unexpired lease on it. Returns false if the lease was lost,
+/// e.g. it lapsed and another worker reclaimed the job.
+async fn with_lease<F>(
+    storage: &Storage,
+    job_id: &str,
+    worker_id: &str,
+    write: F,
+) -> anyhow::Result<bool>
+where
+    F: Fn(&mut Transaction, Job) -> StorageResult<()> + Send + Sync + 'static,
+{
+    let id = job_id.to_string();
+    let worker_id = worker_id.to_string();
+    let write = Arc::new(write);
+    let held = storage
+        .run_transaction(move |tx| {
+            let id = id.clone();
+            let worker_id = worker_id.clone();
+            let write = write.clone();
+            Box::pin(async move {
+                let job: Option<Job> = tx.get(COLLECTION, &id).await?;
+                let Some(job) = job.filter(|j| j.is_leased_by(&worker_id, Utc::now())) else {
+                    return Ok(false);
+                };
+                write(tx, job)?;
+                Ok(true)
+            })
+        })
+        .await?;
+    Ok(held)
+}
+
+/// Push a running job's lease expiry out by another `LEASE_SECONDS`.
+async fn renew_lease(storage: &Storage, job_id: &str, worker_id: &str) -> anyhow::Result<bool> {
+    with_lease(storage, job_id, worker_id, |tx, job| {
+        let renewed = Job {
+            lease_expires_at: Some(Utc::now() + chrono::Duration::seconds(LEASE_SECONDS)),
+            ..job
+        };
+        tx.update(COLLECTION, &renewed.id, &renewed)
+    })
+    .await
+}
+
+/// Execute a claimed job: call handler, delete on success or retry on failure.
+/// The lease is renewed while the handler runs, and the outcome is only
+/// recorded if this worker still holds it.
 async fn execute_job(
-    db: &FirestoreDb,
+    storage: &Storage,
+    worker_id: &str,
     handlers: &Arc<RwLock<HashMap<JobType, JobHandler>>>,
+    timer: &Timer,
     job: &Job,
 ) {
     let handler = {
@@ -203,63 +661,121 @@ async fn execute_job(
         map.get(&job.job_type).cloned()
     };
 
-    let Some(handler) = handler else {
-        error!(job_type = %job.job_type, "No handler registered for job type");
-        return;
-    };
-
-    // Mark as running
-    let running = Job {
-        status: JobStatus::Running,
-        ..job.clone()
+    let result = match handler {
+        Some(handler) => run_with_lease(storage, worker_id, job, handler).await,
+        None => Err(anyhow::anyhow!(
+            "No handler registered for job type {}",
+            job.job_type
+        )),
     };
-    if let Err(e) = db
-        .fluent()
-        .update()
-        .in_col(COLLECTION)
-        .document_id(&job.id)
-        .object(&running)
-        .execute::<()>()
-        .await
-    {
-        error!(error = %e, id = job.id, "Failed to mark job as running");
-        return;
-    }
 
-    // Execute
-    match handler(job.payload.clone()).await {
+    match result {
         Ok(()) => {
             // Delete completed job
-            if let Err(e) = db
-                .fluent()
-                .delete()
-                .from(COLLECTION)
-                .document_id(&job.id)
-                .execute()
-                .await
-            {
-                error!(error = %e, id = job.id, "Failed to delete completed job");
+            let completed = with_lease(storage, &job.id, worker_id, |tx, job| {
+                tx.delete(COLLECTION, &job.id)
+            })
+            .await;
+            match completed {
+                Ok(true) => {
+                    info!(job_type = %job.job_type, id = job.id, worker = worker_id, "Job completed")
+                }
+                Ok(false) => {
+                    warn!(job_type = %job.job_type, id = job.id, worker = worker_id, "Job completed after its lease was lost")
+                }
+                Err(e) => {
+                    error!(error = %e, id = job.id, worker = worker_id, "Failed to delete completed job")
+                }
             }
-            info!(job_type = %job.job_type, id = job.id, "Job completed");
         }
         Err(e) => {
-            error!(error = %e, job_type = %job.job_type, id = job.id, "Job failed");
-            // Delete failed jobs — there is no retry mechanism, so leaving
-            // them in Firestore just leaks documents.
-            if let Err(e) = db
-                .fluent()
-                .delete()
-                .from(COLLECTION)
-                .document_id(&job.id)
-                .execute()
-                .await
-            {
-                error!(error = %e, id = job.id, "Failed to delete failed job");
+            if let Err(e) = record_failure(storage, worker_id, timer, job, &e).await {
+                error!(error = %e, id = job.id, worker = worker_id, "Failed to record job failure");
             }
         }
     }
 }
 
+/// Run the handler, renewing the job's lease every third of `LEASE_SECONDS`
+/// so a slow handler is not reclaimed by another worker mid-run.
+async fn run_with_lease(
+    storage: &Storage,
+    worker_id: &str,
+    job: &Job,
+    handler: JobHandler,
+) -> anyhow::Result<()> {
+    let run = handler(job.payload.clone());
+    tokio::pin!(run);
+    let period = std::time::Duration::from_secs(LEASE_SECONDS as u64 / 3);
+    let mut renewals = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+        tokio::select! {
+            result = &mut run => return result,
+            _ = renewals.tick() => match renew_lease(storage, &job.id, worker_id).await {
+                Ok(true) => {}
+                Ok(false) => warn!(id = job.id, worker = worker_id, "Lost job lease while running"),
+                Err(e) => error!(error = %e, id = job.id, worker = worker_id, "Failed to renew job lease"),
+            },
+        }
+    }
+}
+
+/// Reschedule a failed job with backoff, or move it to the dead-letter
+/// collection once its policy's attempts are used up.
+async fn record_failure(
+    storage: &Storage,
+    worker_id: &str,
+    timer: &Timer,
+    job: &Job,
+    err: &anyhow::Error,
+) -> anyhow::Result<()> {
+    let policy = job.job_type.retry_policy();
+    let attempts = job.attempts + 1;
+    let last_error = format!("{err:#}");
+    let retry_at = (attempts < policy.max_attempts)
+        .then(|| Utc::now() + chrono::Duration::seconds(policy.backoff_seconds(attempts) as i64));
+
+    let recorded = with_lease(storage, &job.id, worker_id, move |tx, job| {
+        let failed = Job {
+            attempts,
+            last_error: Some(last_error.clone()),
+            lease_owner: None,
+            lease_expires_at: None,
+            ..job
+        };
+        match retry_at {
+            Some(execute_at) => {
+                let retry = Job {
+                    status: JobStatus::Pending,
+                    execute_at,
+                    ..failed
+                };
+                tx.update(COLLECTION, &retry.id, &retry)
+            }
+            None => {
+                tx.update(DEAD_COLLECTION, &failed.id, &failed)?;
+                tx.delete(COLLECTION, &failed.id)
+            }
+        }
+    })
+    .await?;
+
+    if !recorded {
+        warn!(error = %err, job_type = %job.job_type, id = job.id, worker = worker_id, "Job failed after its lease was lost");
+        return Ok(());
+    }
+    match retry_at {
+        Some(execute_at) => {
+            error!(error = %err, job_type = %job.job_type, id = job.id, worker = worker_id, attempts, %execute_at, "Job failed, retrying");
+            timer.schedule(execute_at);
+        }
+        None => {
+            error!(error = %err, job_type = %job.job_type, id = job.id, worker = worker_id, attempts, "Job failed permanently, dead-lettering");
+        }
+    }
+    Ok(())
+}
+
 pub struct JobRegistrar<'a> {
     ctx: AppContext,
     handlers: Vec<(JobType, JobHandler)>,
@@ -292,3 +808,325 @@ impl<'a> JobRegistrar<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::emulator;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn backoff_doubles_then_caps() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay_seconds: 5,
+            max_delay_seconds: 60,
+        };
+        assert_eq!(policy.backoff_seconds(1), 5);
+        assert_eq!(policy.backoff_seconds(2), 10);
+        assert_eq!(policy.backoff_seconds(3), 20);
+        assert_eq!(policy.backoff_seconds(5), 60);
+        assert_eq!(policy.backoff_seconds(u32::MAX), 60);
+    }
+
+    fn job(status: JobStatus, lease_expires_at: Option<DateTime<Utc>>) -> Job {
+        Job {
+            status,
+            lease_expires_at,
+            ..Job::new(
+                "roulette:finish-1".to_string(),
+                JobType::RouletteFinish,
+                serde_json::Value::Null,
+                Utc::now() - chrono::Duration::seconds(1),
+            )
+        }
+    }
+
+    #[test]
+    fn keyed_job_ids_are_stable_and_unkeyed_ids_unique() {
+        assert_eq!(
+            job_id(&JobType::RouletteClose, Some("game1")),
+            job_id(&JobType::RouletteClose, Some("game1"))
+        );
+        assert_ne!(
+            job_id(&JobType::RouletteClose, Some("game1")),
+            job_id(&JobType::RouletteFinish, Some("game1"))
+        );
+        assert_ne!(
+            job_id(&JobType::RouletteClose, None),
+            job_id(&JobType::RouletteClose, None)
+        );
+    }
+
+    #[test]
+    fn due_pending_jobs_are_claimable() {
+        let now = Utc::now();
+        assert!(job(JobStatus::Pending, None).is_claimable(now));
+
+        let future = Job {
+            execute_at: now + chrono::Duration::seconds(60),
+            ..job(JobStatus::Pending, None)
+        };
+        assert!(!future.is_claimable(now));
+    }
+
+    #[test]
+    fn running_jobs_are_claimable_only_after_lease_expires() {
+        let now = Utc::now();
+        let held = job(
+            JobStatus::Running,
+            Some(now + chrono::Duration::seconds(30)),
+        );
+        assert!(!held.is_claimable(now));
+
+        let lapsed = job(JobStatus::Running, Some(now - chrono::Duration::seconds(1)));
+        assert!(lapsed.is_claimable(now));
+
+        // Jobs marked running before leases existed are stuck; let them be reclaimed
+        assert!(job(JobStatus::Running, None).is_claimable(now));
+    }
+
+    /// A handler that fails its first `failures` runs, counting every run.
+    fn flaky_handler(failures: u32, runs: Arc<AtomicU32>) -> JobHandler {
+        Arc::new(move |_| {
+            let run = runs.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                if run < failures {
+                    anyhow::bail!("run {run} failed");
+                }
+                Ok(())
+            })
+        })
+    }
+
+    async fn process(queue: &JobQueue) {
+        process_due_jobs(
+            &queue.storage,
+            &queue.worker_id,
+            &queue.handlers,
+            &queue.timer,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the Firestore emulator"]
+    async fn keyed_jobs_dedupe_and_cancel() {
+        let storage = emulator::storage().await;
+        let queue = JobQueue::new(storage);
+
+        let first = queue
+            .enqueue(
+                JobType::RouletteClose,
+                &json!({ "id": "game1" }),
+                60,
+                Some("game1"),
+                Some("guild1"),
+            )
+            .await
+            .unwrap();
+        let second = queue
+            .enqueue(
+                JobType::RouletteClose,
+                &json!({ "id": "game1" }),
+                60,
+                Some("game1"),
+                Some("guild1"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(queue.get_pending_jobs().await.unwrap().len(), 1);
+
+        assert_eq!(queue.cancel("game1").await.unwrap(), 1);
+        assert!(queue.get_job(&first.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the Firestore emulator"]
+    async fn failed_jobs_retry_then_dead_letter_and_replay() {
+        let storage = emulator::storage().await;
+        let queue = JobQueue::new(storage);
+        let policy = JobType::RouletteClose.retry_policy();
+        let runs = Arc::new(AtomicU32::new(0));
+        queue.handlers.write().await.insert(
+            JobType::RouletteClose,
+            flaky_handler(policy.max_attempts, runs.clone()),
+        );
+
+        let handle = queue
+            .enqueue(
+                JobType::RouletteClose,
+                &json!({ "id": "game1" }),
+                0,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        for attempt in 1..policy.max_attempts {
+            process(&queue).await;
+            let job = queue.get_job(&handle.id).await.unwrap().unwrap();
+            assert_eq!(job.status, JobStatus::Pending);
+            assert_eq!(job.attempts, attempt);
+            assert!(job.last_error.is_some());
+            assert!(job.execute_at > Utc::now());
+            // Skip the backoff
+            assert!(queue.reschedule_job(&handle.id, Utc::now()).await.unwrap());
+        }
+
+        process(&queue).await;
+        assert!(queue.get_job(&handle.id).await.unwrap().is_none());
+        let dead = queue.get_dead_jobs().await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, policy.max_attempts);
+
+        assert!(queue.replay_dead_job(&handle.id).await.unwrap());
+        assert!(queue.get_dead_jobs().await.unwrap().is_empty());
+        process(&queue).await;
+        assert!(queue.get_job(&handle.id).await.unwrap().is_none());
+        assert_eq!(runs.load(Ordering::SeqCst), policy.max_attempts + 1);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the Firestore emulator"]
+    async fn lapsed_leases_are_reclaimed_as_failed_attempts() {
+        let storage = emulator::storage().await;
+        let queue = JobQueue::new(storage.clone());
+        let stuck = Job {
+            status: JobStatus::Running,
+            lease_owner: Some("crashed-worker".to_string()),
+            lease_expires_at: Some(Utc::now() - chrono::Duration::seconds(1)),
+            ..Job::new(
+                "roulette:close-stuck".to_string(),
+                JobType::RouletteClose,
+                json!({ "id": "game1" }),
+                Utc::now() - chrono::Duration::seconds(LEASE_SECONDS),
+            )
+        };
+        storage.update(COLLECTION, &stuck.id, &stuck).await.unwrap();
+
+        let Some(Claim::Claimed(claimed)) = claim_job(&storage, &stuck.id, &queue.worker_id)
+            .await
+            .unwrap()
+        else {
+            panic!("job was not claimed");
+        };
+        assert_eq!(claimed.attempts, 1);
+        assert_eq!(
+            claimed.lease_owner.as_deref(),
+            Some(queue.worker_id.as_str())
+        );
+        // The new lease keeps other workers out
+        assert!(
+            claim_job(&storage, &stuck.id, "other-worker")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    fn leased(id: &str, owner: &str, attempts: u32, lease_seconds: i64) -> Job {
+        Job {
+            status: JobStatus::Running,
+            attempts,
+            lease_owner: Some(owner.to_string()),
+            lease_expires_at: Some(Utc::now() + chrono::Duration::seconds(lease_seconds)),
+            ..Job::new(
+                id.to_string(),
+                JobType::RouletteClose,
+                json!({ "id": "game1" }),
+                Utc::now() - chrono::Duration::seconds(LEASE_SECONDS),
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn outcomes_are_only_recorded_under_a_held_lease() {
+        let storage = Storage::memory();
+        let timer = Timer::default();
+        let job = leased("roulette:close-1", "worker-a", 0, LEASE_SECONDS);
+        storage.update(COLLECTION, &job.id, &job).await.unwrap();
+
+        assert!(renew_lease(&storage, &job.id, "worker-a").await.unwrap());
+        assert!(!renew_lease(&storage, &job.id, "worker-b").await.unwrap());
+
+        // Another worker reclaimed the job after worker-a's lease lapsed
+        let reclaimed = leased(&job.id, "worker-b", 1, LEASE_SECONDS);
+        storage
+            .update(COLLECTION, &job.id, &reclaimed)
+            .await
+            .unwrap();
+        let err = anyhow::anyhow!("boom");
+        record_failure(&storage, "worker-a", &timer, &job, &err)
+            .await
+            .unwrap();
+        let deleted = with_lease(&storage, &job.id, "worker-a", |tx, job| {
+            tx.delete(COLLECTION, &job.id)
+        })
+        .await
+        .unwrap();
+        assert!(!deleted);
+
+        let current: Job = storage.get(COLLECTION, &job.id).await.unwrap().unwrap();
+        assert_eq!(current.lease_owner.as_deref(), Some("worker-b"));
+        assert_eq!(current.attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn lapsed_leases_on_the_last_attempt_are_dead_lettered() {
+        let storage = Storage::memory();
+        let max_attempts = JobType::RouletteClose.retry_policy().max_attempts;
+        let job = leased("roulette:close-1", "crashed-worker", max_attempts - 1, -1);
+        storage.update(COLLECTION, &job.id, &job).await.unwrap();
+
+        let claim = claim_job(&storage, &job.id, "worker").await.unwrap();
+        assert!(matches!(claim, Some(Claim::DeadLettered(_))));
+        let current: Option<Job> = storage.get(COLLECTION, &job.id).await.unwrap();
+        assert!(current.is_none());
+        let dead: Job = storage
+            .get(DEAD_COLLECTION, &job.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(dead.attempts, max_attempts);
+    }
+
+    #[tokio::test]
+    async fn jobs_without_a_handler_fail_instead_of_staying_running() {
+        let queue = JobQueue::new(Storage::memory());
+        let handle = queue
+            .enqueue(
+                JobType::RouletteClose,
+                &json!({ "id": "game1" }),
+                0,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        process(&queue).await;
+        let job = queue.get_job(&handle.id).await.unwrap().unwrap();
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.attempts, 1);
+        assert!(job.last_error.unwrap().contains("No handler"));
+    }
+
+    #[test]
+    fn jobs_without_retry_fields_deserialize() {
+        let job: Job = serde_json::from_value(serde_json::json!({
+            "id": "roulette:finish-1",
+            "type": "roulette:finish",
+            "payload": {},
+            "executeAt": "2024-01-01T00:00:00Z",
+            "status": "pending",
+        }))
+        .unwrap();
+        assert_eq!(job.attempts, 0);
+        assert_eq!(job.last_error, None);
+    }
+}
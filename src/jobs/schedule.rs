@@ -0,0 +1,368 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{error, info};
+
+use crate::jobs::queue::{COLLECTION as JOBS_COLLECTION, Job, JobType};
+use crate::jobs::timer::Timer;
+use crate::storage::Storage;
+
+pub const COLLECTION: &str = "job_schedules";
+/// How far ahead to search for the next match; covers leap-day-only expressions.
+const MAX_LOOKAHEAD_DAYS: i64 = 366 * 4;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("Cron expressions need 5 fields (minute hour day month weekday), got {0}")]
+    FieldCount(usize),
+    #[error("Invalid cron field \"{0}\"")]
+    InvalidField(String),
+}
+
+/// A standard 5-field cron expression: minute, hour, day of month, month, day of week.
+/// Each field accepts `*`, numbers, ranges (`1-5`), lists (`1,3`) and steps (`*/15`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = ScheduleError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, dom, month, dow] = fields[..] else {
+            return Err(ScheduleError::FieldCount(fields.len()));
+        };
+        // Sunday may be written as 0 or 7
+        let mut days_of_week = parse_field(dow, 0, 7)?;
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+        Ok(Self {
+            expr: fields.join(" "),
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(dom, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            any_day_of_month: dom == "*",
+            any_day_of_week: dow == "*",
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expr)
+    }
+}
+
+impl Schedule {
+    /// The first matching minute strictly after `after`, evaluated in `tz`.
+    /// Local times skipped by a DST change never match.
+    pub fn next_after(&self, after: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let start = after.with_timezone(&tz).date_naive();
+        for offset in 0..=MAX_LOOKAHEAD_DAYS {
+            let date = start + Duration::days(offset);
+            if !self.matches_date(date) {
+                continue;
+            }
+            for hour in (0..24).filter(|h| bit(self.hours, *h)) {
+                for minute in (0..60).filter(|m| bit(self.minutes, *m)) {
+                    let Some(naive) = date.and_hms_opt(hour, minute, 0) else {
+                        continue;
+                    };
+                    if let Some(next) = resolve_local(tz, naive).filter(|t| *t > after) {
+                        return Some(next);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_date(&self, date: chrono::NaiveDate) -> bool {
+        if !bit(self.months, date.month()) {
+            return false;
+        }
+        let dom = bit(self.days_of_month, date.day());
+        let dow = bit(self.days_of_week, date.weekday().num_days_from_sunday());
+        // Classic cron: when both day fields are restricted, either may match
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+}
+
+fn bit(mask: u64, n: u32) -> bool {
+    mask & (1 << n) != 0
+}
+
+/// Earliest UTC instant for a local time that is after the DST fold, if it exists.
+fn resolve_local(tz: Tz, naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, ScheduleError> {
+    let invalid = || ScheduleError::InvalidField(field.to_string());
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (
+                    a.parse().map_err(|_| invalid())?,
+                    b.parse().map_err(|_| invalid())?,
+                ),
+                // `5/15` means every 15 starting at 5
+                None if part.contains('/') => (range.parse().map_err(|_| invalid())?, max),
+                None => {
+                    let n = range.parse().map_err(|_| invalid())?;
+                    (n, n)
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(invalid());
+        }
+        for n in (start..=end).step_by(step as usize) {
+            mask |= 1 << n;
+        }
+    }
+    Ok(mask)
+}
+
+/// A recurring job, persisted so schedules survive restarts. Each occurrence
+/// is enqueued as an ordinary one-shot job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledJob {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub job_type: JobType,
+    pub payload: serde_json::Value,
+    pub cron: String,
+    pub timezone: String,
+    #[serde(with = "firestore::serialize_as_timestamp")]
+    pub next_run_at: DateTime<Utc>,
+}
+
+impl ScheduledJob {
+    fn next_run_after(&self, after: DateTime<Utc>) -> anyhow::Result<Option<DateTime<Utc>>> {
+        let schedule: Schedule = self.cron.parse()?;
+        let tz: Tz = self
+            .timezone
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid timezone: {e}"))?;
+        Ok(schedule.next_after(after, tz))
+    }
+}
+
+/// Enqueue a job for every schedule that has come due and advance it to its
+/// next occurrence. Missed occurrences (e.g. while the bot was down) collapse
+/// into a single run. The job id is derived from the occurrence, so replicas
+/// racing on the same schedule enqueue it once. Each schedule's upcoming run
+/// is planned on `timer` so the worker wakes for it.
+pub async fn enqueue_due(storage: &Storage, worker_id: &str, timer: &Timer) -> anyhow::Result<()> {
+    let schedules: Vec<(String, ScheduledJob)> = storage.list(COLLECTION).await?;
+
+    let now = Utc::now();
+    for (_, schedule) in schedules {
+        let next = if schedule.next_run_at <= now {
+            advance(storage, &schedule.id).await.unwrap_or_else(|e| {
+                error!(error = %e, id = schedule.id, worker = worker_id, "Failed to run schedule");
+                None
+            })
+        } else {
+            Some(schedule.next_run_at)
+        };
+        if let Some(next) = next {
+            timer.schedule(next);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the schedule's new next run, if this worker advanced it.
+async fn advance(storage: &Storage, id: &str) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let id = id.to_string();
+    let next = storage
+        .run_transaction(move |tx| {
+            let id = id.clone();
+            Box::pin(async move {
+                let schedule: Option<ScheduledJob> = tx.get(COLLECTION, &id).await?;
+                let now = Utc::now();
+                let Some(schedule) = schedule.filter(|s| s.next_run_at <= now) else {
+                    return Ok(None);
+                };
+
+                let job = Job::new(
+                    format!(
+                        "{}-{}",
+                        schedule.id,
+                        schedule.next_run_at.timestamp_millis()
+                    ),
+                    schedule.job_type.clone(),
+                    schedule.payload.clone(),
+                    now,
+                );
+                tx.update(JOBS_COLLECTION, &job.id, &job)?;
+
+                let next_run_at = schedule.next_run_after(now).unwrap_or_else(|e| {
+                    error!(error = %e, id, "Invalid schedule, retiring it");
+                    None
+                });
+                match next_run_at {
+                    Some(next_run_at) => {
+                        let advanced = ScheduledJob {
+                            next_run_at,
+                            ..schedule
+                        };
+                        tx.update(COLLECTION, &id, &advanced)?;
+                        info!(id, %next_run_at, "Scheduled job enqueued");
+                    }
+                    // Expressions that can never match again retire themselves
+                    None => {
+                        tx.delete(COLLECTION, &id)?;
+                    }
+                }
+                Ok(next_run_at)
+            })
+        })
+        .await?;
+    Ok(next)
+}
+
+/// Create or update a schedule. An unchanged schedule keeps its next run time
+/// so restarts do not skip or repeat an occurrence.
+pub async fn upsert(
+    storage: &Storage,
+    id: &str,
+    job_type: JobType,
+    payload: serde_json::Value,
+    schedule: &Schedule,
+    tz: Tz,
+) -> anyhow::Result<()> {
+    let existing: Option<ScheduledJob> = storage.get(COLLECTION, id).await?;
+    let cron = schedule.to_string();
+    let timezone = tz.to_string();
+    if let Some(existing) = &existing
+        && existing.cron == cron
+        && existing.timezone == timezone
+        && existing.job_type == job_type
+        && existing.payload == payload
+    {
+        return Ok(());
+    }
+
+    let next_run_at = schedule
+        .next_after(Utc::now(), tz)
+        .ok_or_else(|| anyhow::anyhow!("Schedule {cron} never runs"))?;
+    let scheduled = ScheduledJob {
+        id: id.to_string(),
+        job_type,
+        payload,
+        cron,
+        timezone,
+        next_run_at,
+    };
+    storage.update(COLLECTION, id, &scheduled).await?;
+    info!(id, cron = scheduled.cron, %next_run_at, "Job scheduled");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_lists_ranges_and_steps() {
+        let schedule: Schedule = "*/15 9-17 * * 1,3,5".parse().unwrap();
+        assert_eq!(
+            schedule.minutes,
+            (1 << 0) | (1 << 15) | (1 << 30) | (1 << 45)
+        );
+        assert!(bit(schedule.hours, 9) && bit(schedule.hours, 17) && !bit(schedule.hours, 18));
+        assert!(bit(schedule.days_of_week, 3) && !bit(schedule.days_of_week, 2));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert_eq!(
+            "* * * *".parse::<Schedule>(),
+            Err(ScheduleError::FieldCount(4))
+        );
+        assert!("60 * * * *".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+        assert!("5-1 * * * *".parse::<Schedule>().is_err());
+        assert!("a * * * *".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn next_run_is_strictly_after() {
+        let schedule: Schedule = "0 * * * *".parse().unwrap();
+        let next = schedule.next_after(utc("2024-03-01T10:00:00Z"), chrono_tz::UTC);
+        assert_eq!(next, Some(utc("2024-03-01T11:00:00Z")));
+    }
+
+    #[test]
+    fn evaluates_in_the_given_timezone() {
+        // Midnight in Los Angeles is 08:00 UTC outside daylight saving
+        let schedule: Schedule = "0 0 * * *".parse().unwrap();
+        let next =
+            schedule.next_after(utc("2024-01-15T12:00:00Z"), chrono_tz::America::Los_Angeles);
+        assert_eq!(next, Some(utc("2024-01-16T08:00:00Z")));
+    }
+
+    #[test]
+    fn skips_local_times_lost_to_dst() {
+        // 02:30 does not exist in Los Angeles on 2024-03-10
+        let schedule: Schedule = "30 2 * * *".parse().unwrap();
+        let next =
+            schedule.next_after(utc("2024-03-10T00:00:00Z"), chrono_tz::America::Los_Angeles);
+        assert_eq!(next, Some(utc("2024-03-11T09:30:00Z")));
+    }
+
+    #[test]
+    fn weekly_schedule_with_sunday_as_seven() {
+        let schedule: Schedule = "0 12 * * 7".parse().unwrap();
+        // 2024-03-01 is a Friday; the next Sunday is the 3rd
+        let next = schedule.next_after(utc("2024-03-01T00:00:00Z"), chrono_tz::UTC);
+        assert_eq!(next, Some(utc("2024-03-03T12:00:00Z")));
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 1st of the month or any Monday
+        let schedule: Schedule = "0 0 1 * 1".parse().unwrap();
+        // 2024-03-01 (Fri) 00:00 has passed; next is Monday the 4th
+        let next = schedule.next_after(utc("2024-03-01T00:00:00Z"), chrono_tz::UTC);
+        assert_eq!(next, Some(utc("2024-03-04T00:00:00Z")));
+    }
+}
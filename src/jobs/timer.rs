@@ -0,0 +1,85 @@
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::Notify;
+
+/// Tracks when the worker loop next needs to run and wakes it early when a
+/// sooner job is enqueued in this process.
+#[derive(Default)]
+pub struct Timer {
+    next: Mutex<Option<DateTime<Utc>>>,
+    notify: Notify,
+}
+
+impl Timer {
+    /// Ask for a pass at `at`, unless one is already planned sooner.
+    pub fn schedule(&self, at: DateTime<Utc>) {
+        let mut next = self.next.lock().expect("job timer poisoned");
+        if next.is_none_or(|n| at < n) {
+            *next = Some(at);
+            self.notify.notify_one();
+        }
+    }
+
+    /// Forget the planned pass; called as a pass begins, since it will see
+    /// every job due so far and plan the next one itself.
+    pub fn clear(&self) {
+        *self.next.lock().expect("job timer poisoned") = None;
+    }
+
+    pub fn next(&self) -> Option<DateTime<Utc>> {
+        *self.next.lock().expect("job timer poisoned")
+    }
+
+    /// Sleep until the planned pass or `deadline`, whichever is sooner,
+    /// re-planning whenever a sooner job is scheduled.
+    pub async fn wait(&self, deadline: DateTime<Utc>) {
+        loop {
+            let wake_at = self.next().map_or(deadline, |n| n.min(deadline));
+            let delay = (wake_at - Utc::now()).to_std().unwrap_or_default();
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => return,
+                _ = self.notify.notified() => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn keeps_the_soonest_pass() {
+        let timer = Timer::default();
+        let now = Utc::now();
+        timer.schedule(now + Duration::seconds(30));
+        timer.schedule(now + Duration::seconds(60));
+        assert_eq!(timer.next(), Some(now + Duration::seconds(30)));
+
+        timer.schedule(now + Duration::seconds(3));
+        assert_eq!(timer.next(), Some(now + Duration::seconds(3)));
+
+        timer.clear();
+        assert_eq!(timer.next(), None);
+    }
+
+    #[tokio::test]
+    async fn wakes_early_for_a_sooner_job() {
+        let timer = Timer::default();
+        let far = Utc::now() + Duration::seconds(60);
+        timer.schedule(far);
+
+        let started = std::time::Instant::now();
+        let waiting = timer.wait(far);
+        tokio::pin!(waiting);
+        tokio::select! {
+            _ = &mut waiting => panic!("woke before anything was due"),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(20)) => {}
+        }
+        timer.schedule(Utc::now() + Duration::milliseconds(20));
+        waiting.await;
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+}
@@ -2,28 +2,39 @@ mod commands;
 mod config;
 mod context;
 mod discord;
+mod error;
 mod firebase;
 mod games;
+mod guilds;
 mod jobs;
 mod roulette;
 mod sardines;
+mod storage;
 mod users;
 mod util;
 
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use config::Config;
+use config::{CommandRegistration, Config, InteractionsMode};
 use context::AppContext;
-use firestore::FirestoreDb;
+use guilds::GuildSettingsStore;
 use jobs::{JobQueue, JobType};
+use storage::Storage;
+use users::leaderboard::MemberCache;
 use users::{UserStore, UserStoreApi};
+use util::random::{RandomSource, SeededRandom, ThreadRandom};
 
 use poise::serenity_prelude as serenity;
 use tokio::sync::RwLock;
 use tracing::{error, info};
 
+use discord::interactions::{InteractionServer, Verifier};
 use discord::types::InteractionType;
+use error::EconomyError;
+
+/// Hourly, on the half hour.
+const STALE_GAME_SWEEP_CRON: &str = "30 * * * *";
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -39,38 +50,19 @@ async fn main() -> anyhow::Result<()> {
     let config = Config::load()?;
     info!("Starting bot");
 
-    // Initialize Firestore
-    let db = firebase::client::create_firestore_db(
-        &config.firebase.project_id,
-        &config.firebase.cert_base64,
-    )
-    .await?;
+    let storage = Storage::connect(&config.storage).await?;
 
-    let guild_id = serenity::GuildId::new(config.discord.server_id.parse()?);
     let token = config.discord.bot_token.clone();
 
     // Create job queue before framework so we can stop it on shutdown
-    let job_queue = Arc::new(RwLock::new(JobQueue::new(db.clone())));
+    let job_queue = Arc::new(RwLock::new(JobQueue::new(storage.clone())));
     let shutdown_job_queue = job_queue.clone();
 
     let framework = poise::Framework::builder()
-        .options(poise::FrameworkOptions {
-            commands: commands::all(),
-            pre_command: |ctx| {
-                Box::pin(async move {
-                    info!(
-                        command = ctx.command().name,
-                        user = ctx.author().name,
-                        "Command invoked"
-                    );
-                })
-            },
-            event_handler: |ctx, event, _framework, data| Box::pin(event_handler(ctx, event, data)),
-            ..Default::default()
-        })
-        .setup(move |ctx, _ready, framework| {
+        .options(framework_options())
+        .setup(move |ctx, ready, framework| {
             Box::pin(build_app_context(
-                ctx, framework, guild_id, config, db, job_queue,
+                ctx, ready, framework, config, storage, job_queue,
             ))
         })
         .build();
@@ -92,26 +84,58 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Framework options shared by the gateway client and the HTTP interactions endpoint.
+fn framework_options() -> poise::FrameworkOptions<AppContext, anyhow::Error> {
+    poise::FrameworkOptions {
+        commands: commands::all(),
+        pre_command: |ctx| {
+            Box::pin(async move {
+                info!(
+                    command = ctx.command().name,
+                    user = ctx.author().name,
+                    "Command invoked"
+                );
+            })
+        },
+        on_error: |error| Box::pin(on_error(error)),
+        event_handler: |ctx, event, framework, data| {
+            Box::pin(event_handler(ctx, event, framework, data))
+        },
+        ..Default::default()
+    }
+}
+
 async fn build_app_context(
     ctx: &serenity::Context,
+    ready: &serenity::Ready,
     framework: &poise::Framework<AppContext, anyhow::Error>,
-    guild_id: serenity::GuildId,
     config: Config,
-    db: FirestoreDb,
+    storage: Storage,
     job_queue: Arc<RwLock<JobQueue>>,
 ) -> anyhow::Result<AppContext> {
     info!("Successfully connected to gateway");
-    poise::builtins::register_in_guild(ctx, &framework.options().commands, guild_id).await?;
-    info!("Commands registered");
+    // Per-guild registration happens as each guild becomes available in event_handler
+    if config.discord.command_registration == CommandRegistration::Global {
+        poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+        info!("Commands registered globally");
+    }
 
-    let user_store: Arc<dyn UserStoreApi> = Arc::new(UserStore::new(db.clone()));
+    let user_store: Arc<dyn UserStoreApi> = Arc::new(UserStore::new(storage.clone()));
+    let guild_settings = Arc::new(GuildSettingsStore::new(storage.clone()));
+    let random: Arc<dyn RandomSource> = match config.replay_seed {
+        Some(seed) => Arc::new(SeededRandom::new(seed)),
+        None => Arc::new(ThreadRandom),
+    };
     let app_context = AppContext {
         config,
-        db,
+        storage,
         http: ctx.http.clone(),
         user_store,
+        guild_settings,
         job_queue,
         game_locks: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        member_cache: MemberCache::default(),
+        random,
     };
 
     // Register job handlers and start polling
@@ -122,25 +146,106 @@ async fn build_app_context(
             .handler(JobType::RouletteClose, roulette::command::close_roulette)
             .handler(JobType::RouletteFinish, roulette::command::finish_roulette)
             .handler(JobType::SardinesFinish, sardines::command::finish_sardines)
+            .handler(JobType::StaleGameSweep, games::sweep::sweep_stale_games)
             .apply()
             .await;
+        queue
+            .schedule(
+                "stale-game-sweep",
+                JobType::StaleGameSweep,
+                &(),
+                STALE_GAME_SWEEP_CRON,
+                app_context.config.discord.timezone,
+            )
+            .await?;
         queue.start(app_context.config.job_queue_poll_interval_ms);
     }
 
     // Recover any pending roulette countdowns
     roulette::command::recover_countdowns(&app_context).await;
 
-    // Recover orphaned sardines games
-    sardines::command::recover_sardines(&app_context).await;
+    // Recover orphaned roulette and sardines games
+    games::sweep::recover_orphaned_games(&app_context).await;
+
+    if app_context.config.discord.interactions == InteractionsMode::Http {
+        let server = Arc::new(InteractionServer {
+            verifier: Verifier::new(&app_context.config.discord.public_key)?,
+            serenity_ctx: ctx.clone(),
+            options: framework_options(),
+            data: app_context.clone(),
+            shard_manager: framework.shard_manager().clone(),
+            bot_id: ready.user.id,
+        });
+        let port = app_context.config.port;
+        tokio::spawn(async move {
+            if let Err(e) = discord::interactions::serve(server, port).await {
+                error!(error = %e, "Interactions endpoint stopped");
+            }
+        });
+    }
 
     Ok(app_context)
 }
 
+/// Render user-caused economy errors as ephemeral replies; log everything else as a bug.
+async fn on_error(error: poise::FrameworkError<'_, AppContext, anyhow::Error>) {
+    match error {
+        poise::FrameworkError::Command { error, ctx, .. } => {
+            let content = match error.downcast_ref::<EconomyError>() {
+                Some(e) if e.is_user_error() => e.to_string(),
+                _ => {
+                    error!(
+                        command = ctx.command().name,
+                        error = ?error,
+                        "Command failed"
+                    );
+                    "Something went wrong, please try again later".to_string()
+                }
+            };
+            if let Err(e) = ctx
+                .send(
+                    poise::CreateReply::default()
+                        .content(content)
+                        .ephemeral(true),
+                )
+                .await
+            {
+                error!(error = %e, "Failed to send error reply");
+            }
+        }
+        poise::FrameworkError::CommandCheckFailed { ctx, .. } => {
+            if let Err(e) = ctx
+                .send(
+                    poise::CreateReply::default()
+                        .content("You do not have permission to use this command")
+                        .ephemeral(true),
+                )
+                .await
+            {
+                error!(error = %e, "Failed to send error reply");
+            }
+        }
+        other => {
+            if let Err(e) = poise::builtins::on_error(other).await {
+                error!(error = %e, "Error while handling error");
+            }
+        }
+    }
+}
+
 async fn event_handler(
     ctx: &serenity::Context,
     event: &serenity::FullEvent,
+    framework: poise::FrameworkContext<'_, AppContext, anyhow::Error>,
     data: &AppContext,
 ) -> Result<(), anyhow::Error> {
+    if let serenity::FullEvent::GuildCreate { guild, .. } = event
+        && data.config.discord.command_registration == CommandRegistration::Guild
+    {
+        poise::builtins::register_in_guild(ctx, &framework.options().commands, guild.id).await?;
+        info!(guild_id = %guild.id, guild = guild.name, "Commands registered in guild");
+    }
+
     if let serenity::FullEvent::InteractionCreate {
         interaction: serenity::Interaction::Component(component),
     } = event
@@ -150,7 +255,13 @@ async fn event_handler(
 
         match id_type.parse::<InteractionType>() {
             Ok(InteractionType::Debug) => {
-                discord::debug::handle_debug_button(ctx, component).await?
+                discord::debug::handle_debug_button(ctx, component, data).await?
+            }
+            Ok(InteractionType::RepHistory) => {
+                users::rep::handle_history_page(ctx, component, data).await?
+            }
+            Ok(InteractionType::RepLeaderboard) => {
+                users::rep::handle_leaderboard_page(ctx, component, data).await?
             }
             Ok(InteractionType::Roulette) => {
                 roulette::command::handle_roulette_join(ctx, component, data).await?
@@ -1,7 +1,6 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
-use chrono::DateTime;
-use firestore::FirestoreDb;
 use poise::serenity_prelude as serenity;
 use serenity::{
     CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage,
@@ -12,10 +11,13 @@ use tracing::{error, info};
 use crate::context::{Context, GameLocks, get_game_lock, remove_game_lock};
 use crate::discord::helpers::{encode_custom_id, parse_custom_id, rep_label};
 use crate::discord::types::{GuildMember, InteractionType};
+use crate::error::{EconomyError, EconomyResult};
+use crate::games::sweep::ORPHAN_GRACE_SECONDS;
 use crate::jobs::JobType;
-use crate::roulette::game::{
-    ROULETTE_FINISH_DELAY_SECONDS, ROULETTE_TIME_MS, Roulette, RouletteJobPayload,
-};
+use crate::roulette::game::{ROULETTE_FINISH_DELAY_SECONDS, Roulette, RouletteJobPayload};
+use crate::roulette::store::RouletteStore;
+use crate::storage::Storage;
+use crate::users::admin::ensure_not_frozen;
 const COUNTDOWN_INTERVAL_MS: u64 = 5000;
 
 /// Start a game of roulette
@@ -37,40 +39,25 @@ pub async fn roulette(
         GuildMember::from_serenity(guild_id, author, member.joined_at, member.nick.as_deref());
 
     let data = ctx.data();
+    let settings = data.settings(guild_id).await?;
+    ensure_not_frozen(data.user_store.as_ref(), &guild_member).await?;
     let member_rep = data.user_store.get_user_rep(&guild_member).await?;
 
-    let mut roulette = match Roulette::init(data.db.clone(), &guild_member, bet) {
-        Ok(r) => r,
-        Err(e) => {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(e.to_string())
-                    .ephemeral(true),
-            )
-            .await?;
-            return Ok(());
-        }
-    };
+    let mut roulette = Roulette::init(
+        data.storage.clone(),
+        &guild_member,
+        bet,
+        data.random.as_ref(),
+    )?;
 
     if member_rep < roulette.buy_in() {
-        let username = &guild_member.username;
-        let buy_in_label = rep_label(roulette.buy_in(), false);
-        ctx.send(
-            poise::CreateReply::default()
-                .content(format!(
-                    "{username} only has {member_rep} and cannot bet in a roulette game whose buy-in is {buy_in_label}"
-                ))
-                .ephemeral(true),
-        )
-        .await?;
-        return Ok(());
+        return Err(EconomyError::InsufficientFunds {
+            balance: member_rep,
+            amount: roulette.buy_in(),
+        }
+        .into());
     }
 
-    // Deduct creator's bet
-    data.user_store
-        .increment_user_rep(&guild_member, -roulette.bet())
-        .await?;
-
     // Get the interaction token for message updates
     let interaction_token = match &ctx {
         poise::Context::Application(app_ctx) => app_ctx.interaction.token.clone(),
@@ -78,7 +65,14 @@ pub async fn roulette(
     };
 
     let job_queue = data.job_queue.read().await;
-    roulette.start(&interaction_token, &job_queue).await?;
+    roulette
+        .start(
+            &interaction_token,
+            &job_queue,
+            settings.roulette_time_seconds,
+            data.user_store.as_ref(),
+        )
+        .await?;
 
     // Send the initial message
     let (content, button) = roulette_message_parts(&roulette);
@@ -93,10 +87,10 @@ pub async fn roulette(
     // Start countdown
     start_countdown(
         roulette.id().to_string(),
-        roulette.start_time().cloned().unwrap_or_default(),
+        roulette.end_time_ms(),
         interaction_token,
         data.http.clone(),
-        data.db.clone(),
+        data.storage.clone(),
         data.game_locks.clone(),
     );
 
@@ -131,35 +125,25 @@ pub async fn handle_roulette_join(
     let game_lock = get_game_lock(&data.game_locks, game_id);
     let _guard = game_lock.write().await;
 
-    let mut game = match Roulette::load(data.db.clone(), game_id).await {
-        Ok(r) => r,
-        Err(_) => {
-            interaction
-                .create_response(ctx, CreateInteractionResponse::Acknowledge)
-                .await?;
+    let loaded: EconomyResult<Roulette> = async {
+        let game = Roulette::load(data.storage.clone(), game_id).await?;
+        validate_roulette_join(&game, &guild_member, data).await?;
+        Ok(game)
+    }
+    .await;
+    let mut game = match loaded {
+        Ok(game) => game,
+        Err(e) if e.is_user_error() => {
+            respond_ephemeral(ctx, interaction, e.to_string()).await?;
             return Ok(());
         }
+        Err(e) => return Err(e.into()),
     };
 
-    if game.is_closed() {
-        interaction
-            .create_response(ctx, CreateInteractionResponse::Acknowledge)
-            .await?;
-        return Ok(());
-    }
-
-    if let Some(error_msg) = validate_roulette_join(&game, &guild_member, data).await? {
-        respond_ephemeral(ctx, interaction, error_msg).await?;
-        return Ok(());
-    }
-
-    // Deduct bet
-    data.user_store
-        .increment_user_rep(&guild_member, -game.bet())
+    // Deduct bet and join
+    game.add_player(&guild_member, data.user_store.as_ref())
         .await?;
 
-    game.add_player(&guild_member).await?;
-
     // Update the message with new player list
     let (content, button) = roulette_message_parts(&game);
     let row = CreateActionRow::Buttons(vec![button]);
@@ -177,24 +161,36 @@ pub async fn handle_roulette_join(
     Ok(())
 }
 
-/// Validate whether a player can join a roulette game. Returns an error message if invalid.
+/// Validate whether a player can join a roulette game.
 async fn validate_roulette_join(
     game: &Roulette,
     guild_member: &GuildMember,
     data: &crate::context::AppContext,
-) -> Result<Option<String>, anyhow::Error> {
+) -> EconomyResult<()> {
+    // Never let a game leak into another guild's economy
+    if game.creator().guild_id != guild_member.guild_id {
+        return Err(EconomyError::GameNotFound);
+    }
+
+    if game.is_closed() {
+        return Err(EconomyError::GameClosed);
+    }
+
     if game.players().iter().any(|p| p.id == guild_member.id) {
-        return Ok(Some(
-            "Cannot join a roulette game you are already in".to_string(),
-        ));
+        return Err(EconomyError::AlreadyJoined);
     }
 
+    ensure_not_frozen(data.user_store.as_ref(), guild_member).await?;
+
     let member_rep = data.user_store.get_user_rep(guild_member).await?;
     if member_rep < game.buy_in() {
-        return Ok(Some("You do not have enough rep".to_string()));
+        return Err(EconomyError::InsufficientFunds {
+            balance: member_rep,
+            amount: game.buy_in(),
+        });
     }
 
-    Ok(None)
+    Ok(())
 }
 
 /// Send an ephemeral error response to an interaction.
@@ -224,11 +220,18 @@ pub async fn close_roulette(
     let game_lock = get_game_lock(&ctx.game_locks, &payload.id);
     let _guard = game_lock.write().await;
 
-    let mut game = match Roulette::load(ctx.db.clone(), &payload.id).await {
+    let mut game = match Roulette::load(ctx.storage.clone(), &payload.id).await {
         Ok(g) => g,
+        Err(EconomyError::GameNotFound) => {
+            info!(
+                id = payload.id,
+                "Roulette game not found for close (likely already ended)"
+            );
+            return Ok(());
+        }
         Err(e) => {
             error!(error = %e, id = payload.id, "Failed to load roulette game for close");
-            return Err(e);
+            return Err(e.into());
         }
     };
 
@@ -261,6 +264,8 @@ pub async fn close_roulette(
             JobType::RouletteFinish,
             &payload,
             ROULETTE_FINISH_DELAY_SECONDS,
+            Some(&payload.id),
+            Some(&game.creator().guild_id),
         )
         .await?;
 
@@ -288,12 +293,19 @@ async fn do_finish_roulette(
     ctx: &crate::context::AppContext,
     payload: &RouletteJobPayload,
 ) -> anyhow::Result<()> {
-    let game = match Roulette::load(ctx.db.clone(), &payload.id).await {
+    let game = match Roulette::load(ctx.storage.clone(), &payload.id).await {
         Ok(g) => g,
+        Err(EconomyError::GameNotFound) => {
+            // An earlier attempt already paid out and removed the game
+            info!(
+                id = payload.id,
+                "Roulette game not found for finish (likely already ended)"
+            );
+            return Ok(());
+        }
         Err(e) => {
             error!(error = %e, id = payload.id, "Failed to load roulette game for finish");
-            // Game document may not exist or is corrupted — nothing to clean up
-            return Err(e);
+            return Err(e.into());
         }
     };
 
@@ -303,15 +315,12 @@ async fn do_finish_roulette(
         "Finishing roulette game"
     );
 
-    let final_message = match game.finish(ctx.user_store.as_ref()).await {
+    let final_message = match game.finish(ctx.random.as_ref()).await {
         Ok(msg) => msg,
         Err(e) => {
-            error!(error = %e, id = payload.id, "Roulette finish failed, cleaning up game");
-            // finish() failed partway — ensure the game document is removed
-            if let Err(del_err) = game.force_cleanup().await {
-                error!(error = %del_err, id = payload.id, "Failed to clean up roulette game after error");
-            }
-            return Err(e);
+            // Keep the game so the job queue's retry can still pay out
+            error!(error = %e, id = payload.id, "Roulette finish failed");
+            return Err(e.into());
         }
     };
 
@@ -360,16 +369,16 @@ pub async fn recover_countdowns(ctx: &crate::context::AppContext) {
             }
         };
 
-        match Roulette::load(ctx.db.clone(), &payload.id).await {
+        match Roulette::load(ctx.storage.clone(), &payload.id).await {
             Ok(game) => {
-                if let Some(start_time) = game.start_time() {
+                if game.start_time().is_some() {
                     info!(id = payload.id, "Recovering countdown for roulette");
                     start_countdown(
                         game.id().to_string(),
-                        start_time.clone(),
+                        game.end_time_ms(),
                         payload.interaction_token,
                         ctx.http.clone(),
-                        ctx.db.clone(),
+                        ctx.storage.clone(),
                         ctx.game_locks.clone(),
                     );
                 }
@@ -381,21 +390,89 @@ pub async fn recover_countdowns(ctx: &crate::context::AppContext) {
     }
 }
 
+/// Finish roulette games with no pending or running close/finish job, such as
+/// one whose job was dead-lettered. Games still inside their join window, or
+/// that closed moments ago, are left alone.
+pub async fn recover_roulettes(ctx: &crate::context::AppContext) {
+    let store = RouletteStore::new(ctx.storage.clone());
+    let all_games = match store.list_all().await {
+        Ok(games) => games,
+        Err(e) => {
+            error!(error = %e, "Failed to list roulette games for recovery");
+            return;
+        }
+    };
+
+    if all_games.is_empty() {
+        return;
+    }
+
+    let active_jobs = {
+        let queue = ctx.job_queue.read().await;
+        match queue.get_active_jobs().await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                error!(error = %e, "Failed to get active jobs for roulette recovery");
+                return;
+            }
+        }
+    };
+
+    let roulette_job_game_ids: HashSet<String> = active_jobs
+        .iter()
+        .filter(|j| matches!(j.job_type, JobType::RouletteClose | JobType::RouletteFinish))
+        .filter_map(|j| j.target_id().map(str::to_string))
+        .collect();
+
+    let grace_cutoff_ms = chrono::Utc::now().timestamp_millis() - ORPHAN_GRACE_SECONDS * 1000;
+    for game in all_games {
+        if roulette_job_game_ids.contains(&game.id) {
+            continue; // Job exists, it will handle this game
+        }
+        let game_lock = get_game_lock(&ctx.game_locks, &game.id);
+        let guard = game_lock.write().await;
+
+        if let Err(e) = finish_orphaned_roulette(ctx, &game.id, grace_cutoff_ms).await {
+            error!(error = %e, id = game.id, "Failed to finish orphaned roulette game");
+        }
+
+        drop(guard);
+        remove_game_lock(&ctx.game_locks, &game.id);
+    }
+}
+
+async fn finish_orphaned_roulette(
+    ctx: &crate::context::AppContext,
+    id: &str,
+    grace_cutoff_ms: i64,
+) -> EconomyResult<()> {
+    // Reload under the lock so joins made since the listing are included
+    let game = match Roulette::load(ctx.storage.clone(), id).await {
+        Ok(g) => g,
+        Err(EconomyError::GameNotFound) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if game.end_time_ms() > grace_cutoff_ms {
+        return Ok(());
+    }
+
+    info!(
+        id,
+        players = game.players().len(),
+        "Finishing orphaned roulette game (no active job)"
+    );
+    game.finish(ctx.random.as_ref()).await?;
+    Ok(())
+}
+
 fn start_countdown(
     game_id: String,
-    start_time_str: String,
+    end_time_ms: i64,
     interaction_token: String,
     http: Arc<serenity::Http>,
-    db: FirestoreDb,
+    storage: Storage,
     game_locks: GameLocks,
 ) {
-    let end_time_ms = {
-        let start = DateTime::parse_from_rfc3339(&start_time_str)
-            .map(|dt| dt.timestamp_millis())
-            .unwrap_or_else(|_| chrono::Utc::now().timestamp_millis());
-        start + ROULETTE_TIME_MS as i64
-    };
-
     tokio::spawn(async move {
         tokio::time::sleep(tokio::time::Duration::from_millis(COUNTDOWN_INTERVAL_MS)).await;
 
@@ -412,9 +489,9 @@ fn start_countdown(
             let game_lock = get_game_lock(&game_locks, &game_id);
             let guard = game_lock.read().await;
 
-            // Reload game from Firestore each tick to get the current player list.
+            // Reload game from storage each tick to get the current player list.
             // If the game was already finished/deleted or closed, stop the countdown.
-            let game = match Roulette::load(db.clone(), &game_id).await {
+            let game = match Roulette::load(storage.clone(), &game_id).await {
                 Ok(g) => g,
                 Err(_) => break,
             };
@@ -428,6 +505,7 @@ fn start_countdown(
                 game.bet(),
                 remaining_secs,
                 game.players(),
+                &game.seed_hash(),
             );
             let button = CreateButton::new(encode_custom_id(InteractionType::Roulette, &game_id))
                 .label("Join Roulette");
@@ -453,11 +531,7 @@ fn start_countdown(
 }
 
 fn roulette_message_parts(game: &Roulette) -> (String, CreateButton) {
-    let start_time_str = game.start_time().expect("roulette must have start time");
-    let start_ms = DateTime::parse_from_rfc3339(start_time_str)
-        .map(|dt| dt.timestamp_millis())
-        .unwrap_or_else(|_| chrono::Utc::now().timestamp_millis());
-    let end_ms = start_ms + ROULETTE_TIME_MS as i64;
+    let end_ms = game.end_time_ms();
     let now_ms = chrono::Utc::now().timestamp_millis();
     let remaining = (end_ms - now_ms) / 1000;
 
@@ -466,6 +540,7 @@ fn roulette_message_parts(game: &Roulette) -> (String, CreateButton) {
         game.bet(),
         remaining,
         game.players(),
+        &game.seed_hash(),
     );
     let button = CreateButton::new(encode_custom_id(InteractionType::Roulette, game.id()))
         .label("Join Roulette");
@@ -477,10 +552,11 @@ fn build_roulette_content(
     bet: i64,
     remaining_secs: i64,
     players: &[crate::games::lottery::DbPlayer],
+    seed_hash: &str,
 ) -> String {
     let bet_label = rep_label(bet, false);
     let banner = format!(
-        "{creator_name} has started a roulette game for {bet_label}. Click the button below within {remaining_secs} seconds to place an equal bet and join the game."
+        "{creator_name} has started a roulette game for {bet_label}. Click the button below within {remaining_secs} seconds to place an equal bet and join the game.\n-# Seed hash: `{seed_hash}`"
     );
 
     if players.len() < 2 {
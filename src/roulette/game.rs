@@ -1,15 +1,20 @@
-use firestore::FirestoreDb;
 use serde::{Deserialize, Serialize};
+use tracing::error;
 
 use crate::discord::helpers::rep_label;
 use crate::discord::types::GuildMember;
-use crate::games::lottery::{DbPlayer, Lottery};
+use crate::error::{EconomyError, EconomyResult};
+use crate::games::fair::{GameKind, Reveal};
+use crate::games::lottery::{DbPlayer, Lottery, Settlement};
 use crate::jobs::JobType;
 use crate::roulette::store::{RouletteLottery, RouletteStore};
-use crate::users::UserStoreApi;
+use crate::storage::Storage;
+use crate::users::{RepChange, RepReason, UserStoreApi};
+use crate::util::dates::seconds_from_now;
+use crate::util::random::RandomSource;
 
+/// Default join window; guilds can override it with `/config`.
 pub const ROULETTE_TIME_SECONDS: u64 = 30;
-pub const ROULETTE_TIME_MS: u64 = ROULETTE_TIME_SECONDS * 1000;
 pub const ROULETTE_FINISH_DELAY_SECONDS: u64 = 3;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,42 +29,79 @@ pub struct Roulette {
 }
 
 impl Roulette {
-    pub fn init(db: FirestoreDb, creator: &GuildMember, bet: i64) -> anyhow::Result<Self> {
+    pub fn init(
+        storage: Storage,
+        creator: &GuildMember,
+        bet: i64,
+        random: &dyn RandomSource,
+    ) -> EconomyResult<Self> {
         let stored_creator = DbPlayer::from(creator);
-        let mut lottery = Lottery::new(stored_creator.clone(), bet)?;
+        let mut lottery = Lottery::new(stored_creator.clone(), bet, random)?;
         lottery.add_player(stored_creator);
         Ok(Self {
             lottery,
-            store: RouletteStore::new(db),
+            store: RouletteStore::new(storage),
         })
     }
 
-    pub async fn load(db: FirestoreDb, id: &str) -> anyhow::Result<Self> {
-        let store = RouletteStore::new(db);
-        let lottery = store
-            .get(id)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Roulette lottery not found"))?;
+    pub async fn load(storage: Storage, id: &str) -> EconomyResult<Self> {
+        let store = RouletteStore::new(storage);
+        let lottery = store.get(id).await?.ok_or(EconomyError::GameNotFound)?;
         Ok(Self { lottery, store })
     }
 
+    /// Deduct the creator's bet, save the game and schedule its close. The bet
+    /// is refunded if the game cannot be saved or scheduled.
     pub async fn start(
         &mut self,
         interaction_token: &str,
         job_queue: &crate::jobs::JobQueue,
+        duration_seconds: u64,
+        user_store: &dyn UserStoreApi,
     ) -> anyhow::Result<String> {
+        let end_time = seconds_from_now(duration_seconds).ok_or_else(|| {
+            anyhow::anyhow!("Roulette duration of {duration_seconds}s is out of range")
+        })?;
         let start_time = self.lottery.start();
+        self.lottery.end_time = Some(end_time.to_rfc3339());
 
-        self.store.put(&self.lottery).await?;
+        let creator = GuildMember::from(&self.lottery.creator);
+        user_store
+            .increment_user_rep(
+                &creator,
+                -self.lottery.bet,
+                &RepChange::game(RepReason::RouletteBuyIn, &self.lottery.id),
+            )
+            .await?;
+
+        if let Err(e) = self.store.put(&self.lottery).await {
+            self.refund(&creator, user_store).await;
+            return Err(e);
+        }
 
         let payload = RouletteJobPayload {
             id: self.lottery.id.clone(),
             interaction_token: interaction_token.to_string(),
         };
 
-        job_queue
-            .enqueue(JobType::RouletteClose, &payload, ROULETTE_TIME_SECONDS)
-            .await?;
+        if let Err(e) = job_queue
+            .enqueue(
+                JobType::RouletteClose,
+                &payload,
+                duration_seconds,
+                Some(&self.lottery.id),
+                Some(&self.lottery.creator.guild_id),
+            )
+            .await
+        {
+            // Without a close job nothing would ever pay out this game
+            if let Err(delete_err) = self.store.delete(&self.lottery.id).await {
+                error!(error = %delete_err, id = self.lottery.id, "Failed to delete unscheduled roulette game");
+                return Err(e);
+            }
+            self.refund(&creator, user_store).await;
+            return Err(e);
+        }
 
         Ok(start_time)
     }
@@ -84,10 +126,32 @@ impl Roulette {
         &self.lottery.players
     }
 
+    pub fn seed_hash(&self) -> String {
+        self.lottery.seed_hash()
+    }
+
     pub fn start_time(&self) -> Option<&String> {
         self.lottery.start_time.as_ref()
     }
 
+    /// When the join window closes, in epoch milliseconds. Games saved before
+    /// durations were configurable fall back to the default window.
+    pub fn end_time_ms(&self) -> i64 {
+        let parse = |t: &String| {
+            chrono::DateTime::parse_from_rfc3339(t)
+                .ok()
+                .map(|dt| dt.timestamp_millis())
+        };
+        if let Some(end) = self.lottery.end_time.as_ref().and_then(parse) {
+            return end;
+        }
+        let start = self
+            .start_time()
+            .and_then(parse)
+            .unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+        start + (ROULETTE_TIME_SECONDS * 1000) as i64
+    }
+
     pub fn is_closed(&self) -> bool {
         self.lottery.is_closed()
     }
@@ -97,20 +161,56 @@ impl Roulette {
         self.store.update(&self.lottery).await
     }
 
-    pub async fn add_player(&mut self, player: &GuildMember) -> anyhow::Result<()> {
+    /// Deduct the joiner's bet and add them to the game, refunding the bet if
+    /// the player list cannot be saved.
+    pub async fn add_player(
+        &mut self,
+        player: &GuildMember,
+        user_store: &dyn UserStoreApi,
+    ) -> anyhow::Result<()> {
+        user_store
+            .increment_user_rep(
+                player,
+                -self.lottery.bet,
+                &RepChange::game(RepReason::RouletteBuyIn, &self.lottery.id),
+            )
+            .await?;
+
         let stored = DbPlayer::from(player);
-        let updated_players = self.store.add_player(&self.lottery.id, &stored).await?;
-        self.lottery.players = updated_players;
-        Ok(())
+        match self.store.add_player(&self.lottery.id, &stored).await {
+            Ok(updated_players) => {
+                self.lottery.players = updated_players;
+                Ok(())
+            }
+            Err(e) => {
+                self.refund(player, user_store).await;
+                Err(e)
+            }
+        }
     }
 
-    /// Delete the game document without processing results. Used for cleanup on failure.
-    pub async fn force_cleanup(&self) -> anyhow::Result<()> {
-        self.store.delete(&self.lottery.id).await
+    /// Best-effort refund of a bet taken for a write that then failed.
+    async fn refund(&self, player: &GuildMember, user_store: &dyn UserStoreApi) {
+        let change = RepChange::game(RepReason::RouletteRefund, &self.lottery.id);
+        if let Err(e) = user_store
+            .increment_user_rep(player, self.lottery.bet, &change)
+            .await
+        {
+            error!(error = %e, id = self.lottery.id, "Failed to refund roulette bet");
+        }
     }
 
-    pub async fn finish(&self, user_store: &dyn UserStoreApi) -> anyhow::Result<String> {
-        if !self.lottery.can_finish() {
+    /// Pay the winner, or refund everyone if too few joined. The payout and
+    /// the game's delete commit together, so a retried finish cannot pay twice.
+    pub async fn finish(&self, random: &dyn RandomSource) -> EconomyResult<String> {
+        let winner = self
+            .lottery
+            .can_finish()
+            .then(|| self.lottery.finish(random).winner);
+        let seed = &self.lottery.server_seed;
+        let id = &self.lottery.id;
+
+        let Some(winner) = winner else {
             // Refund all players since the game didn't happen
             let refunds: Vec<(GuildMember, i64)> = self
                 .lottery
@@ -118,16 +218,15 @@ impl Roulette {
                 .iter()
                 .map(|p| (GuildMember::from(p), self.lottery.bet))
                 .collect();
-            user_store.increment_user_reps(&refunds).await?;
+            self.settle(refunds, RepReason::RouletteRefund, None)
+                .await?;
 
-            self.store.delete(&self.lottery.id).await?;
             let creator_name = &self.lottery.creator.username;
             return Ok(format!(
-                "{creator_name}'s roulette game was cancelled, not enough players joined."
+                "{creator_name}'s roulette game was cancelled, not enough players joined.\n-# Seed: `{seed}` · `/verify {id}`"
             ));
-        }
+        };
 
-        let result = self.lottery.finish();
         let names: Vec<&str> = self
             .lottery
             .players
@@ -136,19 +235,45 @@ impl Roulette {
             .collect();
 
         // Players already paid at join time, so only credit the winner the full pot
-        let winner_member = GuildMember::from(&result.winner);
-        user_store
-            .increment_user_rep(&winner_member, self.lottery.pot_size())
+        let payout = vec![(GuildMember::from(&winner), self.lottery.pot_size())];
+        self.settle(payout, RepReason::RouletteWin, Some(&winner))
             .await?;
 
-        self.store.delete(&self.lottery.id).await?;
-
         let bet_label = rep_label(self.bet(), false);
         let pot_label = rep_label(self.lottery.pot_size(), false);
         let player_names = names.join(", ");
-        let winner_name = &result.winner.username;
+        let winner_name = &winner.username;
         Ok(format!(
-            "The roulette game has ended. {player_names} all bet {bet_label}. {winner_name} won {pot_label}"
+            "The roulette game has ended. {player_names} all bet {bet_label}. {winner_name} won {pot_label}\n-# Seed: `{seed}` · `/verify {id}`"
         ))
     }
+
+    async fn settle(
+        &self,
+        payouts: Vec<(GuildMember, i64)>,
+        reason: RepReason,
+        winner: Option<&DbPlayer>,
+    ) -> EconomyResult<()> {
+        let settlement = Settlement {
+            payouts,
+            change: RepChange::game(reason, &self.lottery.id),
+            reveal: self.reveal(winner),
+        };
+        if !self.store.settle(&self.lottery.id, settlement).await? {
+            return Err(EconomyError::GameNotFound);
+        }
+        Ok(())
+    }
+
+    fn reveal(&self, winner: Option<&DbPlayer>) -> Reveal {
+        Reveal {
+            game_id: self.lottery.id.clone(),
+            kind: GameKind::Roulette,
+            guild_id: self.lottery.creator.guild_id.clone(),
+            server_seed: self.lottery.server_seed.clone(),
+            players: self.lottery.players.clone(),
+            winner_id: winner.map(|w| w.id.clone()),
+            revealed_at: chrono::Utc::now(),
+        }
+    }
 }
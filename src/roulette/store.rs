@@ -1,19 +1,18 @@
-use crate::firebase::FirestoreStore;
-use crate::games::lottery::{DbPlayer, Lottery};
-use firestore::*;
+use crate::games::lottery::{self, DbPlayer, Lottery, Settlement};
+use crate::storage::{Collection, Storage};
 
 const COLLECTION: &str = "roulettes";
 
 pub type RouletteLottery = Lottery<DbPlayer>;
 
 pub struct RouletteStore {
-    store: FirestoreStore,
+    store: Collection,
 }
 
 impl RouletteStore {
-    pub fn new(db: FirestoreDb) -> Self {
+    pub fn new(storage: Storage) -> Self {
         Self {
-            store: FirestoreStore::new(db, COLLECTION),
+            store: Collection::new(storage, COLLECTION),
         }
     }
 
@@ -21,6 +20,10 @@ impl RouletteStore {
         self.store.get(id).await
     }
 
+    pub async fn list_all(&self) -> anyhow::Result<Vec<RouletteLottery>> {
+        self.store.list_all().await
+    }
+
     pub async fn put(&self, lottery: &RouletteLottery) -> anyhow::Result<()> {
         self.store.put(&lottery.id, lottery).await
     }
@@ -33,37 +36,31 @@ impl RouletteStore {
         self.store.delete(id).await
     }
 
-    /// Atomically add a player to the lottery inside a Firestore transaction.
+    /// Pay out and delete a finished game; see [`lottery::settle`].
+    pub async fn settle(&self, id: &str, settlement: Settlement) -> anyhow::Result<bool> {
+        lottery::settle(self.store.storage(), COLLECTION, id, settlement).await
+    }
+
+    /// Atomically add a player to the lottery inside a transaction.
     /// Returns the updated player list. Skips the add if the player already exists.
     pub async fn add_player(&self, id: &str, player: &DbPlayer) -> anyhow::Result<Vec<DbPlayer>> {
         let id = id.to_string();
         let player = player.clone();
         let result: Option<Vec<DbPlayer>> = self
             .store
-            .db()
-            .run_transaction(|db, tx| {
+            .storage()
+            .run_transaction(move |tx| {
                 let id = id.clone();
                 let player = player.clone();
                 Box::pin(async move {
-                    let lottery: Option<RouletteLottery> = db
-                        .fluent()
-                        .select()
-                        .by_id_in(COLLECTION)
-                        .obj()
-                        .one(&id)
-                        .await?;
+                    let lottery: Option<RouletteLottery> = tx.get(COLLECTION, &id).await?;
 
                     if let Some(mut lottery) = lottery {
                         if !lottery.players.iter().any(|p| p.id == player.id) {
                             lottery.players.push(player);
                         }
                         let players = lottery.players.clone();
-                        db.fluent()
-                            .update()
-                            .in_col(COLLECTION)
-                            .document_id(&id)
-                            .object(&lottery)
-                            .add_to_transaction(tx)?;
+                        tx.update(COLLECTION, &id, &lottery)?;
                         Ok(Some(players))
                     } else {
                         Ok(None)
@@ -74,3 +71,54 @@ impl RouletteStore {
         result.ok_or_else(|| anyhow::anyhow!("Roulette lottery not found"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::emulator;
+    use crate::util::random::ThreadRandom;
+
+    fn player(id: &str) -> DbPlayer {
+        DbPlayer {
+            id: id.to_string(),
+            guild_id: "guild".to_string(),
+            username: format!("user-{id}"),
+            joined_at: None,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the Firestore emulator"]
+    async fn add_player_skips_players_already_in() {
+        let storage = emulator::storage().await;
+        let store = RouletteStore::new(storage);
+        let mut lottery = Lottery::new(player("creator"), 10, &ThreadRandom).unwrap();
+        lottery.add_player(player("creator"));
+        store.put(&lottery).await.unwrap();
+
+        let joiner = player("joiner");
+        let (first, second) = tokio::join!(
+            store.add_player(&lottery.id, &joiner),
+            store.add_player(&lottery.id, &joiner),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        let stored = store.get(&lottery.id).await.unwrap().unwrap();
+        let ids: Vec<&str> = stored.players.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["creator", "joiner"]);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the Firestore emulator"]
+    async fn add_player_fails_for_a_missing_game() {
+        let storage = emulator::storage().await;
+        let store = RouletteStore::new(storage);
+        assert!(
+            store
+                .add_player("missing", &player("joiner"))
+                .await
+                .is_err()
+        );
+    }
+}
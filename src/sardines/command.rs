@@ -11,10 +11,14 @@ use tracing::{error, info};
 use crate::context::{Context, get_game_lock, remove_game_lock};
 use crate::discord::helpers::{encode_custom_id, parse_custom_id, rep_label};
 use crate::discord::types::{GuildMember, InteractionType};
+use crate::error::{EconomyError, EconomyResult};
+use crate::games::sweep::ORPHAN_GRACE_SECONDS;
+use crate::guilds::Settings;
 use crate::jobs::JobType;
 use crate::sardines::game::join_failure_chance;
 use crate::sardines::game::{Sardines, SardinesJobPayload};
 use crate::sardines::store::SardinesStore;
+use crate::users::admin::ensure_not_frozen;
 use crate::util::dates::is_today;
 
 /// Start a game of sardines
@@ -35,6 +39,8 @@ pub async fn sardines(
         GuildMember::from_serenity(guild_id, author, member.joined_at, member.nick.as_deref());
 
     let data = ctx.data();
+    let settings = data.settings(guild_id).await?;
+    ensure_not_frozen(data.user_store.as_ref(), &guild_member).await?;
 
     // Check daily limit
     let last_sardines = data
@@ -42,50 +48,31 @@ pub async fn sardines(
         .get_user_last_sardines(&guild_member)
         .await?;
     if let Some(last) = last_sardines
-        && is_today(data.config.discord.timezone, last)
+        && is_today(settings.timezone, last)
     {
-        ctx.send(
-            poise::CreateReply::default()
-                .content("You already started a sardines game today")
-                .ephemeral(true),
-        )
-        .await?;
-        return Ok(());
+        return Err(EconomyError::DailyLimitReached {
+            activity: "started a sardines game",
+        }
+        .into());
     }
 
-    let mut sardines = match Sardines::init(
-        Arc::new(SardinesStore::new(data.db.clone())),
+    let mut sardines = Sardines::init(
+        Arc::new(SardinesStore::new(data.storage.clone())),
         &data.config,
         Arc::clone(&data.user_store),
+        Arc::clone(&data.random),
         &guild_member,
         bet,
-    ) {
-        Ok(s) => s,
-        Err(e) => {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(e.to_string())
-                    .ephemeral(true),
-            )
-            .await?;
-            return Ok(());
-        }
-    };
+    )?;
 
     // Check rep
     let member_rep = data.user_store.get_user_rep(&guild_member).await?;
     if member_rep < sardines.buy_in() {
-        let username = &guild_member.username;
-        let buy_in_label = rep_label(sardines.buy_in(), false);
-        ctx.send(
-            poise::CreateReply::default()
-                .content(format!(
-                    "{username} only has {member_rep} and cannot bet in a sardines game whose buy-in is {buy_in_label}"
-                ))
-                .ephemeral(true),
-        )
-        .await?;
-        return Ok(());
+        return Err(EconomyError::InsufficientFunds {
+            balance: member_rep,
+            amount: sardines.buy_in(),
+        }
+        .into());
     }
 
     // Save the game (saves to Firestore, deducts creator bet)
@@ -119,7 +106,7 @@ pub async fn sardines(
             channel_id,
             message_id,
             &job_queue,
-            data.config.sardines_expiry_seconds,
+            settings.sardines_expiry_seconds,
         )
         .await?;
 
@@ -154,30 +141,29 @@ pub async fn handle_sardines_join(
     let game_lock = get_game_lock(&data.game_locks, game_id);
     let _guard = game_lock.write().await;
 
-    let mut game = match Sardines::load(
-        Arc::new(SardinesStore::new(data.db.clone())),
-        &data.config,
-        Arc::clone(&data.user_store),
-        game_id,
-    )
-    .await
-    {
-        Ok(s) => s,
-        Err(_) => {
-            interaction
-                .create_response(ctx, CreateInteractionResponse::Acknowledge)
-                .await?;
+    let loaded: EconomyResult<Sardines> = async {
+        let settings = data.settings(guild_id).await?;
+        let game = Sardines::load(
+            Arc::new(SardinesStore::new(data.storage.clone())),
+            &data.config,
+            Arc::clone(&data.user_store),
+            Arc::clone(&data.random),
+            game_id,
+        )
+        .await?;
+        validate_join(&game, &guild_member, &settings, data.user_store.as_ref()).await?;
+        Ok(game)
+    }
+    .await;
+    let mut game = match loaded {
+        Ok(game) => game,
+        Err(e) if e.is_user_error() => {
+            respond_ephemeral(ctx, interaction, e.to_string()).await?;
             return Ok(());
         }
+        Err(e) => return Err(e.into()),
     };
 
-    if let Some(error_msg) =
-        validate_join(&game, &guild_member, &data.config, data.user_store.as_ref()).await?
-    {
-        respond_ephemeral(ctx, interaction, error_msg).await?;
-        return Ok(());
-    }
-
     // Check if this join will end the game BEFORE adding the player
     let game_continues = game.can_add_player();
 
@@ -193,6 +179,10 @@ pub async fn handle_sardines_join(
     } else {
         // Game ends — joiner is already in the player pool
         let final_message = game.finish(Some(&guild_member.username)).await?;
+        // The timeout job has nothing left to do
+        if let Err(e) = data.job_queue.read().await.cancel(game.id()).await {
+            error!(error = %e, id = game.id(), "Failed to cancel sardines timeout job");
+        }
         CreateInteractionResponseMessage::new()
             .content(final_message)
             .components(vec![])
@@ -205,27 +195,36 @@ pub async fn handle_sardines_join(
     Ok(())
 }
 
-/// Validate whether a player can join a sardines game. Returns an error message if invalid.
+/// Validate whether a player can join a sardines game.
 async fn validate_join(
     game: &Sardines,
     guild_member: &GuildMember,
-    config: &crate::config::Config,
+    settings: &Settings,
     user_store: &dyn crate::users::UserStoreApi,
-) -> Result<Option<String>, anyhow::Error> {
+) -> EconomyResult<()> {
+    // Never let a game leak into another guild's economy
+    if game.creator().guild_id != guild_member.guild_id {
+        return Err(EconomyError::GameNotFound);
+    }
+
     let player_in_game = game.players().iter().any(|p| p.id == guild_member.id);
-    if player_in_game && !game.can_join_repeat(config) {
-        let min_players = config.min_players_before_rejoin;
-        return Ok(Some(format!(
-            "Cannot join a sardines game you are already in until the minimum player count of {min_players} is met."
-        )));
+    if player_in_game && !game.can_join_repeat(settings) {
+        return Err(EconomyError::RejoinTooSoon {
+            min_players: settings.min_players_before_rejoin,
+        });
     }
 
+    ensure_not_frozen(user_store, guild_member).await?;
+
     let member_rep = user_store.get_user_rep(guild_member).await?;
     if member_rep < game.buy_in() {
-        return Ok(Some("You do not have enough rep".to_string()));
+        return Err(EconomyError::InsufficientFunds {
+            balance: member_rep,
+            amount: game.buy_in(),
+        });
     }
 
-    Ok(None)
+    Ok(())
 }
 
 /// Send an ephemeral error response to an interaction.
@@ -269,15 +268,16 @@ async fn do_finish_sardines(
     payload: &SardinesJobPayload,
 ) -> anyhow::Result<()> {
     let game = match Sardines::load(
-        Arc::new(SardinesStore::new(ctx.db.clone())),
+        Arc::new(SardinesStore::new(ctx.storage.clone())),
         &ctx.config,
         Arc::clone(&ctx.user_store),
+        Arc::clone(&ctx.random),
         &payload.id,
     )
     .await
     {
         Ok(g) => g,
-        Err(_) => {
+        Err(EconomyError::GameNotFound) => {
             // Game was already finished by a player joining — expected case
             info!(
                 id = payload.id,
@@ -285,6 +285,7 @@ async fn do_finish_sardines(
             );
             return Ok(());
         }
+        Err(e) => return Err(e.into()),
     };
 
     info!(
@@ -312,7 +313,7 @@ async fn do_finish_sardines(
 /// Games with pending jobs are handled by the job queue's startup poll.
 /// Games without jobs (orphaned) are finished immediately.
 pub async fn recover_sardines(ctx: &crate::context::AppContext) {
-    let store = SardinesStore::new(ctx.db.clone());
+    let store = SardinesStore::new(ctx.storage.clone());
     let all_games = match store.list_all().await {
         Ok(games) => games,
         Err(e) => {
@@ -330,10 +331,10 @@ pub async fn recover_sardines(ctx: &crate::context::AppContext) {
         "Checking sardines games for recovery"
     );
 
-    // Collect IDs of games that have pending sardines:finish jobs
+    // Collect IDs of games that have pending or running sardines:finish jobs
     let pending_jobs = {
         let queue = ctx.job_queue.read().await;
-        match queue.get_pending_jobs().await {
+        match queue.get_active_jobs().await {
             Ok(jobs) => jobs,
             Err(e) => {
                 error!(error = %e, "Failed to get pending jobs for sardines recovery");
@@ -353,29 +354,61 @@ pub async fn recover_sardines(ctx: &crate::context::AppContext) {
         .collect();
 
     // Finish orphaned games (no pending job)
+    let now = chrono::Utc::now();
     for game in all_games {
         if sardines_job_game_ids.contains(&game.id) {
             continue; // Job exists, it will handle this game
         }
+        // A game started moments ago may still be enqueueing its timeout job
+        let recently_started = game
+            .start_time
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .is_some_and(|t| now - t.to_utc() < chrono::Duration::seconds(ORPHAN_GRACE_SECONDS));
+        if recently_started {
+            continue;
+        }
+        let game_lock = get_game_lock(&ctx.game_locks, &game.id);
+        let guard = game_lock.write().await;
 
-        info!(
-            id = game.id,
-            "Finishing orphaned sardines game (no pending job)"
-        );
-        let sardines = Sardines::from_lottery(
-            Arc::new(SardinesStore::new(ctx.db.clone())),
-            &ctx.config,
-            Arc::clone(&ctx.user_store),
-            game,
-        );
-        if let Err(e) = sardines.finish(None).await {
-            error!(error = %e, "Failed to finish orphaned sardines game");
+        // The listed snapshot may predate joins made before the lock was taken
+        if let Err(e) = finish_orphaned_sardines(ctx, &game.id).await {
+            error!(error = %e, id = game.id, "Failed to finish orphaned sardines game");
         }
+
+        drop(guard);
+        remove_game_lock(&ctx.game_locks, &game.id);
     }
 }
 
+async fn finish_orphaned_sardines(ctx: &crate::context::AppContext, id: &str) -> EconomyResult<()> {
+    let sardines = match Sardines::load(
+        Arc::new(SardinesStore::new(ctx.storage.clone())),
+        &ctx.config,
+        Arc::clone(&ctx.user_store),
+        Arc::clone(&ctx.random),
+        id,
+    )
+    .await
+    {
+        Ok(g) => g,
+        // Finished by a join or its own job since the games were listed
+        Err(EconomyError::GameNotFound) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    info!(id, "Finishing orphaned sardines game (no pending job)");
+    sardines.finish(None).await?;
+    Ok(())
+}
+
 fn sardines_message_parts(game: &Sardines) -> (String, CreateButton) {
-    let content = build_sardines_content(&game.creator().username, game.bet(), game.players());
+    let content = build_sardines_content(
+        &game.creator().username,
+        game.bet(),
+        game.players(),
+        &game.seed_hash(),
+    );
     let button = CreateButton::new(encode_custom_id(InteractionType::Sardines, game.id()))
         .label("Join Sardines");
     (content, button)
@@ -385,12 +418,13 @@ fn build_sardines_content(
     creator_name: &str,
     bet: i64,
     players: &[crate::games::lottery::DbPlayer],
+    seed_hash: &str,
 ) -> String {
     let failure_chance = join_failure_chance(players.len()) * 100.0;
     let bet_label = rep_label(bet, false);
 
     let banner = format!(
-        "## Sardines\n{creator_name} has started a game for {bet_label}. Click the button below to pay the buy-in and attempt to join the game.\nThere is currently a {failure_chance:.2}% chance of ending the game when joining. A winner is randomly selected among all players in the game."
+        "## Sardines\n{creator_name} has started a game for {bet_label}. Click the button below to pay the buy-in and attempt to join the game.\nThere is currently a {failure_chance:.2}% chance of ending the game when joining. A winner is randomly selected among all players in the game.\n-# Seed hash: `{seed_hash}`"
     );
 
     if players.len() < 2 {
@@ -405,10 +439,15 @@ fn build_sardines_content(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{Config, DiscordConfig, FirebaseConfig};
+    use crate::config::Config;
+    use crate::discord::helpers::mention;
+    use crate::error::EconomyResult;
     use crate::games::lottery::Lottery;
     use crate::sardines::store::{SardinesLottery, SardinesStoreApi};
-    use crate::users::UserStoreApi;
+    use crate::users::daily::DailyClaim;
+    use crate::users::ledger::LedgerEntry;
+    use crate::users::{RepChange, UserStoreApi};
+    use crate::util::random::{RandomSource, ScriptedRandom, ThreadRandom};
     use chrono::{DateTime, Utc};
 
     // ── No-op sardines store ─────────────────────────────────────────────────
@@ -423,9 +462,6 @@ mod tests {
         async fn put(&self, _lottery: &SardinesLottery) -> anyhow::Result<()> {
             Ok(())
         }
-        async fn delete(&self, _id: &str) -> anyhow::Result<()> {
-            Ok(())
-        }
         async fn set_players(
             &self,
             _id: &str,
@@ -433,6 +469,13 @@ mod tests {
         ) -> anyhow::Result<()> {
             Ok(())
         }
+        async fn settle(
+            &self,
+            _id: &str,
+            _settlement: crate::games::lottery::Settlement,
+        ) -> anyhow::Result<bool> {
+            Ok(true)
+        }
     }
 
     // ── Fixed-rep user store ─────────────────────────────────────────────────
@@ -443,43 +486,89 @@ mod tests {
 
     #[async_trait::async_trait]
     impl UserStoreApi for FixedRepStore {
-        async fn get_user_rep(&self, _member: &GuildMember) -> anyhow::Result<i64> {
+        async fn get_user_rep(&self, _member: &GuildMember) -> EconomyResult<i64> {
             Ok(self.rep)
         }
         async fn increment_user_rep(
             &self,
             _member: &GuildMember,
             _offset: i64,
-        ) -> anyhow::Result<()> {
+            _change: &RepChange,
+        ) -> EconomyResult<()> {
+            Ok(())
+        }
+        async fn transfer_rep(
+            &self,
+            _from: &GuildMember,
+            _to: &GuildMember,
+            _amount: i64,
+            _change: &RepChange,
+        ) -> EconomyResult<()> {
+            Ok(())
+        }
+        async fn set_user_rep_offset(
+            &self,
+            _member: &GuildMember,
+            _offset: i64,
+            _change: &RepChange,
+        ) -> EconomyResult<()> {
             Ok(())
         }
-        async fn increment_user_reps(&self, _updates: &[(GuildMember, i64)]) -> anyhow::Result<()> {
+        async fn is_user_frozen(&self, _member: &GuildMember) -> EconomyResult<bool> {
+            Ok(false)
+        }
+        async fn set_user_frozen(&self, _member: &GuildMember, _frozen: bool) -> EconomyResult<()> {
             Ok(())
         }
+        async fn claim_daily(
+            &self,
+            _member: &GuildMember,
+            _tz: chrono_tz::Tz,
+            _base_reward: i64,
+        ) -> EconomyResult<DailyClaim> {
+            Ok(DailyClaim {
+                reward: 0,
+                streak: 1,
+            })
+        }
+        async fn get_rep_history(
+            &self,
+            _member: &GuildMember,
+            _offset: u32,
+            _limit: u32,
+        ) -> EconomyResult<Vec<LedgerEntry>> {
+            Ok(Vec::new())
+        }
+        async fn get_guild_reputation_offsets(
+            &self,
+            _guild_id: &str,
+        ) -> EconomyResult<std::collections::HashMap<String, i64>> {
+            Ok(std::collections::HashMap::new())
+        }
         async fn get_user_last_guess(
             &self,
             _member: &GuildMember,
-        ) -> anyhow::Result<Option<DateTime<Utc>>> {
+        ) -> EconomyResult<Option<DateTime<Utc>>> {
             Ok(None)
         }
         async fn set_user_last_guess(
             &self,
             _member: &GuildMember,
             _date: DateTime<Utc>,
-        ) -> anyhow::Result<()> {
+        ) -> EconomyResult<()> {
             Ok(())
         }
         async fn get_user_last_sardines(
             &self,
             _member: &GuildMember,
-        ) -> anyhow::Result<Option<DateTime<Utc>>> {
+        ) -> EconomyResult<Option<DateTime<Utc>>> {
             Ok(None)
         }
         async fn set_user_last_sardines(
             &self,
             _member: &GuildMember,
             _date: DateTime<Utc>,
-        ) -> anyhow::Result<()> {
+        ) -> EconomyResult<()> {
             Ok(())
         }
     }
@@ -488,24 +577,15 @@ mod tests {
 
     fn test_config(min_players: usize) -> Config {
         Config {
-            port: 8006,
-            job_queue_poll_interval_ms: 5000,
             min_players_before_rejoin: min_players,
-            sardines_expiry_seconds: 86400,
-            random_seed: "test".to_string(),
-            discord: DiscordConfig {
-                timezone: "America/Los_Angeles".parse().unwrap(),
-                bot_token: "test".to_string(),
-                public_key: "test".to_string(),
-                server_id: "test".to_string(),
-            },
-            firebase: FirebaseConfig {
-                project_id: "test".to_string(),
-                cert_base64: "test".to_string(),
-            },
+            ..Config::for_tests()
         }
     }
 
+    fn test_settings(min_players: usize) -> Settings {
+        Settings::defaults(&test_config(min_players))
+    }
+
     fn make_player(id: &str) -> crate::games::lottery::DbPlayer {
         crate::games::lottery::DbPlayer {
             id: id.to_string(),
@@ -527,34 +607,60 @@ mod tests {
     /// Build a Sardines instance with the given player IDs already in the game.
     /// The first ID is treated as the creator (matches Sardines::init behaviour).
     fn make_sardines(player_ids: &[&str], bet: i64, min_players: usize) -> Sardines {
+        make_scripted_sardines(player_ids, bet, min_players, Arc::new(ThreadRandom))
+    }
+
+    fn make_scripted_sardines(
+        player_ids: &[&str],
+        bet: i64,
+        min_players: usize,
+        random: Arc<dyn RandomSource>,
+    ) -> Sardines {
         let store: Arc<dyn SardinesStoreApi> = Arc::new(NoOpSardinesStore);
         let user_store: Arc<dyn UserStoreApi> = Arc::new(FixedRepStore { rep: 9999 });
         let config = test_config(min_players);
 
         let creator = make_player(player_ids[0]);
-        let mut lottery: SardinesLottery = Lottery::new(creator, bet).unwrap();
+        let mut lottery: SardinesLottery = Lottery::new(creator, bet, random.as_ref()).unwrap();
         for &id in player_ids {
             lottery.add_player(make_player(id));
         }
 
-        Sardines::from_lottery(store, &config, user_store, lottery)
+        Sardines::from_lottery(store, &config, user_store, random, lottery)
     }
 
     // ── Tests ────────────────────────────────────────────────────────────────
 
+    /// Scripted rolls decide which join ends the game and who wins.
+    #[tokio::test]
+    async fn test_scripted_join_ends_game_and_picks_winner() {
+        let random = ScriptedRandom::new()
+            .roll("join:1", 0.99)
+            .roll("join:2", 0.99)
+            .roll("join:3", 0.0)
+            .roll("winner:4", 0.6);
+        let mut game = make_scripted_sardines(&["creator"], 100, 4, Arc::new(random));
+
+        for (i, id) in ["p1", "p2", "p3"].into_iter().enumerate() {
+            assert_eq!(game.can_add_player(), i < 2, "join {}", i + 1);
+            game.add_player(&make_member(id)).await.unwrap();
+        }
+
+        let message = game.finish(Some("p3")).await.unwrap();
+        assert!(message.contains(&mention("p2")), "{message}");
+    }
+
     /// Creator cannot rejoin their own game when below the minimum player count.
     #[tokio::test]
     async fn test_rejoin_blocked_below_min_players() {
         let game = make_sardines(&["creator"], 100, 4);
         let member = make_member("creator");
-        let config = test_config(4);
+        let settings = test_settings(4);
         let store = FixedRepStore { rep: 9999 };
 
-        let result = validate_join(&game, &member, &config, &store)
-            .await
-            .unwrap();
+        let result = validate_join(&game, &member, &settings, &store).await;
         assert!(
-            result.is_some(),
+            matches!(result, Err(EconomyError::RejoinTooSoon { min_players: 4 })),
             "creator should be blocked from rejoining with only 1 player (min=4)"
         );
     }
@@ -564,14 +670,12 @@ mod tests {
     async fn test_rejoin_allowed_at_min_players() {
         let game = make_sardines(&["creator", "p2", "p3", "p4"], 100, 4);
         let member = make_member("creator");
-        let config = test_config(4);
+        let settings = test_settings(4);
         let store = FixedRepStore { rep: 9999 };
 
-        let result = validate_join(&game, &member, &config, &store)
-            .await
-            .unwrap();
+        let result = validate_join(&game, &member, &settings, &store).await;
         assert!(
-            result.is_none(),
+            result.is_ok(),
             "creator should be allowed to rejoin with 4 players (min=4)"
         );
     }
@@ -581,14 +685,12 @@ mod tests {
     async fn test_new_player_not_blocked_by_rejoin_check() {
         let game = make_sardines(&["creator"], 100, 4);
         let member = make_member("newcomer");
-        let config = test_config(4);
+        let settings = test_settings(4);
         let store = FixedRepStore { rep: 9999 };
 
-        let result = validate_join(&game, &member, &config, &store)
-            .await
-            .unwrap();
+        let result = validate_join(&game, &member, &settings, &store).await;
         assert!(
-            result.is_none(),
+            result.is_ok(),
             "new player should never be blocked by the rejoin check"
         );
     }
@@ -598,18 +700,34 @@ mod tests {
     async fn test_blocked_by_insufficient_rep() {
         let game = make_sardines(&["creator"], 100, 4);
         let member = make_member("broke");
-        let config = test_config(4);
+        let settings = test_settings(4);
         let store = FixedRepStore { rep: 50 }; // buy-in is 100
 
-        let result = validate_join(&game, &member, &config, &store)
-            .await
-            .unwrap();
+        let result = validate_join(&game, &member, &settings, &store).await;
         assert!(
-            result.is_some(),
+            matches!(result, Err(EconomyError::InsufficientFunds { .. })),
             "player with 50 rep should be blocked from a 100-rep game"
         );
     }
 
+    /// A member of another guild cannot join, so economies never mix.
+    #[tokio::test]
+    async fn test_blocked_from_other_guild() {
+        let game = make_sardines(&["creator"], 100, 4);
+        let member = GuildMember {
+            guild_id: "guild2".to_string(),
+            ..make_member("outsider")
+        };
+        let settings = test_settings(4);
+        let store = FixedRepStore { rep: 9999 };
+
+        let result = validate_join(&game, &member, &settings, &store).await;
+        assert!(
+            matches!(result, Err(EconomyError::GameNotFound)),
+            "a member of another guild should not see the game"
+        );
+    }
+
     /// Verifies the ID field used for comparison: DbPlayer.id must match GuildMember.id.
     #[tokio::test]
     async fn test_player_id_field_matches_guild_member_id() {
@@ -617,15 +735,13 @@ mod tests {
         let discord_user_id = "987654321";
         let game = make_sardines(&[discord_user_id], 100, 4);
         let member = make_member(discord_user_id);
-        let config = test_config(4);
+        let settings = test_settings(4);
         let store = FixedRepStore { rep: 9999 };
 
         // Player is in game (1 player, min=4) → should be blocked
-        let result = validate_join(&game, &member, &config, &store)
-            .await
-            .unwrap();
+        let result = validate_join(&game, &member, &settings, &store).await;
         assert!(
-            result.is_some(),
+            result.is_err(),
             "player ID comparison should find the match and block the rejoin"
         );
     }
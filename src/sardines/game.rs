@@ -1,14 +1,18 @@
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tracing::error;
 
 use crate::config::Config;
 use crate::discord::helpers::{mention, rep_label};
 use crate::discord::types::GuildMember;
-use crate::games::lottery::{DbPlayer, Lottery};
+use crate::error::{EconomyError, EconomyResult};
+use crate::games::fair::{self, GameKind, Reveal};
+use crate::games::lottery::{DbPlayer, Lottery, Settlement};
+use crate::guilds::Settings;
 use crate::jobs::JobType;
 use crate::sardines::store::{SardinesLottery, SardinesStoreApi};
-use crate::users::UserStoreApi;
-use crate::util::random::seeded_weighted_random_element;
+use crate::users::{RepChange, RepReason, UserStoreApi};
+use crate::util::random::{RandomSource, seeded_weighted_random_element};
 
 const A: f64 = 0.4;
 const B: f64 = 0.3;
@@ -32,15 +36,17 @@ pub fn join_failure_chance(n: usize) -> f64 {
     -((A - n + B) / (n + C))
 }
 
-fn does_player_lose(n: usize) -> bool {
-    let chance = join_failure_chance(n);
-    rand::random::<f64>() < chance
+/// Whether a join roll (see `games::fair`) ends the game when `players_before`
+/// players are already in it.
+pub fn join_roll_ends_game(roll: f64, players_before: usize) -> bool {
+    roll < join_failure_chance(players_before)
 }
 
 pub struct Sardines {
     pub lottery: SardinesLottery,
     store: Arc<dyn SardinesStoreApi>,
     user_store: Arc<dyn UserStoreApi>,
+    random: Arc<dyn RandomSource>,
     random_seed: String,
 }
 
@@ -49,16 +55,18 @@ impl Sardines {
         store: Arc<dyn SardinesStoreApi>,
         config: &Config,
         user_store: Arc<dyn UserStoreApi>,
+        random: Arc<dyn RandomSource>,
         creator: &GuildMember,
         bet: i64,
-    ) -> anyhow::Result<Self> {
+    ) -> EconomyResult<Self> {
         let stored_creator = DbPlayer::from(creator);
-        let mut lottery = Lottery::new(stored_creator.clone(), bet)?;
+        let mut lottery = Lottery::new(stored_creator.clone(), bet, random.as_ref())?;
         lottery.add_player(stored_creator);
         Ok(Self {
             lottery,
             store,
             user_store,
+            random,
             random_seed: config.random_seed.clone(),
         })
     }
@@ -67,47 +75,56 @@ impl Sardines {
         store: Arc<dyn SardinesStoreApi>,
         config: &Config,
         user_store: Arc<dyn UserStoreApi>,
+        random: Arc<dyn RandomSource>,
         id: &str,
-    ) -> anyhow::Result<Self> {
-        let lottery = store
-            .get(id)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Sardines lottery not found"))?;
+    ) -> EconomyResult<Self> {
+        let lottery = store.get(id).await?.ok_or(EconomyError::GameNotFound)?;
         Ok(Self {
             lottery,
             store,
             user_store,
+            random,
             random_seed: config.random_seed.clone(),
         })
     }
 
-    /// Create a Sardines instance from an already-loaded lottery.
-    /// Used by recovery to wrap games loaded via list_all().
+    /// Create a Sardines instance from an already-built lottery.
+    #[cfg(test)]
     pub fn from_lottery(
         store: Arc<dyn SardinesStoreApi>,
         config: &Config,
         user_store: Arc<dyn UserStoreApi>,
+        random: Arc<dyn RandomSource>,
         lottery: SardinesLottery,
     ) -> Self {
         Self {
             lottery,
             store,
             user_store,
+            random,
             random_seed: config.random_seed.clone(),
         }
     }
 
-    /// Save the game to Firestore and deduct the creator's bet.
+    /// Deduct the creator's bet and save the game. The bet is taken first and
+    /// refunded if the save fails, so a stored game never holds an unpaid bet.
     pub async fn save(&mut self) -> anyhow::Result<String> {
         let start_time = self.lottery.start();
-        self.store.put(&self.lottery).await?;
 
-        // Immediately deduct creator's bet
         let creator = GuildMember::from(&self.lottery.creator);
         self.user_store
-            .increment_user_rep(&creator, -self.lottery.bet)
+            .increment_user_rep(
+                &creator,
+                -self.lottery.bet,
+                &RepChange::game(RepReason::SardinesBuyIn, &self.lottery.id),
+            )
             .await?;
 
+        if let Err(e) = self.store.put(&self.lottery).await {
+            self.refund(&creator).await;
+            return Err(e);
+        }
+
         Ok(start_time)
     }
 
@@ -125,8 +142,15 @@ impl Sardines {
             message_id,
         };
         job_queue
-            .enqueue(JobType::SardinesFinish, &payload, expiry_seconds)
-            .await
+            .enqueue(
+                JobType::SardinesFinish,
+                &payload,
+                expiry_seconds,
+                Some(&self.lottery.id),
+                Some(&self.lottery.creator.guild_id),
+            )
+            .await?;
+        Ok(())
     }
 
     pub fn id(&self) -> &str {
@@ -149,50 +173,121 @@ impl Sardines {
         &self.lottery.players
     }
 
-    pub fn can_join_repeat(&self, config: &Config) -> bool {
-        self.lottery.players.len() >= config.min_players_before_rejoin
+    pub fn can_join_repeat(&self, settings: &Settings) -> bool {
+        self.lottery.players.len() >= settings.min_players_before_rejoin
     }
 
     /// Check if the next player can be added without ending the game.
     /// Returns true if the game continues, false if the joiner triggers the end.
-    /// Must be called BEFORE add_player so the probability uses the pre-add count.
+    /// Must be called BEFORE add_player so the roll uses the pre-add count.
     pub fn can_add_player(&self) -> bool {
         // Length is all players, but joiners should not count the creator
         // So do not add one to check the incoming player, just leave it at length
-        !does_player_lose(self.lottery.players.len())
+        let players_before = self.lottery.players.len();
+        let roll = self
+            .lottery
+            .roll(self.random.as_ref(), &fair::join_label(players_before));
+        !join_roll_ends_game(roll, players_before)
     }
 
-    pub async fn add_player(&mut self, player: &GuildMember) -> anyhow::Result<()> {
-        let stored = DbPlayer::from(player);
-        self.lottery.add_player(stored);
-        self.store
-            .set_players(&self.lottery.id, &self.lottery.players)
-            .await?;
+    pub fn seed_hash(&self) -> String {
+        self.lottery.seed_hash()
+    }
 
-        // Immediately deduct buy-in
+    /// Deduct the joiner's bet and add them to the game, refunding the bet if
+    /// the player list cannot be saved.
+    pub async fn add_player(&mut self, player: &GuildMember) -> anyhow::Result<()> {
         self.user_store
-            .increment_user_rep(player, -self.lottery.bet)
+            .increment_user_rep(
+                player,
+                -self.lottery.bet,
+                &RepChange::game(RepReason::SardinesBuyIn, &self.lottery.id),
+            )
             .await?;
+
+        self.lottery.add_player(DbPlayer::from(player));
+        if let Err(e) = self
+            .store
+            .set_players(&self.lottery.id, &self.lottery.players)
+            .await
+        {
+            self.lottery.players.pop();
+            self.refund(player).await;
+            return Err(e);
+        }
         Ok(())
     }
 
+    /// Best-effort refund of a bet taken for a write that then failed.
+    async fn refund(&self, player: &GuildMember) {
+        let change = RepChange::game(RepReason::SardinesRefund, &self.lottery.id);
+        if let Err(e) = self
+            .user_store
+            .increment_user_rep(player, self.lottery.bet, &change)
+            .await
+        {
+            error!(error = %e, id = self.lottery.id, "Failed to refund sardines bet");
+        }
+    }
+
     /// Get the payout multiplier using weighted random seeded by lottery ID.
     fn get_multiplier(&self) -> f64 {
-        *seeded_weighted_random_element(&PAYOUT_MULTIPLIERS, &self.lottery.id, &self.random_seed)
+        *seeded_weighted_random_element(
+            &PAYOUT_MULTIPLIERS,
+            &self.lottery.id,
+            &self.random_seed,
+            self.lottery.seed_version,
+        )
     }
 
     /// Get the winner's payout: pot_size * multiplier.
-    fn get_payout(&self) -> i64 {
+    pub fn get_payout(&self) -> i64 {
         (self.lottery.pot_size() as f64 * self.get_multiplier()).floor() as i64
     }
 
+    fn reveal(&self, winner: Option<&DbPlayer>) -> Reveal {
+        Reveal {
+            game_id: self.lottery.id.clone(),
+            kind: GameKind::Sardines,
+            guild_id: self.lottery.creator.guild_id.clone(),
+            server_seed: self.lottery.server_seed.clone(),
+            players: self.lottery.players.clone(),
+            winner_id: winner.map(|w| w.id.clone()),
+            revealed_at: chrono::Utc::now(),
+        }
+    }
+
+    /// Pay out and delete the game in one transaction, failing if it was
+    /// already settled so a retried finish cannot pay twice.
+    async fn settle(
+        &self,
+        payouts: Vec<(GuildMember, i64)>,
+        reason: RepReason,
+        winner: Option<&DbPlayer>,
+    ) -> EconomyResult<()> {
+        let settlement = Settlement {
+            payouts,
+            change: RepChange::game(reason, &self.lottery.id),
+            reveal: self.reveal(winner),
+        };
+        if !self.store.settle(&self.lottery.id, settlement).await? {
+            return Err(EconomyError::GameNotFound);
+        }
+        Ok(())
+    }
+
     /// Finish the game. If `ended_by` is Some, a player triggered the end by joining;
     /// if None, the game expired via timeout.
     /// All current players are in the winner pool.
-    pub async fn finish(&self, ended_by: Option<&str>) -> anyhow::Result<String> {
+    pub async fn finish(&self, ended_by: Option<&str>) -> EconomyResult<String> {
         let creator_name = &self.lottery.creator.username;
 
-        if !self.lottery.can_finish() {
+        let winner = self
+            .lottery
+            .can_finish()
+            .then(|| self.lottery.finish(self.random.as_ref()).winner);
+
+        let Some(winner) = &winner else {
             // Not enough players — refund everyone
             let refunds: Vec<(GuildMember, i64)> = self
                 .lottery
@@ -200,16 +295,16 @@ impl Sardines {
                 .iter()
                 .map(|p| (GuildMember::from(p), self.lottery.bet))
                 .collect();
-            self.user_store.increment_user_reps(&refunds).await?;
-            self.store.delete(&self.lottery.id).await?;
+            self.settle(refunds, RepReason::SardinesRefund, None)
+                .await?;
 
+            let seed = &self.lottery.server_seed;
+            let id = &self.lottery.id;
             return Ok(format!(
-                "{creator_name}'s sardines game has expired. Not enough players joined, all bets refunded."
+                "{creator_name}'s sardines game has expired. Not enough players joined, all bets refunded.\n-# Seed: `{seed}` · `/verify {id}`"
             ));
-        }
+        };
 
-        let result = self.lottery.finish();
-        let winner = &result.winner;
         let payout = self.get_payout();
         let multiplier = self.get_multiplier();
 
@@ -242,11 +337,9 @@ impl Sardines {
         };
 
         // Credit the winner with the payout (all bets already deducted at join time)
-        let winner_member = GuildMember::from(winner);
-        self.user_store
-            .increment_user_rep(&winner_member, payout)
+        let payouts = vec![(GuildMember::from(winner), payout)];
+        self.settle(payouts, RepReason::SardinesWin, Some(winner))
             .await?;
-        self.store.delete(&self.lottery.id).await?;
 
         let winner_mention = mention(&winner.id);
         let payout_label = rep_label(payout, false);
@@ -260,8 +353,10 @@ impl Sardines {
             None => "has expired".to_string(),
         };
 
+        let seed = &self.lottery.server_seed;
+        let id = &self.lottery.id;
         Ok(format!(
-            "The sardines game started by {creator_name} {ending}.\n{winner_mention} won {payout_label} with a payout multiplier of **{multiplier_pct:.0}%**.\n\n{bettor_names} all bet {bet_label} for a total pot of {pot_label}."
+            "The sardines game started by {creator_name} {ending}.\n{winner_mention} won {payout_label} with a payout multiplier of **{multiplier_pct:.0}%**.\n\n{bettor_names} all bet {bet_label} for a total pot of {pot_label}.\n-# Seed: `{seed}` · `/verify {id}`"
         ))
     }
 }
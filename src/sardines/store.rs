@@ -1,6 +1,6 @@
-use crate::firebase::FirestoreStore;
-use crate::games::lottery::{DbPlayer, Lottery};
-use firestore::*;
+use crate::error::EconomyError;
+use crate::games::lottery::{self, DbPlayer, Lottery, Settlement};
+use crate::storage::{Collection, Storage};
 
 const COLLECTION: &str = "sardines";
 
@@ -9,21 +9,22 @@ const COLLECTION: &str = "sardines";
 pub trait SardinesStoreApi: Send + Sync {
     async fn get(&self, id: &str) -> anyhow::Result<Option<SardinesLottery>>;
     async fn put(&self, lottery: &SardinesLottery) -> anyhow::Result<()>;
-    async fn delete(&self, id: &str) -> anyhow::Result<()>;
     // async fn list_all(&self) -> anyhow::Result<Vec<SardinesLottery>>;
     async fn set_players(&self, id: &str, players: &[DbPlayer]) -> anyhow::Result<()>;
+    /// Pay out and delete a finished game; false if it was already settled.
+    async fn settle(&self, id: &str, settlement: Settlement) -> anyhow::Result<bool>;
 }
 
 pub type SardinesLottery = Lottery<DbPlayer>;
 
 pub struct SardinesStore {
-    store: FirestoreStore,
+    store: Collection,
 }
 
 impl SardinesStore {
-    pub fn new(db: FirestoreDb) -> Self {
+    pub fn new(storage: Storage) -> Self {
         Self {
-            store: FirestoreStore::new(db, COLLECTION),
+            store: Collection::new(storage, COLLECTION),
         }
     }
 
@@ -35,46 +36,43 @@ impl SardinesStore {
         self.store.put(&lottery.id, lottery).await
     }
 
-    pub async fn delete(&self, id: &str) -> anyhow::Result<()> {
-        self.store.delete(id).await
-    }
-
     pub async fn list_all(&self) -> anyhow::Result<Vec<SardinesLottery>> {
         self.store.list_all().await
     }
 
+    /// Replace the player list. Fails with `GameNotFound` if the game has
+    /// already ended, so a joiner's bet can be refunded.
     pub async fn set_players(&self, id: &str, players: &[DbPlayer]) -> anyhow::Result<()> {
         let id = id.to_string();
         let players = players.to_vec();
-        self.store
-            .db()
-            .run_transaction(|db, tx| {
+        let found = self
+            .store
+            .storage()
+            .run_transaction(move |tx| {
                 let id = id.clone();
                 let players = players.clone();
                 Box::pin(async move {
-                    let lottery: Option<SardinesLottery> = db
-                        .fluent()
-                        .select()
-                        .by_id_in(COLLECTION)
-                        .obj()
-                        .one(&id)
-                        .await?;
-
-                    if let Some(mut lottery) = lottery {
-                        lottery.players = players;
-                        db.fluent()
-                            .update()
-                            .in_col(COLLECTION)
-                            .document_id(&id)
-                            .object(&lottery)
-                            .add_to_transaction(tx)?;
-                    }
-                    Ok(())
+                    let lottery: Option<SardinesLottery> = tx.get(COLLECTION, &id).await?;
+
+                    let Some(mut lottery) = lottery else {
+                        return Ok(false);
+                    };
+                    lottery.players = players;
+                    tx.update(COLLECTION, &id, &lottery)?;
+                    Ok(true)
                 })
             })
             .await?;
+        if !found {
+            return Err(EconomyError::GameNotFound.into());
+        }
         Ok(())
     }
+
+    /// Pay out and delete a finished game; see [`lottery::settle`].
+    pub async fn settle(&self, id: &str, settlement: Settlement) -> anyhow::Result<bool> {
+        lottery::settle(self.store.storage(), COLLECTION, id, settlement).await
+    }
 }
 
 #[async_trait::async_trait]
@@ -87,10 +85,6 @@ impl SardinesStoreApi for SardinesStore {
         self.put(lottery).await
     }
 
-    async fn delete(&self, id: &str) -> anyhow::Result<()> {
-        self.delete(id).await
-    }
-
     // async fn list_all(&self) -> anyhow::Result<Vec<SardinesLottery>> {
     //     self.list_all().await
     // }
@@ -98,4 +92,55 @@ impl SardinesStoreApi for SardinesStore {
     async fn set_players(&self, id: &str, players: &[DbPlayer]) -> anyhow::Result<()> {
         self.set_players(id, players).await
     }
+
+    async fn settle(&self, id: &str, settlement: Settlement) -> anyhow::Result<bool> {
+        self.settle(id, settlement).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::emulator;
+    use crate::util::random::ThreadRandom;
+
+    fn player(id: &str) -> DbPlayer {
+        DbPlayer {
+            id: id.to_string(),
+            guild_id: "guild".to_string(),
+            username: format!("user-{id}"),
+            joined_at: None,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the Firestore emulator"]
+    async fn set_players_replaces_the_player_list() {
+        let storage = emulator::storage().await;
+        let store = SardinesStore::new(storage);
+        let mut lottery = Lottery::new(player("creator"), 10, &ThreadRandom).unwrap();
+        lottery.add_player(player("creator"));
+        store.put(&lottery).await.unwrap();
+
+        let players = [player("creator"), player("a"), player("b")];
+        store.set_players(&lottery.id, &players).await.unwrap();
+
+        let stored = store.get(&lottery.id).await.unwrap().unwrap();
+        assert_eq!(stored.players, players);
+        assert_eq!(stored.bet, 10);
+    }
+
+    #[tokio::test]
+    async fn set_players_fails_for_a_missing_game() {
+        let store = SardinesStore::new(Storage::memory());
+        let err = store
+            .set_players("missing", &[player("a")])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<EconomyError>(),
+            Some(EconomyError::GameNotFound)
+        ));
+        assert!(store.get("missing").await.unwrap().is_none());
+    }
 }
@@ -0,0 +1,101 @@
+use serde_json::Value;
+
+use crate::storage::StorageResult;
+
+/// A single change to a document, buffered until its transaction commits.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Write {
+    /// Create the document, failing if it already exists.
+    Create {
+        collection: String,
+        id: String,
+        doc: Value,
+    },
+    /// Replace the document, creating it if needed.
+    Set {
+        collection: String,
+        id: String,
+        doc: Value,
+    },
+    /// Overwrite only the listed top-level fields, creating the document if
+    /// needed. A listed field missing from `doc` is removed, as in Firestore.
+    Merge {
+        collection: String,
+        id: String,
+        doc: Value,
+        fields: Vec<String>,
+    },
+    Delete {
+        collection: String,
+        id: String,
+    },
+}
+
+/// A document database that keeps documents as JSON, for running without
+/// Firestore. Backends are used behind a lock, so a transaction sees no other
+/// writer between its reads and its commit.
+pub trait Backend: Send {
+    fn get(&self, collection: &str, id: &str) -> StorageResult<Option<Value>>;
+
+    /// Every document in the collection with its id, in id order.
+    fn list(&self, collection: &str) -> StorageResult<Vec<(String, Value)>>;
+
+    /// Documents whose id starts with `prefix`, in id order. Backends that
+    /// keep ids sorted should scan just that range.
+    fn list_prefix(&self, collection: &str, prefix: &str) -> StorageResult<Vec<(String, Value)>> {
+        let mut docs = self.list(collection)?;
+        docs.retain(|(id, _)| id.starts_with(prefix));
+        Ok(docs)
+    }
+
+    /// Apply all writes, or none of them if any fails.
+    fn apply(&mut self, writes: Vec<Write>) -> StorageResult<()>;
+}
+
+/// The document that results from applying a `Set` or `Merge` to `current`.
+pub(crate) fn merged(current: Option<Value>, doc: Value, fields: Option<&[String]>) -> Value {
+    let Some(fields) = fields else {
+        return doc;
+    };
+    let mut merged = match current {
+        Some(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let Value::Object(mut updates) = doc else {
+        return Value::Object(merged);
+    };
+    for field in fields {
+        match updates.remove(field) {
+            Some(value) => merged.insert(field.clone(), value),
+            None => merged.remove(field),
+        };
+    }
+    Value::Object(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_touches_only_listed_fields() {
+        let current = json!({ "name": "old", "offset": 5, "frozen": true });
+        let doc = json!({ "name": "new", "offset": 9 });
+        let fields = ["name".to_string(), "frozen".to_string()];
+
+        assert_eq!(
+            merged(Some(current), doc, Some(&fields)),
+            json!({ "name": "new", "offset": 5 })
+        );
+    }
+
+    #[test]
+    fn set_replaces_the_document() {
+        let current = json!({ "name": "old", "offset": 5 });
+        assert_eq!(
+            merged(Some(current), json!({ "name": "new" }), None),
+            json!({ "name": "new" })
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::storage::Storage;
+
+/// Typed access to one collection, for stores that only need plain CRUD.
+#[derive(Clone)]
+pub struct Collection {
+    storage: Storage,
+    name: &'static str,
+}
+
+impl Collection {
+    pub fn new(storage: Storage, name: &'static str) -> Self {
+        Self { storage, name }
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    pub async fn get<T: DeserializeOwned + Send>(&self, id: &str) -> anyhow::Result<Option<T>> {
+        Ok(self.storage.get(self.name, id).await?)
+    }
+
+    pub async fn put<T: Serialize + DeserializeOwned + Sync + Send>(
+        &self,
+        id: &str,
+        obj: &T,
+    ) -> anyhow::Result<()> {
+        Ok(self.storage.put(self.name, id, obj).await?)
+    }
+
+    pub async fn update<T: Serialize + DeserializeOwned + Sync + Send>(
+        &self,
+        id: &str,
+        obj: &T,
+    ) -> anyhow::Result<()> {
+        Ok(self.storage.update(self.name, id, obj).await?)
+    }
+
+    pub async fn delete(&self, id: &str) -> anyhow::Result<()> {
+        Ok(self.storage.delete(self.name, id).await?)
+    }
+
+    pub async fn list_all<T: DeserializeOwned + Send>(&self) -> anyhow::Result<Vec<T>> {
+        let docs = self.storage.list(self.name).await?;
+        Ok(docs.into_iter().map(|(_, doc)| doc).collect())
+    }
+}
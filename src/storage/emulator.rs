@@ -0,0 +1,27 @@
+//! Test harness for running stores against the Firestore emulator.
+//!
+//! Emulator tests are `#[ignore]`d so the default suite runs offline. Start
+//! one with `gcloud emulators firestore start --host-port=localhost:8080` and
+//! run them with `FIRESTORE_EMULATOR_HOST=localhost:8080 cargo test -- --ignored`.
+
+use crate::firebase::client::{EMULATOR_HOST_ENV, create_emulator_db};
+use crate::storage::Storage;
+
+const PROJECT_ALPHABET: [char; 36] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
+    't', 'u', 'v', 'w', 'x', 'y', 'z', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+/// Storage on a fresh emulator project, so tests never see each other's
+/// documents. Panics when no emulator is configured.
+pub async fn storage() -> Storage {
+    assert!(
+        std::env::var(EMULATOR_HOST_ENV).is_ok(),
+        "{EMULATOR_HOST_ENV} must point at a running Firestore emulator"
+    );
+    let project_id = format!("test-{}", nanoid::nanoid!(12, &PROJECT_ALPHABET));
+    let db = create_emulator_db(&project_id)
+        .await
+        .expect("failed to connect to the Firestore emulator");
+    Storage::firestore(db)
+}
@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use crate::storage::StorageError;
+use crate::storage::StorageResult;
+use crate::storage::backend::{Backend, Write, merged};
+
+/// Keeps every collection in process memory; state is lost on exit.
+/// Used by tests and for throwaway local runs.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    collections: HashMap<String, BTreeMap<String, Value>>,
+}
+
+impl Backend for MemoryBackend {
+    fn get(&self, collection: &str, id: &str) -> StorageResult<Option<Value>> {
+        Ok(self
+            .collections
+            .get(collection)
+            .and_then(|docs| docs.get(id))
+            .cloned())
+    }
+
+    fn list(&self, collection: &str) -> StorageResult<Vec<(String, Value)>> {
+        Ok(self
+            .collections
+            .get(collection)
+            .map(|docs| {
+                docs.iter()
+                    .map(|(id, doc)| (id.clone(), doc.clone()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    fn list_prefix(&self, collection: &str, prefix: &str) -> StorageResult<Vec<(String, Value)>> {
+        let Some(docs) = self.collections.get(collection) else {
+            return Ok(Vec::new());
+        };
+        Ok(docs
+            .range(prefix.to_string()..)
+            .take_while(|(id, _)| id.starts_with(prefix))
+            .map(|(id, doc)| (id.clone(), doc.clone()))
+            .collect())
+    }
+
+    fn apply(&mut self, writes: Vec<Write>) -> StorageResult<()> {
+        // Creates are the only writes that can fail, so check them all first
+        for write in &writes {
+            if let Write::Create { collection, id, .. } = write
+                && self.get(collection, id)?.is_some()
+            {
+                return Err(StorageError::AlreadyExists {
+                    collection: collection.clone(),
+                    id: id.clone(),
+                });
+            }
+        }
+
+        for write in writes {
+            match write {
+                Write::Create {
+                    collection,
+                    id,
+                    doc,
+                }
+                | Write::Set {
+                    collection,
+                    id,
+                    doc,
+                } => {
+                    self.collections
+                        .entry(collection)
+                        .or_default()
+                        .insert(id, doc);
+                }
+                Write::Merge {
+                    collection,
+                    id,
+                    doc,
+                    fields,
+                } => {
+                    let docs = self.collections.entry(collection).or_default();
+                    let current = docs.remove(&id);
+                    docs.insert(id, merged(current, doc, Some(&fields)));
+                }
+                Write::Delete { collection, id } => {
+                    if let Some(docs) = self.collections.get_mut(&collection) {
+                        docs.remove(&id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
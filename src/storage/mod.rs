@@ -0,0 +1,655 @@
+pub mod backend;
+pub mod collection;
+#[cfg(test)]
+pub mod emulator;
+pub mod memory;
+pub mod query;
+pub mod sqlite;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use firestore::errors::{BackoffError, FirestoreError};
+use firestore::*;
+use gcloud_sdk::google::firestore::v1::Write as FirestoreWrite;
+use serde::{Serialize, de::DeserializeOwned};
+use thiserror::Error;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+pub use backend::{Backend, Write};
+pub use collection::Collection;
+pub use memory::MemoryBackend;
+pub use query::Query;
+pub use sqlite::SqliteBackend;
+
+use crate::config::StorageConfig;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error(transparent)]
+    Firestore(#[from] FirestoreError),
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Malformed document: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("Document {collection}/{id} already exists")]
+    AlreadyExists { collection: String, id: String },
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+/// Sorts after any character used in a document id, so the ids starting
+/// with `prefix` are exactly those from `prefix` to `prefix + PREFIX_END`.
+pub(crate) const PREFIX_END: char = '\u{f8ff}';
+
+/// The future a transaction body returns; it may borrow the transaction.
+pub type TransactionFuture<'t, T> = Pin<Box<dyn Future<Output = StorageResult<T>> + Send + 't>>;
+
+/// Handle to the document database, cheap to clone.
+///
+/// Firestore documents are written straight from the typed objects so
+/// timestamp fields keep their Firestore type; the local backends store
+/// documents as JSON.
+#[derive(Clone)]
+pub struct Storage {
+    inner: Inner,
+}
+
+#[derive(Clone)]
+enum Inner {
+    Firestore(FirestoreDb),
+    Local(Arc<Mutex<Box<dyn Backend>>>),
+}
+
+impl Storage {
+    pub fn firestore(db: FirestoreDb) -> Self {
+        Self {
+            inner: Inner::Firestore(db),
+        }
+    }
+
+    pub fn local(backend: impl Backend + 'static) -> Self {
+        Self {
+            inner: Inner::Local(Arc::new(Mutex::new(Box::new(backend)))),
+        }
+    }
+
+    pub fn memory() -> Self {
+        Self::local(MemoryBackend::default())
+    }
+
+    /// Open the configured database.
+    pub async fn connect(config: &StorageConfig) -> anyhow::Result<Self> {
+        Ok(match config {
+            StorageConfig::Firestore(firebase) => Self::firestore(
+                crate::firebase::client::create_firestore_db(
+                    &firebase.project_id,
+                    &firebase.cert_base64,
+                )
+                .await?,
+            ),
+            StorageConfig::Sqlite(path) => Self::local(SqliteBackend::open(path)?),
+            StorageConfig::Memory => Self::memory(),
+        })
+    }
+
+    pub async fn get<T: DeserializeOwned + Send>(
+        &self,
+        collection: &str,
+        id: &str,
+    ) -> StorageResult<Option<T>> {
+        match &self.inner {
+            Inner::Firestore(db) => Ok(db
+                .fluent()
+                .select()
+                .by_id_in(collection)
+                .obj()
+                .one(id)
+                .await?),
+            Inner::Local(backend) => from_json(backend.lock().await.get(collection, id)?),
+        }
+    }
+
+    /// Create a document, failing if one already exists with that id.
+    pub async fn put<T: Serialize + DeserializeOwned + Sync + Send>(
+        &self,
+        collection: &str,
+        id: &str,
+        obj: &T,
+    ) -> StorageResult<()> {
+        match &self.inner {
+            Inner::Firestore(db) => {
+                db.fluent()
+                    .insert()
+                    .into(collection)
+                    .document_id(id)
+                    .object(obj)
+                    .execute::<()>()
+                    .await?;
+                Ok(())
+            }
+            Inner::Local(backend) => backend.lock().await.apply(vec![Write::Create {
+                collection: collection.to_string(),
+                id: id.to_string(),
+                doc: serde_json::to_value(obj)?,
+            }]),
+        }
+    }
+
+    /// Create or replace a document.
+    pub async fn update<T: Serialize + DeserializeOwned + Sync + Send>(
+        &self,
+        collection: &str,
+        id: &str,
+        obj: &T,
+    ) -> StorageResult<()> {
+        self.write(collection, id, obj, None).await
+    }
+
+    /// Write only `fields` of `obj`, creating the document if needed.
+    pub async fn update_fields<T: Serialize + DeserializeOwned + Sync + Send>(
+        &self,
+        collection: &str,
+        id: &str,
+        obj: &T,
+        fields: Vec<String>,
+    ) -> StorageResult<()> {
+        self.write(collection, id, obj, Some(fields)).await
+    }
+
+    async fn write<T: Serialize + DeserializeOwned + Sync + Send>(
+        &self,
+        collection: &str,
+        id: &str,
+        obj: &T,
+        fields: Option<Vec<String>>,
+    ) -> StorageResult<()> {
+        match &self.inner {
+            Inner::Firestore(db) => {
+                let update = db.fluent().update();
+                let update = match fields {
+                    Some(fields) => update.fields(fields),
+                    None => update,
+                };
+                update
+                    .in_col(collection)
+                    .document_id(id)
+                    .object(obj)
+                    .execute::<()>()
+                    .await?;
+                Ok(())
+            }
+            Inner::Local(backend) => backend
+                .lock()
+                .await
+                .apply(vec![local_write(collection, id, obj, fields)?]),
+        }
+    }
+
+    pub async fn delete(&self, collection: &str, id: &str) -> StorageResult<()> {
+        match &self.inner {
+            Inner::Firestore(db) => {
+                db.fluent()
+                    .delete()
+                    .from(collection)
+                    .document_id(id)
+                    .execute()
+                    .await?;
+                Ok(())
+            }
+            Inner::Local(backend) => backend.lock().await.apply(vec![Write::Delete {
+                collection: collection.to_string(),
+                id: id.to_string(),
+            }]),
+        }
+    }
+
+    /// Every document in the collection, paired with its id.
+    pub async fn list<T: DeserializeOwned + Send>(
+        &self,
+        collection: &str,
+    ) -> StorageResult<Vec<(String, T)>> {
+        match &self.inner {
+            Inner::Firestore(db) => {
+                let docs = db.fluent().select().from(collection).query().await?;
+                docs.iter()
+                    .map(|doc| {
+                        let id = doc.name.rsplit('/').next().unwrap_or_default();
+                        Ok((id.to_string(), FirestoreDb::deserialize_doc_to(doc)?))
+                    })
+                    .collect()
+            }
+            Inner::Local(backend) => backend
+                .lock()
+                .await
+                .list(collection)?
+                .into_iter()
+                .map(|(id, doc)| Ok((id, serde_json::from_value(doc)?)))
+                .collect(),
+        }
+    }
+
+    /// Documents whose id starts with `prefix`, paired with their id. Scans
+    /// just that id range rather than the whole collection.
+    pub async fn list_prefix<T: DeserializeOwned + Send>(
+        &self,
+        collection: &str,
+        prefix: &str,
+    ) -> StorageResult<Vec<(String, T)>> {
+        match &self.inner {
+            Inner::Firestore(db) => {
+                let reference = |id: String| {
+                    FirestoreReference(format!("{}/{collection}/{id}", db.get_documents_path()))
+                };
+                let start = reference(prefix.to_string());
+                let end = reference(format!("{prefix}{PREFIX_END}"));
+                let docs = db
+                    .fluent()
+                    .select()
+                    .from(collection)
+                    .filter(|q| {
+                        q.for_all([
+                            q.field("__name__").greater_than_or_equal(&start),
+                            q.field("__name__").less_than_or_equal(&end),
+                        ])
+                    })
+                    .query()
+                    .await?;
+                docs.iter()
+                    .map(|doc| {
+                        let id = doc.name.rsplit('/').next().unwrap_or_default();
+                        Ok((id.to_string(), FirestoreDb::deserialize_doc_to(doc)?))
+                    })
+                    .collect()
+            }
+            Inner::Local(backend) => backend
+                .lock()
+                .await
+                .list_prefix(collection, prefix)?
+                .into_iter()
+                .map(|(id, doc)| Ok((id, serde_json::from_value(doc)?)))
+                .collect(),
+        }
+    }
+
+    pub async fn query<T: DeserializeOwned + Send>(&self, query: &Query) -> StorageResult<Vec<T>> {
+        match &self.inner {
+            Inner::Firestore(db) => {
+                let mut select = db
+                    .fluent()
+                    .select()
+                    .from(query.collection.as_str())
+                    .filter(|q| {
+                        q.for_all(query.filters.iter().map(|filter| match filter {
+                            query::Filter::Eq(field, value) => q.field(field).eq(value),
+                            query::Filter::In(field, values) => q.field(field).is_in(values),
+                        }))
+                    })
+                    .offset(query.offset);
+                if let Some((field, descending)) = &query.order_by {
+                    let direction = if *descending {
+                        FirestoreQueryDirection::Descending
+                    } else {
+                        FirestoreQueryDirection::Ascending
+                    };
+                    select = select.order_by([(field.as_str(), direction)]);
+                }
+                if let Some(limit) = query.limit {
+                    select = select.limit(limit);
+                }
+                Ok(select.obj().query().await?)
+            }
+            Inner::Local(backend) => {
+                let docs = backend.lock().await.list(&query.collection)?;
+                query
+                    .run(docs)
+                    .into_iter()
+                    .map(|doc| Ok(serde_json::from_value(doc)?))
+                    .collect()
+            }
+        }
+    }
+
+    /// Run `body` as a transaction: its reads see a consistent snapshot and
+    /// its writes commit together. Firestore retries the body on contention,
+    /// so it must be safe to run more than once.
+    pub async fn run_transaction<T, F>(&self, body: F) -> StorageResult<T>
+    where
+        T: Send + 'static,
+        F: for<'t> Fn(&'t mut Transaction) -> TransactionFuture<'t, T> + Send + Sync + 'static,
+    {
+        match &self.inner {
+            Inner::Firestore(db) => {
+                let body = Arc::new(body);
+                let result = db
+                    .run_transaction(|db, fs_tx| {
+                        let body = body.clone();
+                        Box::pin(async move {
+                            let writes = FirestoreTransactionData::new(
+                                fs_tx.transaction_id().clone(),
+                                db.get_documents_path().clone(),
+                                tracing::Span::current(),
+                                Vec::new(),
+                            );
+                            let mut tx = Transaction {
+                                inner: TxInner::Firestore { db, writes },
+                            };
+                            let result = body(&mut tx).await?;
+                            if let TxInner::Firestore { writes, .. } = tx.inner {
+                                for write in writes.writes() {
+                                    fs_tx
+                                        .add(BufferedWrite(write.clone()))
+                                        .map_err(|e| BackoffError::from(StorageError::from(e)))?;
+                                }
+                            }
+                            Ok(result)
+                        })
+                    })
+                    .await?;
+                Ok(result)
+            }
+            Inner::Local(backend) => {
+                let mut tx = Transaction {
+                    inner: TxInner::Local {
+                        backend: backend.clone().lock_owned().await,
+                        writes: Vec::new(),
+                    },
+                };
+                let result = body(&mut tx).await?;
+                if let TxInner::Local {
+                    mut backend,
+                    writes,
+                } = tx.inner
+                {
+                    backend.apply(writes)?;
+                }
+                Ok(result)
+            }
+        }
+    }
+}
+
+/// Reads and buffered writes of a running transaction; see [`Storage::run_transaction`].
+pub struct Transaction {
+    inner: TxInner,
+}
+
+enum TxInner {
+    Firestore {
+        db: FirestoreDb,
+        writes: FirestoreTransactionData,
+    },
+    /// Holds the backend's lock until the transaction commits.
+    Local {
+        backend: OwnedMutexGuard<Box<dyn Backend>>,
+        writes: Vec<Write>,
+    },
+}
+
+impl Transaction {
+    pub async fn get<T: DeserializeOwned + Send>(
+        &mut self,
+        collection: &str,
+        id: &str,
+    ) -> StorageResult<Option<T>> {
+        match &self.inner {
+            TxInner::Firestore { db, .. } => Ok(db
+                .fluent()
+                .select()
+                .by_id_in(collection)
+                .obj()
+                .one(id)
+                .await?),
+            TxInner::Local { backend, .. } => from_json(backend.get(collection, id)?),
+        }
+    }
+
+    /// Create or replace a document when the transaction commits.
+    pub fn update<T: Serialize + DeserializeOwned + Sync + Send>(
+        &mut self,
+        collection: &str,
+        id: &str,
+        obj: &T,
+    ) -> StorageResult<()> {
+        self.write(collection, id, obj, None)
+    }
+
+    /// Write only `fields` of `obj` when the transaction commits.
+    pub fn update_fields<T: Serialize + DeserializeOwned + Sync + Send>(
+        &mut self,
+        collection: &str,
+        id: &str,
+        obj: &T,
+        fields: Vec<String>,
+    ) -> StorageResult<()> {
+        self.write(collection, id, obj, Some(fields))
+    }
+
+    fn write<T: Serialize + DeserializeOwned + Sync + Send>(
+        &mut self,
+        collection: &str,
+        id: &str,
+        obj: &T,
+        fields: Option<Vec<String>>,
+    ) -> StorageResult<()> {
+        match &mut self.inner {
+            TxInner::Firestore { db, writes } => {
+                let update = db.fluent().update();
+                let update = match fields {
+                    Some(fields) => update.fields(fields),
+                    None => update,
+                };
+                update
+                    .in_col(collection)
+                    .document_id(id)
+                    .object(obj)
+                    .add_to_transaction(writes)?;
+            }
+            TxInner::Local { writes, .. } => {
+                writes.push(local_write(collection, id, obj, fields)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete a document when the transaction commits.
+    pub fn delete(&mut self, collection: &str, id: &str) -> StorageResult<()> {
+        match &mut self.inner {
+            TxInner::Firestore { db, writes } => {
+                db.fluent()
+                    .delete()
+                    .from(collection)
+                    .document_id(id)
+                    .add_to_transaction(writes)?;
+            }
+            TxInner::Local { writes, .. } => writes.push(Write::Delete {
+                collection: collection.to_string(),
+                id: id.to_string(),
+            }),
+        }
+        Ok(())
+    }
+}
+
+/// A write already built for Firestore, replayed into its transaction at commit.
+struct BufferedWrite(FirestoreWrite);
+
+impl TryFrom<BufferedWrite> for FirestoreWrite {
+    type Error = FirestoreError;
+
+    fn try_from(write: BufferedWrite) -> Result<Self, Self::Error> {
+        Ok(write.0)
+    }
+}
+
+fn local_write<T: Serialize>(
+    collection: &str,
+    id: &str,
+    obj: &T,
+    fields: Option<Vec<String>>,
+) -> StorageResult<Write> {
+    let collection = collection.to_string();
+    let id = id.to_string();
+    let doc = serde_json::to_value(obj)?;
+    Ok(match fields {
+        Some(fields) => Write::Merge {
+            collection,
+            id,
+            doc,
+            fields,
+        },
+        None => Write::Set {
+            collection,
+            id,
+            doc,
+        },
+    })
+}
+
+fn from_json<T: DeserializeOwned>(doc: Option<serde_json::Value>) -> StorageResult<Option<T>> {
+    Ok(doc.map(serde_json::from_value).transpose()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Doc {
+        name: String,
+        #[serde(default)]
+        score: i64,
+    }
+
+    fn doc(name: &str, score: i64) -> Doc {
+        Doc {
+            name: name.to_string(),
+            score,
+        }
+    }
+
+    fn backends() -> Vec<Storage> {
+        vec![
+            Storage::memory(),
+            Storage::local(SqliteBackend::open(":memory:").unwrap()),
+        ]
+    }
+
+    #[tokio::test]
+    async fn crud_round_trips() {
+        for storage in backends() {
+            storage.put("docs", "a", &doc("a", 1)).await.unwrap();
+            assert!(matches!(
+                storage.put("docs", "a", &doc("a", 2)).await,
+                Err(StorageError::AlreadyExists { .. })
+            ));
+
+            storage
+                .update_fields("docs", "a", &doc("renamed", 9), vec!["name".to_string()])
+                .await
+                .unwrap();
+            assert_eq!(
+                storage.get::<Doc>("docs", "a").await.unwrap(),
+                Some(doc("renamed", 1))
+            );
+
+            storage.update("docs", "b", &doc("b", 2)).await.unwrap();
+            let ids: Vec<String> = storage
+                .list::<Doc>("docs")
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            assert_eq!(ids, ["a", "b"]);
+
+            storage.delete("docs", "a").await.unwrap();
+            assert_eq!(storage.get::<Doc>("docs", "a").await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn transactions_commit_together_or_not_at_all() {
+        for storage in backends() {
+            storage.put("docs", "from", &doc("from", 10)).await.unwrap();
+
+            let moved = storage
+                .run_transaction(|tx| {
+                    Box::pin(async move {
+                        let Some(from) = tx.get::<Doc>("docs", "from").await? else {
+                            return Ok(false);
+                        };
+                        tx.update("docs", "from", &doc("from", from.score - 4))?;
+                        tx.update("docs", "to", &doc("to", 4))?;
+                        Ok(true)
+                    })
+                })
+                .await
+                .unwrap();
+            assert!(moved);
+            assert_eq!(
+                storage.get::<Doc>("docs", "to").await.unwrap(),
+                Some(doc("to", 4))
+            );
+
+            // A body that fails leaves nothing behind
+            let failed = storage
+                .run_transaction(|tx| {
+                    Box::pin(async move {
+                        tx.delete("docs", "to")?;
+                        let _: Doc = serde_json::from_str("not json")?;
+                        Ok(())
+                    })
+                })
+                .await;
+            assert!(failed.is_err());
+            assert_eq!(
+                storage.get::<Doc>("docs", "from").await.unwrap(),
+                Some(doc("from", 6))
+            );
+            assert!(storage.get::<Doc>("docs", "to").await.unwrap().is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn queries_filter_and_page() {
+        for storage in backends() {
+            for (id, score) in [("a", 1), ("b", 3), ("c", 2)] {
+                storage.update("docs", id, &doc("x", score)).await.unwrap();
+            }
+            storage.update("docs", "d", &doc("y", 5)).await.unwrap();
+
+            let query = Query::new("docs")
+                .eq("name", "x")
+                .order_by_desc("score")
+                .limit(2);
+            let scores: Vec<i64> = storage
+                .query::<Doc>(&query)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|d| d.score)
+                .collect();
+            assert_eq!(scores, [3, 2]);
+        }
+    }
+
+    #[tokio::test]
+    async fn prefix_listing_stays_within_the_prefix() {
+        for storage in backends() {
+            for id in ["g1.a", "g1.b", "g10.a", "g2.a", "g1"] {
+                storage.update("docs", id, &doc(id, 0)).await.unwrap();
+            }
+            let ids: Vec<String> = storage
+                .list_prefix::<Doc>("docs", "g1.")
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect();
+            assert_eq!(ids, ["g1.a", "g1.b"]);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// A filter on a top-level document field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Eq(String, Value),
+    In(String, Vec<Value>),
+}
+
+/// A collection query: documents matching every filter, optionally sorted
+/// and paged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub collection: String,
+    pub filters: Vec<Filter>,
+    /// Field to sort by, and whether to sort descending.
+    pub order_by: Option<(String, bool)>,
+    pub offset: u32,
+    pub limit: Option<u32>,
+}
+
+impl Query {
+    pub fn new(collection: &str) -> Self {
+        Self {
+            collection: collection.to_string(),
+            filters: Vec::new(),
+            order_by: None,
+            offset: 0,
+            limit: None,
+        }
+    }
+
+    pub fn eq(mut self, field: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.filters.push(Filter::Eq(field.into(), value.into()));
+        self
+    }
+
+    pub fn any_of<V: Into<Value>>(
+        mut self,
+        field: impl Into<String>,
+        values: impl IntoIterator<Item = V>,
+    ) -> Self {
+        let values = values.into_iter().map(Into::into).collect();
+        self.filters.push(Filter::In(field.into(), values));
+        self
+    }
+
+    pub fn order_by_desc(mut self, field: impl Into<String>) -> Self {
+        self.order_by = Some((field.into(), true));
+        self
+    }
+
+    pub fn offset(mut self, offset: u32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn limit(mut self, limit: u32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Run the query over a whole collection held as JSON. Like Firestore,
+    /// documents missing the sort field are left out.
+    pub(crate) fn run(&self, docs: Vec<(String, Value)>) -> Vec<Value> {
+        let mut matched: Vec<Value> = docs
+            .into_iter()
+            .map(|(_, doc)| doc)
+            .filter(|doc| self.filters.iter().all(|f| f.matches(doc)))
+            .filter(|doc| {
+                self.order_by
+                    .as_ref()
+                    .is_none_or(|(field, _)| doc.get(field).is_some())
+            })
+            .collect();
+
+        if let Some((field, descending)) = &self.order_by {
+            matched.sort_by(|a, b| {
+                let ordering = compare(&a[field], &b[field]);
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+
+        matched
+            .into_iter()
+            .skip(self.offset as usize)
+            .take(self.limit.map_or(usize::MAX, |l| l as usize))
+            .collect()
+    }
+}
+
+impl Filter {
+    fn matches(&self, doc: &Value) -> bool {
+        match self {
+            Self::Eq(field, value) => doc.get(field) == Some(value),
+            Self::In(field, values) => doc.get(field).is_some_and(|v| values.contains(v)),
+        }
+    }
+}
+
+/// Order JSON field values the way Firestore orders stored ones. Timestamps
+/// are stored as RFC 3339 strings whose fractional seconds vary in length,
+/// so they are compared as instants rather than as text.
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => {
+            match (a.parse::<DateTime<Utc>>(), b.parse::<DateTime<Utc>>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                _ => a.cmp(b),
+            }
+        }
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (a, b) => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn docs() -> Vec<(String, Value)> {
+        [
+            json!({ "guild": "a", "at": "2024-01-01T00:00:00Z" }),
+            json!({ "guild": "a", "at": "2024-01-01T00:00:00.500Z" }),
+            json!({ "guild": "b", "at": "2024-01-02T00:00:00Z" }),
+            json!({ "guild": "a" }),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, doc)| (i.to_string(), doc))
+        .collect()
+    }
+
+    #[test]
+    fn filters_orders_timestamps_and_pages() {
+        let query = Query::new("ledger").eq("guild", "a").order_by_desc("at");
+        let at = |docs: Vec<Value>| -> Vec<Value> {
+            docs.into_iter().map(|d| d["at"].clone()).collect()
+        };
+
+        assert_eq!(
+            at(query.run(docs())),
+            vec![
+                json!("2024-01-01T00:00:00.500Z"),
+                json!("2024-01-01T00:00:00Z")
+            ]
+        );
+        assert_eq!(
+            at(query.clone().offset(1).limit(1).run(docs())),
+            vec![json!("2024-01-01T00:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn in_filter_matches_any_value() {
+        let query = Query::new("ledger").any_of("guild", ["b", "c"]);
+        assert_eq!(query.run(docs()).len(), 1);
+    }
+}
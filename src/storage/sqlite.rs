@@ -0,0 +1,132 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use serde_json::Value;
+
+use crate::storage::PREFIX_END;
+use crate::storage::StorageError;
+use crate::storage::StorageResult;
+use crate::storage::backend::{Backend, Write, merged};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS documents (
+    collection TEXT NOT NULL,
+    id TEXT NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (collection, id)
+)";
+
+/// Keeps documents as JSON rows in a single SQLite file, for self-hosting
+/// without Google credentials.
+pub struct SqliteBackend {
+    conn: Connection,
+}
+
+impl SqliteBackend {
+    /// Open (or create) the database at `path`; `:memory:` gives a private in-memory one.
+    pub fn open(path: &str) -> StorageResult<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute(SCHEMA, [])?;
+        Ok(Self { conn })
+    }
+}
+
+impl Backend for SqliteBackend {
+    fn get(&self, collection: &str, id: &str) -> StorageResult<Option<Value>> {
+        let data: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT data FROM documents WHERE collection = ?1 AND id = ?2",
+                params![collection, id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(data.map(|d| serde_json::from_str(&d)).transpose()?)
+    }
+
+    fn list(&self, collection: &str) -> StorageResult<Vec<(String, Value)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, data FROM documents WHERE collection = ?1 ORDER BY id")?;
+        let rows = stmt
+            .query_map(params![collection], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(|(id, data)| Ok((id, serde_json::from_str(&data)?)))
+            .collect()
+    }
+
+    fn list_prefix(&self, collection: &str, prefix: &str) -> StorageResult<Vec<(String, Value)>> {
+        let end = format!("{prefix}{PREFIX_END}");
+        let mut stmt = self.conn.prepare(
+            "SELECT id, data FROM documents WHERE collection = ?1 AND id >= ?2 AND id <= ?3 ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map(params![collection, prefix, end], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows.into_iter()
+            .map(|(id, data)| Ok((id, serde_json::from_str(&data)?)))
+            .collect()
+    }
+
+    fn apply(&mut self, writes: Vec<Write>) -> StorageResult<()> {
+        // Dropping the transaction on an early return rolls it back
+        let tx = self.conn.transaction()?;
+        for write in writes {
+            match write {
+                Write::Create {
+                    collection,
+                    id,
+                    doc,
+                } => {
+                    let inserted = tx.execute(
+                        "INSERT OR IGNORE INTO documents (collection, id, data) VALUES (?1, ?2, ?3)",
+                        params![collection, id, doc.to_string()],
+                    )?;
+                    if inserted == 0 {
+                        return Err(StorageError::AlreadyExists { collection, id });
+                    }
+                }
+                Write::Set {
+                    collection,
+                    id,
+                    doc,
+                } => upsert(&tx, &collection, &id, &doc)?,
+                Write::Merge {
+                    collection,
+                    id,
+                    doc,
+                    fields,
+                } => {
+                    let current: Option<String> = tx
+                        .query_row(
+                            "SELECT data FROM documents WHERE collection = ?1 AND id = ?2",
+                            params![collection, id],
+                            |row| row.get(0),
+                        )
+                        .optional()?;
+                    let current = current.map(|d| serde_json::from_str(&d)).transpose()?;
+                    upsert(&tx, &collection, &id, &merged(current, doc, Some(&fields)))?;
+                }
+                Write::Delete { collection, id } => {
+                    tx.execute(
+                        "DELETE FROM documents WHERE collection = ?1 AND id = ?2",
+                        params![collection, id],
+                    )?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn upsert(conn: &Connection, collection: &str, id: &str, doc: &Value) -> StorageResult<()> {
+    conn.execute(
+        "INSERT INTO documents (collection, id, data) VALUES (?1, ?2, ?3)
+         ON CONFLICT (collection, id) DO UPDATE SET data = excluded.data",
+        params![collection, id, doc.to_string()],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use poise::serenity_prelude as serenity;
+use tracing::error;
+
+use crate::context::Context;
+use crate::discord::helpers::{mention, rep_label};
+use crate::discord::types::GuildMember;
+use crate::error::{EconomyError, EconomyResult};
+use crate::users::{RepChange, RepReason, UserStoreApi};
+
+/// Block frozen members from moving ℞.
+pub async fn ensure_not_frozen(
+    user_store: &dyn UserStoreApi,
+    member: &GuildMember,
+) -> EconomyResult<()> {
+    if user_store.is_user_frozen(member).await? {
+        return Err(EconomyError::AccountFrozen);
+    }
+    Ok(())
+}
+
+/// Server managers and members with the guild's admin role may use admin commands.
+pub async fn is_admin(ctx: Context<'_>) -> Result<bool, anyhow::Error> {
+    let Some(member) = ctx.author_member().await else {
+        return Ok(false);
+    };
+    if member.permissions.is_some_and(|p| p.manage_guild()) {
+        return Ok(true);
+    }
+    let settings = ctx.data().settings(member.guild_id).await?;
+    Ok(settings
+        .admin_role_id
+        .is_some_and(|id| member.roles.contains(&serenity::RoleId::new(id))))
+}
+
+/// Server administration
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("admin_rep"),
+    check = "is_admin"
+)]
+pub async fn admin(_ctx: Context<'_>) -> Result<(), anyhow::Error> {
+    Ok(())
+}
+
+/// Correct a member's ℞
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "rep",
+    subcommands("grant", "fine", "set", "freeze", "unfreeze")
+)]
+async fn admin_rep(_ctx: Context<'_>) -> Result<(), anyhow::Error> {
+    Ok(())
+}
+
+/// Give ℞ to a member
+#[poise::command(slash_command, guild_only)]
+async fn grant(
+    ctx: Context<'_>,
+    #[description = "Member to grant to"] user: serenity::User,
+    #[description = "Amount to grant"]
+    #[min = 1]
+    #[max = 1_000_000_000]
+    amount: i64,
+    #[description = "Why this grant is being made"] reason: String,
+) -> Result<(), anyhow::Error> {
+    let target = target_member(ctx, &user).await?;
+    let change = RepChange::new(RepReason::AdminGrant).with_note(&reason);
+    ctx.data()
+        .user_store
+        .increment_user_rep(&target, amount, &change)
+        .await?;
+
+    let amount_label = rep_label(amount, false);
+    let target_mention = mention(&target.id);
+    let action = format!("granted {amount_label} to {target_mention}");
+    report(ctx, &target, action, &reason).await
+}
+
+/// Take ℞ from a member
+#[poise::command(slash_command, guild_only)]
+async fn fine(
+    ctx: Context<'_>,
+    #[description = "Member to fine"] user: serenity::User,
+    #[description = "Amount to take"]
+    #[min = 1]
+    #[max = 1_000_000_000]
+    amount: i64,
+    #[description = "Why this fine is being made"] reason: String,
+) -> Result<(), anyhow::Error> {
+    let target = target_member(ctx, &user).await?;
+    let change = RepChange::new(RepReason::AdminFine).with_note(&reason);
+    ctx.data()
+        .user_store
+        .increment_user_rep(&target, -amount, &change)
+        .await?;
+
+    let amount_label = rep_label(amount, false);
+    let target_mention = mention(&target.id);
+    let action = format!("fined {target_mention} {amount_label}");
+    report(ctx, &target, action, &reason).await
+}
+
+/// Overwrite a member's earned ℞ (their balance excluding join-date tenure)
+#[poise::command(slash_command, guild_only)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Member to adjust"] user: serenity::User,
+    #[description = "New earned rep, excluding join-date tenure"]
+    #[min = -1_000_000_000]
+    #[max = 1_000_000_000]
+    offset: i64,
+    #[description = "Why this adjustment is being made"] reason: String,
+) -> Result<(), anyhow::Error> {
+    let target = target_member(ctx, &user).await?;
+    let change = RepChange::new(RepReason::AdminSet).with_note(&reason);
+    ctx.data()
+        .user_store
+        .set_user_rep_offset(&target, offset, &change)
+        .await?;
+
+    let offset_label = rep_label(offset, false);
+    let target_mention = mention(&target.id);
+    let action = format!("set {target_mention}'s earned rep to {offset_label}");
+    report(ctx, &target, action, &reason).await
+}
+
+/// Stop a member from sending or betting ℞
+#[poise::command(slash_command, guild_only)]
+async fn freeze(
+    ctx: Context<'_>,
+    #[description = "Member to freeze"] user: serenity::User,
+    #[description = "Why this member is being frozen"] reason: String,
+) -> Result<(), anyhow::Error> {
+    let target = target_member(ctx, &user).await?;
+    ctx.data().user_store.set_user_frozen(&target, true).await?;
+    let action = format!("froze {}", mention(&target.id));
+    report(ctx, &target, action, &reason).await
+}
+
+/// Allow a frozen member to send and bet ℞ again
+#[poise::command(slash_command, guild_only)]
+async fn unfreeze(
+    ctx: Context<'_>,
+    #[description = "Member to unfreeze"] user: serenity::User,
+    #[description = "Why this member is being unfrozen"] reason: String,
+) -> Result<(), anyhow::Error> {
+    let target = target_member(ctx, &user).await?;
+    ctx.data()
+        .user_store
+        .set_user_frozen(&target, false)
+        .await?;
+    let action = format!("unfroze {}", mention(&target.id));
+    report(ctx, &target, action, &reason).await
+}
+
+async fn target_member(ctx: Context<'_>, user: &serenity::User) -> anyhow::Result<GuildMember> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+    let member = guild_id.member(ctx.serenity_context(), user.id).await?;
+    Ok(GuildMember::from_serenity(
+        guild_id,
+        user,
+        member.joined_at,
+        member.nick.as_deref(),
+    ))
+}
+
+/// Confirm the action to the admin and post it to the guild's audit log channel, if set.
+async fn report(
+    ctx: Context<'_>,
+    target: &GuildMember,
+    action: String,
+    reason: &str,
+) -> Result<(), anyhow::Error> {
+    let rep = ctx.data().user_store.get_user_rep(target).await?;
+    let admin = mention(ctx.author().id);
+    let username = &target.username;
+    let rep_label = rep_label(rep, false);
+    let msg = format!("{admin} {action}. Reason: {reason}\n{username} now has {rep_label}");
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(msg.clone())
+            .ephemeral(true),
+    )
+    .await?;
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+    if let Some(channel_id) = ctx.data().settings(guild_id).await?.admin_log_channel_id {
+        let channel = serenity::ChannelId::new(channel_id);
+        let audit = serenity::CreateMessage::new()
+            .content(msg)
+            .allowed_mentions(serenity::CreateAllowedMentions::new());
+        if let Err(e) = channel.send_message(ctx.serenity_context(), audit).await {
+            error!(error = %e, "Failed to post admin audit message");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::context::Context;
+use crate::discord::helpers::rep_label;
+use crate::discord::types::GuildMember;
+use crate::util::dates::{is_today, is_yesterday};
+
+/// Consecutive days beyond this no longer increase the reward.
+pub const DAILY_STREAK_CAP: i64 = 7;
+
+/// Result of a successful daily claim.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DailyClaim {
+    pub reward: i64,
+    pub streak: i64,
+}
+
+/// Compute the streak after claiming now. Returns None if the member already claimed today.
+/// Claiming the day after the last claim extends the streak; any gap resets it to 1.
+pub fn next_streak(tz: Tz, last_claim: Option<DateTime<Utc>>, streak: i64) -> Option<i64> {
+    match last_claim {
+        Some(last) if is_today(tz, last) => None,
+        Some(last) if is_yesterday(tz, last) => Some(streak + 1),
+        _ => Some(1),
+    }
+}
+
+/// The reward scales linearly with the streak, up to `DAILY_STREAK_CAP` days.
+pub fn daily_reward(base_reward: i64, streak: i64) -> i64 {
+    base_reward.saturating_mul(streak.clamp(1, DAILY_STREAK_CAP))
+}
+
+/// Claim your daily ℞ allowance
+#[poise::command(slash_command, guild_only)]
+pub async fn daily(ctx: Context<'_>) -> Result<(), anyhow::Error> {
+    let data = ctx.data();
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+
+    let member_data = ctx
+        .author_member()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("Could not get member info"))?;
+    let member = GuildMember::from_serenity(
+        guild_id,
+        ctx.author(),
+        member_data.joined_at,
+        member_data.nick.as_deref(),
+    );
+
+    // Already-claimed surfaces as DailyLimitReached, rendered by the on_error hook
+    let settings = data.settings(guild_id).await?;
+    let claim = data
+        .user_store
+        .claim_daily(&member, settings.timezone, settings.daily_reward)
+        .await?;
+
+    let username = &member.username;
+    let reward = rep_label(claim.reward, true);
+    let streak = claim.streak;
+    let msg = if streak > 1 {
+        format!("{username} claimed their daily {reward}. Streak: **{streak} days** 🔥")
+    } else {
+        format!("{username} claimed their daily {reward}")
+    };
+    ctx.send(poise::CreateReply::default().content(msg)).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn first_claim_starts_streak() {
+        assert_eq!(next_streak(chrono_tz::UTC, None, 0), Some(1));
+    }
+
+    #[test]
+    fn second_claim_today_is_rejected() {
+        assert_eq!(next_streak(chrono_tz::UTC, Some(Utc::now()), 3), None);
+    }
+
+    #[test]
+    fn claim_after_yesterday_extends_streak() {
+        let yesterday = Utc::now() - Duration::days(1);
+        assert_eq!(next_streak(chrono_tz::UTC, Some(yesterday), 3), Some(4));
+    }
+
+    #[test]
+    fn missed_day_resets_streak() {
+        let two_days_ago = Utc::now() - Duration::days(2);
+        assert_eq!(next_streak(chrono_tz::UTC, Some(two_days_ago), 3), Some(1));
+    }
+
+    #[test]
+    fn reward_is_capped() {
+        assert_eq!(daily_reward(10, 1), 10);
+        assert_eq!(daily_reward(10, 3), 30);
+        assert_eq!(daily_reward(10, 30), 10 * DAILY_STREAK_CAP);
+        assert_eq!(daily_reward(i64::MAX, 2), i64::MAX);
+    }
+}
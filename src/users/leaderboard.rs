@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use poise::serenity_prelude as serenity;
+
+use crate::discord::types::GuildMember;
+use crate::users::UserStoreApi;
+use crate::users::store::calculate_rep_from_joined_date;
+
+/// Discord's maximum page size for listing guild members.
+const MEMBER_PAGE_LIMIT: u64 = 1000;
+/// How long a guild's member list is reused, e.g. while paging the leaderboard.
+const MEMBER_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Recently listed guild members, so paging through the leaderboard does not
+/// list every member from Discord again on each button click. Reputation
+/// offsets are still read fresh.
+#[derive(Clone, Default)]
+pub struct MemberCache {
+    guilds: Arc<Mutex<HashMap<serenity::GuildId, CachedMembers>>>,
+}
+
+/// When the members were listed, and the members.
+type CachedMembers = (Instant, Arc<Vec<GuildMember>>);
+
+impl MemberCache {
+    fn get(&self, guild_id: serenity::GuildId, now: Instant) -> Option<Arc<Vec<GuildMember>>> {
+        let guilds = self.guilds.lock().expect("member cache poisoned");
+        guilds
+            .get(&guild_id)
+            .filter(|(fetched_at, _)| now.duration_since(*fetched_at) < MEMBER_CACHE_TTL)
+            .map(|(_, members)| members.clone())
+    }
+
+    fn insert(
+        &self,
+        guild_id: serenity::GuildId,
+        members: Vec<GuildMember>,
+        now: Instant,
+    ) -> Arc<Vec<GuildMember>> {
+        let members = Arc::new(members);
+        let mut guilds = self.guilds.lock().expect("member cache poisoned");
+        guilds.retain(|_, (fetched_at, _)| now.duration_since(*fetched_at) < MEMBER_CACHE_TTL);
+        guilds.insert(guild_id, (now, members.clone()));
+        members
+    }
+}
+
+/// A single member's position on the leaderboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standing {
+    pub user_id: String,
+    pub username: String,
+    pub rep: i64,
+}
+
+/// Build the ranked standings for a guild. When `earned_only` is set, members are
+/// ranked by `reputation_offset` alone, excluding the join-date base.
+pub async fn guild_standings(
+    http: &serenity::Http,
+    user_store: &dyn UserStoreApi,
+    member_cache: &MemberCache,
+    guild_id: serenity::GuildId,
+    earned_only: bool,
+) -> anyhow::Result<Vec<Standing>> {
+    let members = match member_cache.get(guild_id, Instant::now()) {
+        Some(members) => members,
+        None => {
+            let members = list_guild_members(http, guild_id).await?;
+            member_cache.insert(guild_id, members, Instant::now())
+        }
+    };
+    let offsets = user_store
+        .get_guild_reputation_offsets(&guild_id.to_string())
+        .await?;
+    Ok(rank_standings(&members, &offsets, earned_only))
+}
+
+/// Page through every non-bot member of the guild.
+async fn list_guild_members(
+    http: &serenity::Http,
+    guild_id: serenity::GuildId,
+) -> anyhow::Result<Vec<GuildMember>> {
+    let mut members = Vec::new();
+    let mut after = None;
+    loop {
+        let page = guild_id
+            .members(http, Some(MEMBER_PAGE_LIMIT), after)
+            .await?;
+        let fetched = page.len() as u64;
+        after = page.last().map(|m| m.user.id);
+        members.extend(page.iter().filter(|m| !m.user.bot).map(|m| {
+            GuildMember::from_serenity(guild_id, &m.user, m.joined_at, m.nick.as_deref())
+        }));
+        if fetched < MEMBER_PAGE_LIMIT {
+            break;
+        }
+    }
+    Ok(members)
+}
+
+/// Rank members by reputation, highest first. Ties are broken by username.
+pub fn rank_standings(
+    members: &[GuildMember],
+    offsets: &HashMap<String, i64>,
+    earned_only: bool,
+) -> Vec<Standing> {
+    let mut standings: Vec<Standing> = members
+        .iter()
+        .map(|member| {
+            let offset = offsets.get(&member.id).copied().unwrap_or(0);
+            let rep = if earned_only {
+                offset
+            } else {
+                calculate_rep_from_joined_date(member) + offset
+            };
+            Standing {
+                user_id: member.id.clone(),
+                username: member.username.clone(),
+                rep,
+            }
+        })
+        .collect();
+    standings.sort_by(|a, b| b.rep.cmp(&a.rep).then_with(|| a.username.cmp(&b.username)));
+    standings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    fn member(id: &str, days_ago: Option<i64>) -> GuildMember {
+        GuildMember {
+            id: id.to_string(),
+            guild_id: "guild1".to_string(),
+            username: id.to_string(),
+            joined_at: days_ago.map(|d| Utc::now() - Duration::days(d)),
+        }
+    }
+
+    #[test]
+    fn ranks_by_total_rep() {
+        let members = [member("old", Some(100)), member("new", Some(1))];
+        let offsets = HashMap::from([("new".to_string(), 50)]);
+        let standings = rank_standings(&members, &offsets, false);
+        assert_eq!(standings[0].user_id, "old");
+        assert_eq!(standings[0].rep, 100);
+        assert_eq!(standings[1].rep, 51);
+    }
+
+    #[test]
+    fn earned_only_ignores_tenure() {
+        let members = [member("old", Some(100)), member("new", Some(1))];
+        let offsets = HashMap::from([("new".to_string(), 50)]);
+        let standings = rank_standings(&members, &offsets, true);
+        assert_eq!(standings[0].user_id, "new");
+        assert_eq!(standings[1].rep, 0);
+    }
+
+    #[test]
+    fn cached_members_expire() {
+        let cache = MemberCache::default();
+        let guild_id = serenity::GuildId::new(1);
+        let now = Instant::now();
+        cache.insert(guild_id, vec![member("a", None)], now);
+
+        assert_eq!(cache.get(guild_id, now).unwrap().len(), 1);
+        assert!(cache.get(serenity::GuildId::new(2), now).is_none());
+        assert!(cache.get(guild_id, now + MEMBER_CACHE_TTL).is_none());
+    }
+
+    #[test]
+    fn ties_break_by_username() {
+        let members = [member("b", None), member("a", None)];
+        let standings = rank_standings(&members, &HashMap::new(), false);
+        assert_eq!(standings[0].user_id, "a");
+    }
+}
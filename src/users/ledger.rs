@@ -0,0 +1,192 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::discord::types::GuildMember;
+
+pub const LEDGER_COLLECTION: &str = "ledger";
+
+/// Why a member's ℞ changed. Stored on every ledger entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepReason {
+    #[serde(rename = "rep:send")]
+    RepSend,
+    #[serde(rename = "guess:reward")]
+    GuessReward,
+    #[serde(rename = "daily:claim")]
+    DailyClaim,
+    #[serde(rename = "roulette:buy-in")]
+    RouletteBuyIn,
+    #[serde(rename = "roulette:win")]
+    RouletteWin,
+    #[serde(rename = "roulette:refund")]
+    RouletteRefund,
+    #[serde(rename = "sardines:buy-in")]
+    SardinesBuyIn,
+    #[serde(rename = "sardines:win")]
+    SardinesWin,
+    #[serde(rename = "sardines:refund")]
+    SardinesRefund,
+    #[serde(rename = "admin:grant")]
+    AdminGrant,
+    #[serde(rename = "admin:fine")]
+    AdminFine,
+    #[serde(rename = "admin:set")]
+    AdminSet,
+}
+
+impl fmt::Display for RepReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RepSend => write!(f, "rep:send"),
+            Self::GuessReward => write!(f, "guess:reward"),
+            Self::DailyClaim => write!(f, "daily:claim"),
+            Self::RouletteBuyIn => write!(f, "roulette:buy-in"),
+            Self::RouletteWin => write!(f, "roulette:win"),
+            Self::RouletteRefund => write!(f, "roulette:refund"),
+            Self::SardinesBuyIn => write!(f, "sardines:buy-in"),
+            Self::SardinesWin => write!(f, "sardines:win"),
+            Self::SardinesRefund => write!(f, "sardines:refund"),
+            Self::AdminGrant => write!(f, "admin:grant"),
+            Self::AdminFine => write!(f, "admin:fine"),
+            Self::AdminSet => write!(f, "admin:set"),
+        }
+    }
+}
+
+/// Tags a batch of ℞ movements with a reason and, for games, the game id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepChange {
+    pub reason: RepReason,
+    pub game_id: Option<String>,
+    pub note: Option<String>,
+    /// Username on the other side of a transfer, if any.
+    pub counterparty: Option<String>,
+}
+
+impl RepChange {
+    pub fn new(reason: RepReason) -> Self {
+        Self {
+            reason,
+            game_id: None,
+            note: None,
+            counterparty: None,
+        }
+    }
+
+    pub fn game(reason: RepReason, game_id: &str) -> Self {
+        Self {
+            reason,
+            game_id: Some(game_id.to_string()),
+            note: None,
+            counterparty: None,
+        }
+    }
+
+    /// Attach a free-text note, e.g. the reason an admin gave for an adjustment.
+    pub fn with_note(self, note: &str) -> Self {
+        Self {
+            note: Some(note.to_string()),
+            ..self
+        }
+    }
+
+    /// Name the member on the other side of a transfer.
+    pub fn with_counterparty(self, member: &GuildMember) -> Self {
+        Self {
+            counterparty: Some(member.username.clone()),
+            ..self
+        }
+    }
+}
+
+/// An immutable record of a single change to a member's reputation offset.
+/// Written in the same transaction as the offset increment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    pub id: String,
+    pub guild_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub delta: i64,
+    pub reason: RepReason,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Username on the other side of a transfer, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<String>,
+    #[serde(with = "firestore::serialize_as_timestamp")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl LedgerEntry {
+    pub fn new(member: &GuildMember, delta: i64, change: &RepChange) -> Self {
+        Self {
+            id: nanoid::nanoid!(),
+            guild_id: member.guild_id.clone(),
+            user_id: member.id.clone(),
+            username: member.username.clone(),
+            delta,
+            reason: change.reason,
+            game_id: change.game_id.clone(),
+            note: change.note.clone(),
+            counterparty: change.counterparty.clone(),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reason_serializes_as_display_string() {
+        let reasons = [
+            RepReason::RepSend,
+            RepReason::GuessReward,
+            RepReason::DailyClaim,
+            RepReason::RouletteBuyIn,
+            RepReason::RouletteWin,
+            RepReason::RouletteRefund,
+            RepReason::SardinesBuyIn,
+            RepReason::SardinesWin,
+            RepReason::SardinesRefund,
+            RepReason::AdminGrant,
+            RepReason::AdminFine,
+            RepReason::AdminSet,
+        ];
+        for reason in reasons {
+            let json = serde_json::to_value(reason).unwrap();
+            assert_eq!(json, serde_json::Value::String(reason.to_string()));
+        }
+    }
+
+    fn member(id: &str) -> GuildMember {
+        GuildMember {
+            id: id.to_string(),
+            guild_id: "guild1".to_string(),
+            username: id.to_string(),
+            joined_at: None,
+        }
+    }
+
+    #[test]
+    fn entries_record_only_an_explicit_counterparty() {
+        let send = RepChange::new(RepReason::RepSend).with_counterparty(&member("b"));
+        let entry = LedgerEntry::new(&member("a"), -10, &send);
+        assert_eq!(entry.counterparty.as_deref(), Some("b"));
+
+        // A refund that happens to cancel out is still not a transfer
+        let refund = RepChange::game(RepReason::RouletteRefund, "game1");
+        assert!(
+            LedgerEntry::new(&member("a"), 10, &refund)
+                .counterparty
+                .is_none()
+        );
+    }
+}
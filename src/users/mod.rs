@@ -1,4 +1,9 @@
+pub mod admin;
+pub mod daily;
+pub mod leaderboard;
+pub mod ledger;
 pub mod rep;
 pub mod store;
 
+pub use ledger::{RepChange, RepReason};
 pub use store::{UserStore, UserStoreApi};
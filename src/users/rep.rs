@@ -1,13 +1,26 @@
 use poise::serenity_prelude as serenity;
-use tracing::error;
+use serenity::{
+    CreateActionRow, CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage,
+};
 
-use crate::context::Context;
-use crate::discord::helpers::rep_label;
-use crate::discord::types::GuildMember;
-use crate::util::dates::get_day_string;
+use crate::context::{AppContext, Context};
+use crate::discord::helpers::{encode_custom_id, parse_custom_id, rep_label};
+use crate::discord::types::{GuildMember, InteractionType};
+use crate::users::admin::ensure_not_frozen;
+use crate::users::leaderboard::{Standing, guild_standings};
+use crate::users::ledger::LedgerEntry;
+use crate::users::{RepChange, RepReason, UserStoreApi};
+use crate::util::dates::{format_distance_to_now, get_day_string};
+
+const HISTORY_PAGE_SIZE: u32 = 10;
+const LEADERBOARD_PAGE_SIZE: usize = 10;
 
 /// Server Reputation (℞); currency for games
-#[poise::command(slash_command, guild_only, subcommands("view", "send"))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("view", "send", "history", "leaderboard")
+)]
 pub async fn rep(_ctx: Context<'_>) -> Result<(), anyhow::Error> {
     Ok(())
 }
@@ -37,9 +50,10 @@ async fn view(
     );
 
     let rep = ctx.data().user_store.get_user_rep(&member).await?;
+    let timezone = ctx.data().settings(guild_id).await?.timezone;
 
     let joined = match member.joined_at {
-        Some(dt) => get_day_string(ctx.data().config.discord.timezone, dt),
+        Some(dt) => get_day_string(timezone, dt),
         None => "<join date missing>".to_string(),
     };
 
@@ -96,6 +110,7 @@ async fn send(
         sender_member_data.joined_at,
         sender_member_data.nick.as_deref(),
     );
+    ensure_not_frozen(ctx.data().user_store.as_ref(), &sender).await?;
 
     let receiver_data = guild_id.member(ctx.serenity_context(), to.id).await?;
     let receiver = GuildMember::from_serenity(
@@ -109,40 +124,342 @@ async fn send(
     let receiver_name = receiver.username.clone();
 
     let amount_label = rep_label(amount, false);
-    let initial_msg = format!("{sender_name} is sending {receiver_name} {amount_label}");
-    let handle = ctx
-        .send(poise::CreateReply::default().content(initial_msg))
-        .await?;
 
-    match ctx
-        .data()
+    // Insufficient funds surface as an EconomyError, rendered by the on_error hook
+    ctx.data()
         .user_store
-        .increment_user_reps(&[(sender.clone(), -amount), (receiver.clone(), amount)])
+        .transfer_rep(
+            &sender,
+            &receiver,
+            amount,
+            &RepChange::new(RepReason::RepSend),
+        )
+        .await?;
+
+    let sender_rep = ctx.data().user_store.get_user_rep(&sender).await?;
+    let receiver_rep = ctx.data().user_store.get_user_rep(&receiver).await?;
+
+    let sender_rep_label = rep_label(sender_rep, false);
+    let receiver_rep_label = rep_label(receiver_rep, false);
+    let msg = format!(
+        "{sender_name} sent {receiver_name} {amount_label}.\n{sender_name}: {sender_rep_label}\t{receiver_name}: {receiver_rep_label}"
+    );
+    ctx.send(poise::CreateReply::default().content(msg)).await?;
+
+    Ok(())
+}
+
+/// View your recent ℞ movements
+#[poise::command(slash_command, guild_only)]
+async fn history(ctx: Context<'_>) -> Result<(), anyhow::Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+
+    let member_data = ctx
+        .author_member()
         .await
-    {
-        Ok(_) => {
-            let sender_rep = ctx.data().user_store.get_user_rep(&sender).await?;
-            let receiver_rep = ctx.data().user_store.get_user_rep(&receiver).await?;
-
-            let sender_rep_label = rep_label(sender_rep, false);
-            let receiver_rep_label = rep_label(receiver_rep, false);
-            let msg = format!(
-                "{sender_name} sent {receiver_name} {amount_label}.\n{sender_name}: {sender_rep_label}\t{receiver_name}: {receiver_rep_label}"
-            );
-            handle
-                .edit(ctx, poise::CreateReply::default().content(msg))
-                .await?;
+        .ok_or_else(|| anyhow::anyhow!("Could not get member info"))?;
+    let member = GuildMember::from_serenity(
+        guild_id,
+        ctx.author(),
+        member_data.joined_at,
+        member_data.nick.as_deref(),
+    );
+
+    let (content, row) = history_page(ctx.data().user_store.as_ref(), &member, 0).await?;
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .components(vec![row])
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Handle the REP_HISTORY Previous/Next button interaction.
+pub async fn handle_history_page(
+    ctx: &serenity::Context,
+    interaction: &serenity::ComponentInteraction,
+    data: &AppContext,
+) -> Result<(), anyhow::Error> {
+    let custom_id = &interaction.data.custom_id;
+    let (_, id) = parse_custom_id(custom_id);
+    let (user_id, page) = id
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Malformed history id: {id}"))?;
+    let page: u32 = page.parse()?;
+
+    if interaction.user.id.to_string() != user_id {
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("You can only page through your own history")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let guild_id = interaction
+        .guild_id
+        .ok_or_else(|| anyhow::anyhow!("Must be in a guild"))?;
+    let member_info = interaction
+        .member
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No member data"))?;
+    let member = GuildMember::from_serenity(
+        guild_id,
+        &interaction.user,
+        member_info.joined_at,
+        member_info.nick.as_deref(),
+    );
+
+    let (content, row) = history_page(data.user_store.as_ref(), &member, page).await?;
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(vec![row]),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Rank server members by ℞
+#[poise::command(slash_command, guild_only)]
+async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "Rank by earned rep only, excluding join-date tenure (default: false)"]
+    earned: Option<bool>,
+    #[description = "If true response is visible to everyone (default: false)"] public: Option<
+        bool,
+    >,
+) -> Result<(), anyhow::Error> {
+    let earned_only = earned.unwrap_or(false);
+    let is_public = public.unwrap_or(false);
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("Not in a guild"))?;
+
+    // Listing guild members can exceed the 3 second interaction deadline
+    if is_public {
+        ctx.defer().await?;
+    } else {
+        ctx.defer_ephemeral().await?;
+    }
+
+    let standings = guild_standings(
+        &ctx.serenity_context().http,
+        ctx.data().user_store.as_ref(),
+        &ctx.data().member_cache,
+        guild_id,
+        earned_only,
+    )
+    .await?;
+    let caller_id = ctx.author().id.to_string();
+    let (content, row) = leaderboard_page(&standings, &caller_id, earned_only, 0);
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .components(vec![row])
+            .ephemeral(!is_public),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Handle the REP_LEADERBOARD Previous/Next button interaction.
+pub async fn handle_leaderboard_page(
+    ctx: &serenity::Context,
+    interaction: &serenity::ComponentInteraction,
+    data: &AppContext,
+) -> Result<(), anyhow::Error> {
+    let custom_id = &interaction.data.custom_id;
+    let (_, id) = parse_custom_id(custom_id);
+    let (mode, page) = id
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("Malformed leaderboard id: {id}"))?;
+    let earned_only = mode == "earned";
+    let page: usize = page.parse()?;
+
+    let guild_id = interaction
+        .guild_id
+        .ok_or_else(|| anyhow::anyhow!("Must be in a guild"))?;
+
+    let standings = guild_standings(
+        &ctx.http,
+        data.user_store.as_ref(),
+        &data.member_cache,
+        guild_id,
+        earned_only,
+    )
+    .await?;
+    let caller_id = interaction.user.id.to_string();
+    let (content, row) = leaderboard_page(&standings, &caller_id, earned_only, page);
+    interaction
+        .create_response(
+            ctx,
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .components(vec![row]),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Build the message content and paging buttons for one page of the leaderboard.
+fn leaderboard_page(
+    standings: &[Standing],
+    caller_id: &str,
+    earned_only: bool,
+    page: usize,
+) -> (String, CreateActionRow) {
+    let mode = if earned_only { "earned" } else { "total" };
+    let start = page * LEADERBOARD_PAGE_SIZE;
+    let has_next = standings.len() > start + LEADERBOARD_PAGE_SIZE;
+
+    let lines: Vec<String> = standings
+        .iter()
+        .enumerate()
+        .skip(start)
+        .take(LEADERBOARD_PAGE_SIZE)
+        .map(|(i, s)| {
+            let rank = i + 1;
+            let username = &s.username;
+            let rep = rep_label(s.rep, false);
+            format!("{rank}. {username} {rep}")
+        })
+        .collect();
+    let lines = lines.join("\n");
+
+    let own_rank = match standings.iter().position(|s| s.user_id == caller_id) {
+        Some(i) => {
+            let rank = i + 1;
+            let total = standings.len();
+            let rep = rep_label(standings[i].rep, true);
+            format!("Your rank: #{rank} of {total} with {rep}")
         }
-        Err(e) => {
-            error!("error updating rep: {e:?}");
-            handle
-                .edit(
-                    ctx,
-                    poise::CreateReply::default().content(format!("Error: {e}")),
-                )
-                .await?;
+        None => "You are not ranked".to_string(),
+    };
+
+    let page_number = page + 1;
+    let content =
+        format!("## \u{211e} Leaderboard ({mode}, page {page_number})\n{lines}\n\n{own_rank}");
+
+    let prev = CreateButton::new(encode_custom_id(
+        InteractionType::RepLeaderboard,
+        &format!("{mode}:{}", page.saturating_sub(1)),
+    ))
+    .label("Previous")
+    .disabled(page == 0);
+    let next = CreateButton::new(encode_custom_id(
+        InteractionType::RepLeaderboard,
+        &format!("{mode}:{}", page + 1),
+    ))
+    .label("Next")
+    .disabled(!has_next);
+
+    (content, CreateActionRow::Buttons(vec![prev, next]))
+}
+
+/// Build the message content and paging buttons for one page of a member's history.
+async fn history_page(
+    user_store: &dyn UserStoreApi,
+    member: &GuildMember,
+    page: u32,
+) -> anyhow::Result<(String, CreateActionRow)> {
+    // Fetch one extra entry to know whether a next page exists
+    let mut entries = user_store
+        .get_rep_history(member, page * HISTORY_PAGE_SIZE, HISTORY_PAGE_SIZE + 1)
+        .await?;
+    let has_next = entries.len() > HISTORY_PAGE_SIZE as usize;
+    entries.truncate(HISTORY_PAGE_SIZE as usize);
+
+    let username = &member.username;
+    let content = if entries.is_empty() {
+        format!("{username} has no \u{211e} history on this page")
+    } else {
+        let lines: Vec<String> = entries.iter().map(format_history_entry).collect();
+        let lines = lines.join("\n");
+        let page_number = page + 1;
+        format!("**{username}'s \u{211e} history** (page {page_number})\n{lines}")
+    };
+
+    let prev = CreateButton::new(encode_custom_id(
+        InteractionType::RepHistory,
+        &format!("{}:{}", member.id, page.saturating_sub(1)),
+    ))
+    .label("Previous")
+    .disabled(page == 0);
+    let next = CreateButton::new(encode_custom_id(
+        InteractionType::RepHistory,
+        &format!("{}:{}", member.id, page + 1),
+    ))
+    .label("Next")
+    .disabled(!has_next);
+
+    Ok((content, CreateActionRow::Buttons(vec![prev, next])))
+}
+
+/// Format a single ledger entry as one line of the history view.
+fn format_history_entry(entry: &LedgerEntry) -> String {
+    let sign = if entry.delta < 0 { "-" } else { "+" };
+    let amount = rep_label(entry.delta.abs(), true);
+    let reason = &entry.reason;
+    let when = format_distance_to_now(entry.created_at);
+    match &entry.counterparty {
+        Some(other) if entry.delta < 0 => format!("{sign}{amount} {reason} to {other} {when}"),
+        Some(other) => format!("{sign}{amount} {reason} from {other} {when}"),
+        None => format!("{sign}{amount} {reason} {when}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn entry(delta: i64, reason: RepReason, counterparty: Option<&str>) -> LedgerEntry {
+        LedgerEntry {
+            id: "entry".to_string(),
+            guild_id: "guild1".to_string(),
+            user_id: "user1".to_string(),
+            username: "user1".to_string(),
+            delta,
+            reason,
+            game_id: None,
+            note: None,
+            counterparty: counterparty.map(str::to_string),
+            created_at: Utc::now(),
         }
     }
 
-    Ok(())
+    #[test]
+    fn history_entry_shows_sign_and_reason() {
+        let line = format_history_entry(&entry(-50, RepReason::RouletteBuyIn, None));
+        assert!(line.starts_with("-**\u{211e}50**"));
+        assert!(line.contains("roulette:buy-in"));
+    }
+
+    #[test]
+    fn history_entry_shows_transfer_direction() {
+        let sent = format_history_entry(&entry(-5, RepReason::RepSend, Some("bob")));
+        assert!(sent.contains("to bob"));
+        let received = format_history_entry(&entry(5, RepReason::RepSend, Some("alice")));
+        assert!(received.contains("from alice"));
+    }
 }
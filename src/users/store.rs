@@ -1,37 +1,78 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, Utc};
-use firestore::*;
+use chrono_tz::Tz;
+use firestore::{path_camel_case, paths_camel_case};
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::discord::types::GuildMember;
-use crate::firebase::FirestoreStore;
+use crate::error::{EconomyError, EconomyResult};
+use crate::storage::{Collection, Query, Storage, StorageResult, Transaction};
+use crate::users::daily::{DailyClaim, daily_reward, next_streak};
+use crate::users::ledger::{LEDGER_COLLECTION, LedgerEntry, RepChange, RepReason};
 
 const COLLECTION: &str = "users";
 
 /// Trait abstracting user-store operations, enabling mock implementations in tests.
 #[async_trait::async_trait]
 pub trait UserStoreApi: Send + Sync {
-    async fn get_user_rep(&self, member: &GuildMember) -> anyhow::Result<i64>;
-    async fn increment_user_rep(&self, member: &GuildMember, offset: i64) -> anyhow::Result<()>;
-    async fn increment_user_reps(&self, updates: &[(GuildMember, i64)]) -> anyhow::Result<()>;
+    async fn get_user_rep(&self, member: &GuildMember) -> EconomyResult<i64>;
+    async fn increment_user_rep(
+        &self,
+        member: &GuildMember,
+        offset: i64,
+        change: &RepChange,
+    ) -> EconomyResult<()>;
+    async fn transfer_rep(
+        &self,
+        from: &GuildMember,
+        to: &GuildMember,
+        amount: i64,
+        change: &RepChange,
+    ) -> EconomyResult<()>;
+    async fn set_user_rep_offset(
+        &self,
+        member: &GuildMember,
+        offset: i64,
+        change: &RepChange,
+    ) -> EconomyResult<()>;
+    async fn is_user_frozen(&self, member: &GuildMember) -> EconomyResult<bool>;
+    async fn set_user_frozen(&self, member: &GuildMember, frozen: bool) -> EconomyResult<()>;
+    async fn claim_daily(
+        &self,
+        member: &GuildMember,
+        tz: Tz,
+        base_reward: i64,
+    ) -> EconomyResult<DailyClaim>;
+    async fn get_rep_history(
+        &self,
+        member: &GuildMember,
+        offset: u32,
+        limit: u32,
+    ) -> EconomyResult<Vec<LedgerEntry>>;
+    async fn get_guild_reputation_offsets(
+        &self,
+        guild_id: &str,
+    ) -> EconomyResult<HashMap<String, i64>>;
     async fn get_user_last_guess(
         &self,
         member: &GuildMember,
-    ) -> anyhow::Result<Option<DateTime<Utc>>>;
+    ) -> EconomyResult<Option<DateTime<Utc>>>;
     async fn set_user_last_guess(
         &self,
         member: &GuildMember,
         last_guess_date: DateTime<Utc>,
-    ) -> anyhow::Result<()>;
+    ) -> EconomyResult<()>;
     async fn get_user_last_sardines(
         &self,
         member: &GuildMember,
-    ) -> anyhow::Result<Option<DateTime<Utc>>>;
+    ) -> EconomyResult<Option<DateTime<Utc>>>;
     async fn set_user_last_sardines(
         &self,
         member: &GuildMember,
         last_sardines_date: DateTime<Utc>,
-    ) -> anyhow::Result<()>;
+    ) -> EconomyResult<()>;
 }
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -45,22 +86,37 @@ pub struct User {
     pub last_sardines_date: Option<DateTime<Utc>>,
     #[serde(default)]
     pub reputation_offset: i64,
+    #[serde(default)]
+    #[serde(with = "firestore::serialize_as_optional_timestamp")]
+    pub last_daily_date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub daily_streak: i64,
+    #[serde(default)]
+    pub frozen: bool,
+}
+
+/// Projection of a user document holding just the reputation offset.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UserOffsetRow {
+    #[serde(default)]
+    reputation_offset: i64,
 }
 
 #[derive(Clone)]
 pub struct UserStore {
-    store: FirestoreStore,
+    store: Collection,
 }
 
 impl UserStore {
-    pub fn new(db: FirestoreDb) -> Self {
+    pub fn new(storage: Storage) -> Self {
         Self {
-            store: FirestoreStore::new(db, COLLECTION),
+            store: Collection::new(storage, COLLECTION),
         }
     }
 
     /// Get a user document, initializing it if it doesn't exist.
-    pub async fn get_user(&self, member: &GuildMember) -> anyhow::Result<User> {
+    pub async fn get_user(&self, member: &GuildMember) -> EconomyResult<User> {
         let doc_id = member.doc_id();
         let user: Option<User> = self.store.get(&doc_id).await?;
 
@@ -71,13 +127,16 @@ impl UserStore {
     }
 
     /// Initialize a user document with default values.
-    async fn init_user(&self, member: &GuildMember) -> anyhow::Result<User> {
+    async fn init_user(&self, member: &GuildMember) -> EconomyResult<User> {
         let doc_id = member.doc_id();
         let user = User {
             name: member.username.clone(),
             last_guess_date: None,
             last_sardines_date: None,
             reputation_offset: 0,
+            last_daily_date: None,
+            daily_streak: 0,
+            frozen: false,
         };
 
         debug!(doc_id, "Initializing new user document");
@@ -87,7 +146,7 @@ impl UserStore {
     }
 
     /// Get a user's total reputation (base from join date + offset).
-    pub async fn get_user_rep(&self, member: &GuildMember) -> anyhow::Result<i64> {
+    pub async fn get_user_rep(&self, member: &GuildMember) -> EconomyResult<i64> {
         let base_rep = calculate_rep_from_joined_date(member);
         let user = self.get_user(member).await?;
         Ok(base_rep + user.reputation_offset)
@@ -98,79 +157,245 @@ impl UserStore {
         &self,
         member: &GuildMember,
         offset: i64,
-    ) -> anyhow::Result<()> {
-        self.increment_user_reps(&[(member.clone(), offset)]).await
+        change: &RepChange,
+    ) -> EconomyResult<()> {
+        self.increment_user_reps(&[(member.clone(), offset)], change)
+            .await
     }
 
     /// Atomically increment reputation offsets for multiple users in a single transaction.
-    pub async fn increment_user_reps(&self, updates: &[(GuildMember, i64)]) -> anyhow::Result<()> {
+    /// Each increment also writes a ledger entry tagged with `change` in the same transaction.
+    pub async fn increment_user_reps(
+        &self,
+        updates: &[(GuildMember, i64)],
+        change: &RepChange,
+    ) -> EconomyResult<()> {
+        let updates = updates.to_vec();
+        let change = change.clone();
         self.store
-            .db()
-            .run_transaction(|db, tx| {
-                let updates = updates.to_vec();
+            .storage()
+            .run_transaction(move |tx| {
+                let updates = updates.clone();
+                let change = change.clone();
                 Box::pin(async move {
-                    // Read phase: fetch all users first
-                    let mut user_states: Vec<(String, GuildMember, i64, Option<User>)> = Vec::new();
-                    for (member, offset) in &updates {
-                        let doc_id = member.doc_id();
-                        let existing: Option<User> = db
-                            .fluent()
-                            .select()
-                            .by_id_in(COLLECTION)
-                            .obj()
-                            .one(&doc_id)
-                            .await?;
-                        user_states.push((doc_id, member.clone(), *offset, existing));
-                    }
+                    let existing = read_users(tx, &updates).await?;
+                    write_increments(tx, &updates, existing, &change)
+                })
+            })
+            .await?
+    }
 
-                    // Write phase: update only reputation_offset (and name) for existing users,
-                    // or create the full document for new users
-                    for (doc_id, member, offset, existing) in &user_states {
-                        match existing {
-                            Some(user) => {
-                                let updated = User {
-                                    reputation_offset: user.reputation_offset + offset,
-                                    name: member.username.clone(),
-                                    ..user.clone()
-                                };
-                                db.fluent()
-                                    .update()
-                                    .fields(paths_camel_case!(User::reputation_offset, User::name))
-                                    .in_col(COLLECTION)
-                                    .document_id(doc_id)
-                                    .object(&updated)
-                                    .add_to_transaction(tx)?;
-                            }
-                            None => {
-                                let new_user = User {
-                                    name: member.username.clone(),
-                                    last_guess_date: None,
-                                    last_sardines_date: None,
-                                    reputation_offset: *offset,
-                                };
-                                db.fluent()
-                                    .update()
-                                    .in_col(COLLECTION)
-                                    .document_id(doc_id)
-                                    .object(&new_user)
-                                    .add_to_transaction(tx)?;
-                            }
-                        }
+    /// Move `amount` from one member to another. The sender's balance is checked
+    /// inside the same transaction as the write, so concurrent sends cannot both
+    /// pass the check and overdraw the sender.
+    pub async fn transfer_rep(
+        &self,
+        from: &GuildMember,
+        to: &GuildMember,
+        amount: i64,
+        change: &RepChange,
+    ) -> EconomyResult<()> {
+        let updates = vec![(from.clone(), -amount), (to.clone(), amount)];
+        let change = change.clone();
+        self.store
+            .storage()
+            .run_transaction(move |tx| {
+                let updates = updates.clone();
+                let change = change.clone();
+                Box::pin(async move {
+                    let mut existing = read_users(tx, &updates).await?;
+                    let sender_offset = existing[0]
+                        .as_ref()
+                        .map(|u| u.reputation_offset)
+                        .unwrap_or(0);
+                    let balance = calculate_rep_from_joined_date(&updates[0].0) + sender_offset;
+                    if balance < amount {
+                        return Ok(Err(EconomyError::InsufficientFunds { balance, amount }));
+                    }
+                    // Check both sides before either is written
+                    if let Err(e) = next_offsets(&updates, &existing) {
+                        return Ok(Err(e));
                     }
+                    // Each side's ledger entry names the other
+                    let (sender, receiver) = (&updates[0].0, &updates[1].0);
+                    let receiver_existing = existing.split_off(1);
+                    let sent = change.clone().with_counterparty(receiver);
+                    if let Err(e) = write_increments(tx, &updates[..1], existing, &sent)? {
+                        return Ok(Err(e));
+                    }
+                    let received = change.clone().with_counterparty(sender);
+                    write_increments(tx, &updates[1..], receiver_existing, &received)
+                })
+            })
+            .await?
+    }
 
-                    Ok(())
+    /// Overwrite a user's reputation offset, recording the difference in the ledger.
+    pub async fn set_user_rep_offset(
+        &self,
+        member: &GuildMember,
+        offset: i64,
+        change: &RepChange,
+    ) -> EconomyResult<()> {
+        let member = member.clone();
+        let change = change.clone();
+        self.store
+            .storage()
+            .run_transaction(move |tx| {
+                let member = member.clone();
+                let change = change.clone();
+                Box::pin(async move {
+                    let probe = [(member.clone(), 0)];
+                    let existing = read_users(tx, &probe).await?;
+                    let current = existing[0]
+                        .as_ref()
+                        .map(|u| u.reputation_offset)
+                        .unwrap_or(0);
+                    let Some(difference) = offset.checked_sub(current) else {
+                        return Ok(Err(EconomyError::BalanceOverflow));
+                    };
+                    let updates = [(member, difference)];
+                    write_increments(tx, &updates, existing, &change)
                 })
             })
-            .await?;
+            .await?
+    }
 
+    /// Check whether an admin has frozen the user's ℞.
+    pub async fn is_user_frozen(&self, member: &GuildMember) -> EconomyResult<bool> {
+        let user = self.get_user(member).await?;
+        Ok(user.frozen)
+    }
+
+    /// Freeze or unfreeze the user's ℞.
+    pub async fn set_user_frozen(&self, member: &GuildMember, frozen: bool) -> EconomyResult<()> {
+        let doc_id = member.doc_id();
+        let user = self.get_user(member).await?;
+        let updated = User {
+            frozen,
+            name: member.username.clone(),
+            ..user
+        };
+        self.store
+            .storage()
+            .update_fields(
+                COLLECTION,
+                &doc_id,
+                &updated,
+                paths_camel_case!(User::frozen, User::name),
+            )
+            .await?;
         Ok(())
     }
 
+    /// Claim the member's daily allowance. The once-per-day check, streak update,
+    /// offset increment and ledger entry all happen in one transaction.
+    pub async fn claim_daily(
+        &self,
+        member: &GuildMember,
+        tz: Tz,
+        base_reward: i64,
+    ) -> EconomyResult<DailyClaim> {
+        let member = member.clone();
+        self.store
+            .storage()
+            .run_transaction(move |tx| {
+                let member = member.clone();
+                Box::pin(async move {
+                    let doc_id = member.doc_id();
+                    let user: User = tx.get(COLLECTION, &doc_id).await?.unwrap_or_else(|| User {
+                        name: member.username.clone(),
+                        ..User::default()
+                    });
+
+                    let Some(streak) = next_streak(tz, user.last_daily_date, user.daily_streak)
+                    else {
+                        return Ok(Err(EconomyError::DailyLimitReached {
+                            activity: "claimed your daily \u{211e}",
+                        }));
+                    };
+                    let reward = daily_reward(base_reward, streak);
+                    let Some(reputation_offset) = user.reputation_offset.checked_add(reward) else {
+                        return Ok(Err(EconomyError::BalanceOverflow));
+                    };
+
+                    let updated = User {
+                        name: member.username.clone(),
+                        reputation_offset,
+                        last_daily_date: Some(Utc::now()),
+                        daily_streak: streak,
+                        ..user
+                    };
+                    tx.update_fields(
+                        COLLECTION,
+                        &doc_id,
+                        &updated,
+                        paths_camel_case!(
+                            User::name,
+                            User::reputation_offset,
+                            User::last_daily_date,
+                            User::daily_streak
+                        ),
+                    )?;
+
+                    let entry =
+                        LedgerEntry::new(&member, reward, &RepChange::new(RepReason::DailyClaim));
+                    tx.update(LEDGER_COLLECTION, &entry.id, &entry)?;
+
+                    Ok(Ok(DailyClaim { reward, streak }))
+                })
+            })
+            .await?
+    }
+
+    /// Get a page of the member's ledger entries, newest first. Firestore
+    /// needs the composite index in firestore.indexes.json for this query.
+    pub async fn get_rep_history(
+        &self,
+        member: &GuildMember,
+        offset: u32,
+        limit: u32,
+    ) -> EconomyResult<Vec<LedgerEntry>> {
+        let query = Query::new(LEDGER_COLLECTION)
+            .eq(
+                path_camel_case!(LedgerEntry::guild_id),
+                member.guild_id.clone(),
+            )
+            .eq(path_camel_case!(LedgerEntry::user_id), member.id.clone())
+            .order_by_desc(path_camel_case!(LedgerEntry::created_at))
+            .offset(offset)
+            .limit(limit);
+        Ok(self.store.storage().query(&query).await?)
+    }
+
+    /// Get every stored reputation offset in a guild, keyed by user ID.
+    /// User documents are keyed "{guild_id}.{user_id}", so the guild is
+    /// selected by document ID range.
+    pub async fn get_guild_reputation_offsets(
+        &self,
+        guild_id: &str,
+    ) -> EconomyResult<HashMap<String, i64>> {
+        let prefix = format!("{guild_id}.");
+        let rows: Vec<(String, UserOffsetRow)> = self
+            .store
+            .storage()
+            .list_prefix(COLLECTION, &prefix)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(doc_id, row)| {
+                doc_id
+                    .strip_prefix(&prefix)
+                    .map(|user_id| (user_id.to_string(), row.reputation_offset))
+            })
+            .collect())
+    }
+
     /// Get the user's last guess date.
     pub async fn get_user_last_guess(
         &self,
         member: &GuildMember,
-    ) -> anyhow::Result<Option<DateTime<Utc>>> {
+    ) -> EconomyResult<Option<DateTime<Utc>>> {
         let user = self.get_user(member).await?;
         Ok(user.last_guess_date)
     }
@@ -180,7 +405,7 @@ impl UserStore {
         &self,
         member: &GuildMember,
         last_guess_date: DateTime<Utc>,
-    ) -> anyhow::Result<()> {
+    ) -> EconomyResult<()> {
         let doc_id = member.doc_id();
         let user = self.get_user(member).await?;
         let updated = User {
@@ -189,14 +414,13 @@ impl UserStore {
             ..user
         };
         self.store
-            .db()
-            .fluent()
-            .update()
-            .fields(paths_camel_case!(User::last_guess_date, User::name))
-            .in_col(COLLECTION)
-            .document_id(&doc_id)
-            .object(&updated)
-            .execute::<()>()
+            .storage()
+            .update_fields(
+                COLLECTION,
+                &doc_id,
+                &updated,
+                paths_camel_case!(User::last_guess_date, User::name),
+            )
             .await?;
         Ok(())
     }
@@ -205,7 +429,7 @@ impl UserStore {
     pub async fn get_user_last_sardines(
         &self,
         member: &GuildMember,
-    ) -> anyhow::Result<Option<DateTime<Utc>>> {
+    ) -> EconomyResult<Option<DateTime<Utc>>> {
         let user = self.get_user(member).await?;
         Ok(user.last_sardines_date)
     }
@@ -215,7 +439,7 @@ impl UserStore {
         &self,
         member: &GuildMember,
         last_sardines_date: DateTime<Utc>,
-    ) -> anyhow::Result<()> {
+    ) -> EconomyResult<()> {
         let doc_id = member.doc_id();
         let user = self.get_user(member).await?;
         let updated = User {
@@ -224,14 +448,13 @@ impl UserStore {
             ..user
         };
         self.store
-            .db()
-            .fluent()
-            .update()
-            .fields(paths_camel_case!(User::last_sardines_date, User::name))
-            .in_col(COLLECTION)
-            .document_id(&doc_id)
-            .object(&updated)
-            .execute::<()>()
+            .storage()
+            .update_fields(
+                COLLECTION,
+                &doc_id,
+                &updated,
+                paths_camel_case!(User::last_sardines_date, User::name),
+            )
             .await?;
         Ok(())
     }
@@ -239,22 +462,75 @@ impl UserStore {
 
 #[async_trait::async_trait]
 impl UserStoreApi for UserStore {
-    async fn get_user_rep(&self, member: &GuildMember) -> anyhow::Result<i64> {
+    async fn get_user_rep(&self, member: &GuildMember) -> EconomyResult<i64> {
         self.get_user_rep(member).await
     }
 
-    async fn increment_user_rep(&self, member: &GuildMember, offset: i64) -> anyhow::Result<()> {
-        self.increment_user_rep(member, offset).await
+    async fn increment_user_rep(
+        &self,
+        member: &GuildMember,
+        offset: i64,
+        change: &RepChange,
+    ) -> EconomyResult<()> {
+        self.increment_user_rep(member, offset, change).await
+    }
+
+    async fn transfer_rep(
+        &self,
+        from: &GuildMember,
+        to: &GuildMember,
+        amount: i64,
+        change: &RepChange,
+    ) -> EconomyResult<()> {
+        self.transfer_rep(from, to, amount, change).await
+    }
+
+    async fn set_user_rep_offset(
+        &self,
+        member: &GuildMember,
+        offset: i64,
+        change: &RepChange,
+    ) -> EconomyResult<()> {
+        self.set_user_rep_offset(member, offset, change).await
+    }
+
+    async fn is_user_frozen(&self, member: &GuildMember) -> EconomyResult<bool> {
+        self.is_user_frozen(member).await
+    }
+
+    async fn set_user_frozen(&self, member: &GuildMember, frozen: bool) -> EconomyResult<()> {
+        self.set_user_frozen(member, frozen).await
     }
 
-    async fn increment_user_reps(&self, updates: &[(GuildMember, i64)]) -> anyhow::Result<()> {
-        self.increment_user_reps(updates).await
+    async fn claim_daily(
+        &self,
+        member: &GuildMember,
+        tz: Tz,
+        base_reward: i64,
+    ) -> EconomyResult<DailyClaim> {
+        self.claim_daily(member, tz, base_reward).await
+    }
+
+    async fn get_rep_history(
+        &self,
+        member: &GuildMember,
+        offset: u32,
+        limit: u32,
+    ) -> EconomyResult<Vec<LedgerEntry>> {
+        self.get_rep_history(member, offset, limit).await
+    }
+
+    async fn get_guild_reputation_offsets(
+        &self,
+        guild_id: &str,
+    ) -> EconomyResult<HashMap<String, i64>> {
+        self.get_guild_reputation_offsets(guild_id).await
     }
 
     async fn get_user_last_guess(
         &self,
         member: &GuildMember,
-    ) -> anyhow::Result<Option<DateTime<Utc>>> {
+    ) -> EconomyResult<Option<DateTime<Utc>>> {
         self.get_user_last_guess(member).await
     }
 
@@ -262,14 +538,14 @@ impl UserStoreApi for UserStore {
         &self,
         member: &GuildMember,
         last_guess_date: DateTime<Utc>,
-    ) -> anyhow::Result<()> {
+    ) -> EconomyResult<()> {
         self.set_user_last_guess(member, last_guess_date).await
     }
 
     async fn get_user_last_sardines(
         &self,
         member: &GuildMember,
-    ) -> anyhow::Result<Option<DateTime<Utc>>> {
+    ) -> EconomyResult<Option<DateTime<Utc>>> {
         self.get_user_last_sardines(member).await
     }
 
@@ -277,13 +553,106 @@ impl UserStoreApi for UserStore {
         &self,
         member: &GuildMember,
         last_sardines_date: DateTime<Utc>,
-    ) -> anyhow::Result<()> {
-        self.set_user_last_sardines(member, last_sardines_date).await
+    ) -> EconomyResult<()> {
+        self.set_user_last_sardines(member, last_sardines_date)
+            .await
+    }
+}
+
+/// Apply `updates` and their ledger entries inside a transaction run by
+/// another store, e.g. a game payout committed together with the game's delete.
+/// On overflow nothing is written and the caller should write nothing either.
+pub async fn increment_in_transaction(
+    tx: &mut Transaction,
+    updates: &[(GuildMember, i64)],
+    change: &RepChange,
+) -> StorageResult<EconomyResult<()>> {
+    let existing = read_users(tx, updates).await?;
+    write_increments(tx, updates, existing, change)
+}
+
+/// Transaction read phase: fetch the current document for each member being updated.
+async fn read_users(
+    tx: &mut Transaction,
+    updates: &[(GuildMember, i64)],
+) -> StorageResult<Vec<Option<User>>> {
+    let mut existing = Vec::with_capacity(updates.len());
+    for (member, _) in updates {
+        existing.push(tx.get(COLLECTION, &member.doc_id()).await?);
     }
+    Ok(existing)
+}
+
+/// Each member's reputation offset after its increment, or `BalanceOverflow`
+/// if any of them would leave the range of an i64.
+fn next_offsets(
+    updates: &[(GuildMember, i64)],
+    existing: &[Option<User>],
+) -> EconomyResult<Vec<i64>> {
+    updates
+        .iter()
+        .zip(existing)
+        .map(|((_, offset), user)| {
+            let current = user.as_ref().map_or(0, |u| u.reputation_offset);
+            current
+                .checked_add(*offset)
+                .ok_or(EconomyError::BalanceOverflow)
+        })
+        .collect()
+}
+
+/// Transaction write phase: update only reputation_offset (and name) for existing users,
+/// or create the full document for new users, and record a ledger entry for each.
+/// Nothing is written if any offset would overflow.
+fn write_increments(
+    tx: &mut Transaction,
+    updates: &[(GuildMember, i64)],
+    existing: Vec<Option<User>>,
+    change: &RepChange,
+) -> StorageResult<EconomyResult<()>> {
+    let next = match next_offsets(updates, &existing) {
+        Ok(next) => next,
+        Err(e) => return Ok(Err(e)),
+    };
+    for (((member, offset), existing), reputation_offset) in updates.iter().zip(existing).zip(next)
+    {
+        let doc_id = member.doc_id();
+        match existing {
+            Some(user) => {
+                let updated = User {
+                    reputation_offset,
+                    name: member.username.clone(),
+                    ..user
+                };
+                tx.update_fields(
+                    COLLECTION,
+                    &doc_id,
+                    &updated,
+                    paths_camel_case!(User::reputation_offset, User::name),
+                )?;
+            }
+            None => {
+                let new_user = User {
+                    name: member.username.clone(),
+                    last_guess_date: None,
+                    last_sardines_date: None,
+                    reputation_offset: *offset,
+                    last_daily_date: None,
+                    daily_streak: 0,
+                    frozen: false,
+                };
+                tx.update(COLLECTION, &doc_id, &new_user)?;
+            }
+        }
+
+        let entry = LedgerEntry::new(member, *offset, change);
+        tx.update(LEDGER_COLLECTION, &entry.id, &entry)?;
+    }
+    Ok(Ok(()))
 }
 
 /// Calculate base reputation from how many days since the member joined the guild.
-fn calculate_rep_from_joined_date(member: &GuildMember) -> i64 {
+pub fn calculate_rep_from_joined_date(member: &GuildMember) -> i64 {
     match member.joined_at {
         Some(joined) => {
             let duration = Utc::now() - joined;
@@ -292,3 +661,109 @@ fn calculate_rep_from_joined_date(member: &GuildMember) -> i64 {
         None => 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::emulator;
+
+    fn member(id: &str) -> GuildMember {
+        GuildMember {
+            id: id.to_string(),
+            guild_id: "guild".to_string(),
+            username: format!("user-{id}"),
+            joined_at: None,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the Firestore emulator"]
+    async fn concurrent_increments_are_not_lost() {
+        let storage = emulator::storage().await;
+        let store = UserStore::new(storage);
+        let (alice, bob) = (member("alice"), member("bob"));
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let store = store.clone();
+                let updates = vec![(alice.clone(), 1), (bob.clone(), -1)];
+                tokio::spawn(async move {
+                    store
+                        .increment_user_reps(&updates, &RepChange::new(RepReason::AdminGrant))
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        assert_eq!(store.get_user_rep(&alice).await.unwrap(), 8);
+        assert_eq!(store.get_user_rep(&bob).await.unwrap(), -8);
+        let history = store.get_rep_history(&alice, 0, 20).await.unwrap();
+        assert_eq!(history.len(), 8);
+        assert!(history.iter().all(|e| e.delta == 1));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the Firestore emulator"]
+    async fn transfer_rejects_overdraft_without_writing() {
+        let storage = emulator::storage().await;
+        let store = UserStore::new(storage);
+        let (alice, bob) = (member("alice"), member("bob"));
+        let change = RepChange::new(RepReason::RepSend);
+
+        store.increment_user_rep(&alice, 5, &change).await.unwrap();
+        let err = store.transfer_rep(&alice, &bob, 6, &change).await;
+        assert!(matches!(
+            err,
+            Err(EconomyError::InsufficientFunds {
+                balance: 5,
+                amount: 6
+            })
+        ));
+        store.transfer_rep(&alice, &bob, 5, &change).await.unwrap();
+
+        assert_eq!(store.get_user_rep(&alice).await.unwrap(), 0);
+        assert_eq!(store.get_user_rep(&bob).await.unwrap(), 5);
+        assert_eq!(store.get_rep_history(&bob, 0, 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn transfer_entries_name_the_other_side() {
+        let store = UserStore::new(Storage::memory());
+        let (alice, bob) = (member("alice"), member("bob"));
+        let change = RepChange::new(RepReason::RepSend);
+
+        store.increment_user_rep(&alice, 5, &change).await.unwrap();
+        store.transfer_rep(&alice, &bob, 5, &change).await.unwrap();
+
+        let sent = &store.get_rep_history(&alice, 0, 1).await.unwrap()[0];
+        assert_eq!(sent.delta, -5);
+        assert_eq!(sent.counterparty.as_deref(), Some("user-bob"));
+        let received = &store.get_rep_history(&bob, 0, 1).await.unwrap()[0];
+        assert_eq!(received.counterparty.as_deref(), Some("user-alice"));
+    }
+
+    #[tokio::test]
+    async fn overflowing_transfers_write_nothing() {
+        let store = UserStore::new(Storage::memory());
+        let (alice, bob) = (member("alice"), member("bob"));
+        let change = RepChange::new(RepReason::AdminGrant);
+
+        store.increment_user_rep(&alice, 5, &change).await.unwrap();
+        store
+            .increment_user_rep(&bob, i64::MAX, &change)
+            .await
+            .unwrap();
+        let err = store
+            .transfer_rep(&alice, &bob, 5, &change)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, EconomyError::BalanceOverflow));
+
+        assert_eq!(store.get_user_rep(&alice).await.unwrap(), 5);
+        assert_eq!(store.get_user_rep(&bob).await.unwrap(), i64::MAX);
+        assert_eq!(store.get_rep_history(&alice, 0, 10).await.unwrap().len(), 1);
+    }
+}
@@ -9,11 +9,24 @@ pub fn is_today(tz: Tz, date: DateTime<Utc>) -> bool {
     today == other
 }
 
+/// Check if a UTC datetime is "yesterday" in the given timezone.
+pub fn is_yesterday(tz: Tz, date: DateTime<Utc>) -> bool {
+    let today = Utc::now().with_timezone(&tz).date_naive();
+    let other = date.with_timezone(&tz).date_naive();
+    today.pred_opt() == Some(other)
+}
+
 /// Format a UTC datetime as "YYYY-MM-DD" in the given timezone.
 pub fn get_day_string(tz: Tz, date: DateTime<Utc>) -> String {
     date.with_timezone(&tz).format("%Y-%m-%d").to_string()
 }
 
+/// The time `seconds` from now, or `None` if that is beyond what a datetime can hold.
+pub fn seconds_from_now(seconds: u64) -> Option<DateTime<Utc>> {
+    let seconds = i64::try_from(seconds).ok()?;
+    Utc::now().checked_add_signed(chrono::Duration::try_seconds(seconds)?)
+}
+
 /// Format a datetime as a human-readable distance from now (e.g. "3 days ago").
 pub fn format_distance_to_now(date: DateTime<Utc>) -> String {
     HumanTime::from(date).to_string()
@@ -1,7 +1,170 @@
+use chrono::NaiveDate;
 use rand::prelude::*;
 use rand_chacha::ChaCha8Rng;
+use ring::digest::{SHA256, digest};
+use serde::{Deserialize, Serialize};
 use std::collections::hash_map::DefaultHasher;
+#[cfg(test)]
+use std::collections::{HashMap, VecDeque};
 use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::games::fair;
+
+/// Where unseeded randomness comes from. The bot uses [`ThreadRandom`];
+/// [`SeededRandom`] replays a run from a seed and [`ScriptedRandom`] lets
+/// tests choose outcomes.
+pub trait RandomSource: Send + Sync {
+    /// A uniform value in `[0, 1)`.
+    fn next_f64(&self) -> f64;
+
+    /// A fresh secret seed for a new game (see `games::fair`).
+    fn server_seed(&self) -> String;
+
+    /// The roll a game makes for `label`. Real sources derive it from the
+    /// game's committed seed so the outcome can be verified later.
+    fn game_roll(&self, server_seed: &str, game_id: &str, label: &str) -> f64 {
+        fair::roll(server_seed, game_id, label)
+    }
+
+    /// A uniform integer between min and max, inclusive.
+    fn range_inclusive(&self, min: i64, max: i64) -> i64 {
+        let count = (max - min + 1) as usize;
+        min + fair::pick(self.next_f64(), count) as i64
+    }
+}
+
+/// The thread-local RNG and the system's secure randomness for game seeds.
+pub struct ThreadRandom;
+
+impl RandomSource for ThreadRandom {
+    fn next_f64(&self) -> f64 {
+        rand::random()
+    }
+
+    fn server_seed(&self) -> String {
+        fair::new_server_seed()
+    }
+
+    fn range_inclusive(&self, min: i64, max: i64) -> i64 {
+        random_inclusive(min, max)
+    }
+}
+
+/// Every draw and game seed comes from one seeded stream, so a run with the
+/// same seed and the same sequence of commands makes the same rolls.
+pub struct SeededRandom {
+    rng: Mutex<ChaCha8Rng>,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(ChaCha8Rng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl RandomSource for SeededRandom {
+    fn next_f64(&self) -> f64 {
+        self.rng.lock().expect("rng poisoned").r#gen()
+    }
+
+    fn server_seed(&self) -> String {
+        let bytes: [u8; 32] = self.rng.lock().expect("rng poisoned").r#gen();
+        hex::encode(bytes)
+    }
+}
+
+/// Plays back values chosen by a test. Game rolls are scripted by label (e.g.
+/// `join:3` or `winner:4`); any draw that was not scripted panics.
+#[cfg(test)]
+#[derive(Default)]
+pub struct ScriptedRandom {
+    draws: Mutex<VecDeque<f64>>,
+    rolls: Mutex<HashMap<String, f64>>,
+}
+
+#[cfg(test)]
+impl ScriptedRandom {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a value for the next unlabelled draw.
+    pub fn then(self, value: f64) -> Self {
+        self.draws.lock().expect("script poisoned").push_back(value);
+        self
+    }
+
+    /// Fix the roll every game makes for `label`.
+    pub fn roll(self, label: &str, value: f64) -> Self {
+        self.rolls
+            .lock()
+            .expect("script poisoned")
+            .insert(label.to_string(), value);
+        self
+    }
+}
+
+#[cfg(test)]
+impl RandomSource for ScriptedRandom {
+    fn next_f64(&self) -> f64 {
+        self.draws
+            .lock()
+            .expect("script poisoned")
+            .pop_front()
+            .expect("unscripted random draw")
+    }
+
+    fn server_seed(&self) -> String {
+        "scripted".to_string()
+    }
+
+    fn game_roll(&self, _server_seed: &str, _game_id: &str, label: &str) -> f64 {
+        *self
+            .rolls
+            .lock()
+            .expect("script poisoned")
+            .get(label)
+            .unwrap_or_else(|| panic!("unscripted game roll {label}"))
+    }
+}
+
+/// How a seed string and the base seed are turned into an RNG. Seeded values
+/// (daily magic numbers, sardines multipliers) only stay the same while the
+/// version does, so a version is never changed once released; new ones are
+/// added instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeedVersion {
+    /// `DefaultHasher` over `seed + base_seed`, truncated to a 64-bit ChaCha8
+    /// seed. Its output may change with the Rust toolchain; kept only so
+    /// values handed out before v2 stay consistent until the cutover.
+    V1,
+    /// SHA-256 over `seed`, a zero byte, then `base_seed`; the 32-byte digest
+    /// seeds ChaCha8 directly.
+    V2,
+}
+
+impl SeedVersion {
+    pub const CURRENT: Self = Self::V2;
+
+    /// The version for values tied to `day`. Days before `v2_from` keep v1 so
+    /// switching mid-day does not change answers already given out; without a
+    /// cutover every day uses the current version.
+    pub fn for_day(v2_from: Option<NaiveDate>, day: NaiveDate) -> Self {
+        match v2_from {
+            Some(cutover) if day < cutover => Self::V1,
+            _ => Self::CURRENT,
+        }
+    }
+
+    /// Documents saved before versions were recorded used v1.
+    pub fn legacy() -> Self {
+        Self::V1
+    }
+}
 
 /// Generate a random inclusive integer between min and max (unseeded).
 pub fn random_inclusive(min: i64, max: i64) -> i64 {
@@ -11,28 +174,127 @@ pub fn random_inclusive(min: i64, max: i64) -> i64 {
 
 /// Generate a random inclusive integer between min and max using a seeded RNG.
 /// The seed string is combined with `base_seed` to produce deterministic results.
-pub fn seeded_random_inclusive(min: i64, max: i64, seed: &str, base_seed: &str) -> i64 {
-    let mut rng = make_seeded_rng(seed, base_seed);
+pub fn seeded_random_inclusive(
+    min: i64,
+    max: i64,
+    seed: &str,
+    base_seed: &str,
+    version: SeedVersion,
+) -> i64 {
+    let mut rng = make_seeded_rng(seed, base_seed, version);
     rng.gen_range(min..=max)
 }
 
 /// Generate a weighted random number between min and max using a seeded RNG.
-pub fn seeded_weighted_random(min: i64, max: i64, seed: &str, base_seed: &str) -> i64 {
-    let mut rng = make_seeded_rng(seed, base_seed);
+pub fn seeded_weighted_random(
+    min: i64,
+    max: i64,
+    seed: &str,
+    base_seed: &str,
+    version: SeedVersion,
+) -> i64 {
+    let mut rng = make_seeded_rng(seed, base_seed, version);
     let rand: f64 = rng.r#gen();
     (max as f64 / (rand * max as f64 + min as f64)).round() as i64
 }
 
 /// Pick a random element from a slice using weighted seeded randomness (biased toward earlier elements).
-pub fn seeded_weighted_random_element<'a, T>(slice: &'a [T], seed: &str, base_seed: &str) -> &'a T {
-    let idx = seeded_weighted_random(1, slice.len() as i64, seed, base_seed) as usize - 1;
+pub fn seeded_weighted_random_element<'a, T>(
+    slice: &'a [T],
+    seed: &str,
+    base_seed: &str,
+    version: SeedVersion,
+) -> &'a T {
+    let idx = seeded_weighted_random(1, slice.len() as i64, seed, base_seed, version) as usize - 1;
     &slice[idx.min(slice.len() - 1)]
 }
 
-fn make_seeded_rng(seed: &str, base_seed: &str) -> ChaCha8Rng {
-    let combined = format!("{}{}", seed, base_seed);
-    let mut hasher = DefaultHasher::new();
-    combined.hash(&mut hasher);
-    let hash = hasher.finish();
-    ChaCha8Rng::seed_from_u64(hash)
+fn make_seeded_rng(seed: &str, base_seed: &str, version: SeedVersion) -> ChaCha8Rng {
+    match version {
+        SeedVersion::V1 => {
+            let combined = format!("{}{}", seed, base_seed);
+            let mut hasher = DefaultHasher::new();
+            combined.hash(&mut hasher);
+            let hash = hasher.finish();
+            ChaCha8Rng::seed_from_u64(hash)
+        }
+        SeedVersion::V2 => {
+            let input = [seed.as_bytes(), &[0], base_seed.as_bytes()].concat();
+            let mut key = [0u8; 32];
+            key.copy_from_slice(digest(&SHA256, &input).as_ref());
+            ChaCha8Rng::from_seed(key)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    // Golden values: if these change, every member's magic number and every
+    // sardines multiplier changes with them. Add a new version instead.
+    #[test]
+    fn v2_values_never_change() {
+        let magic: Vec<i64> = ["alice:2024-01-01", "alice:2024-01-02", "bob:2024-01-01"]
+            .iter()
+            .map(|seed| seeded_random_inclusive(1, 100, seed, "base", SeedVersion::V2))
+            .collect();
+        assert_eq!(magic, vec![30, 91, 31]);
+
+        let multipliers = [1.2, 1.5, 1.8, 2.0, 2.5];
+        let picked: Vec<f64> = ["game1", "game2", "game3"]
+            .iter()
+            .map(|id| *seeded_weighted_random_element(&multipliers, id, "base", SeedVersion::V2))
+            .collect();
+        assert_eq!(picked, vec![1.2, 1.2, 2.5]);
+    }
+
+    #[test]
+    fn v2_separates_seed_from_base_seed() {
+        let joined = seeded_random_inclusive(1, 1_000_000, "ab", "c", SeedVersion::V2);
+        let shifted = seeded_random_inclusive(1, 1_000_000, "a", "bc", SeedVersion::V2);
+        assert_ne!(joined, shifted);
+    }
+
+    #[test]
+    fn seeded_sources_replay_the_same_draws() {
+        let a = SeededRandom::new(7);
+        let b = SeededRandom::new(7);
+        assert_eq!(a.server_seed(), b.server_seed());
+        for _ in 0..10 {
+            assert_eq!(a.range_inclusive(1, 6), b.range_inclusive(1, 6));
+        }
+    }
+
+    #[test]
+    fn scripted_sources_play_back_in_order() {
+        let random = ScriptedRandom::new()
+            .then(0.0)
+            .then(0.99)
+            .roll("winner:2", 0.7);
+        assert_eq!(random.range_inclusive(1, 6), 1);
+        assert_eq!(random.range_inclusive(1, 6), 6);
+        assert_eq!(random.game_roll("seed", "game", "winner:2"), 0.7);
+    }
+
+    #[test]
+    fn days_before_the_cutover_keep_v1() {
+        let cutover = Some(day("2024-06-02"));
+        assert_eq!(
+            SeedVersion::for_day(cutover, day("2024-06-01")),
+            SeedVersion::V1
+        );
+        assert_eq!(
+            SeedVersion::for_day(cutover, day("2024-06-02")),
+            SeedVersion::V2
+        );
+        assert_eq!(
+            SeedVersion::for_day(None, day("2024-06-01")),
+            SeedVersion::V2
+        );
+    }
 }